@@ -105,5 +105,36 @@ fn add_benchmark(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, add_benchmark);
+// Objects with many keys, the lookup by key uses binary search with the version header.
+fn add_wide_object_benchmark(c: &mut Criterion) {
+    for size in [16, 256, 4096, 65536] {
+        let mut obj = serde_json::Map::new();
+        for i in 0..size {
+            obj.insert(format!("key{}", i), format!("value{}", i).into());
+        }
+        let bytes = serde_json::to_vec(&serde_json::Value::Object(obj)).unwrap();
+
+        let val = jsonb::parse_value(&bytes).unwrap();
+        let jsonb_bytes = val.to_vec_with_version();
+
+        let key = format!("key{}", size / 2);
+        let expected = format!("value{}", size / 2);
+        let paths = vec![key.as_str()];
+
+        c.bench_function(&format!("jsonb get wide object {} keys", size), |b| {
+            b.iter(|| jsonb_get(&jsonb_bytes, &paths, &expected))
+        });
+
+        c.bench_function(
+            &format!("jsonb get_by_name wide object {} keys", size),
+            |b| b.iter(|| jsonb::get_by_name(&jsonb_bytes, &key, false).unwrap()),
+        );
+
+        c.bench_function(&format!("serde_json get wide object {} keys", size), |b| {
+            b.iter(|| serde_json_get(&bytes, &paths, &expected))
+        });
+    }
+}
+
+criterion_group!(benches, add_benchmark, add_wide_object_benchmark);
 criterion_main!(benches);
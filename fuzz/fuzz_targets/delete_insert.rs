@@ -0,0 +1,46 @@
+// Copyright 2024 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_main]
+
+use std::collections::BTreeSet;
+
+use jsonb::keypath::parse_key_paths;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let (value, new_value) = data.split_at(data.len() / 2);
+    let index = data.first().map(|b| *b as i32 - 128).unwrap_or_default();
+    let keys: BTreeSet<&str> = ["a", "b"].into_iter().collect();
+    let key_paths = parse_key_paths(b"{a,0,b}").unwrap();
+
+    let mut buf = Vec::new();
+    let _ = jsonb::delete_by_name(value, "a", &mut buf);
+    buf.clear();
+    let _ = jsonb::delete_by_index(value, index, &mut buf);
+    buf.clear();
+    let _ = jsonb::delete_by_keypath(value, key_paths.paths.iter(), &mut buf);
+    buf.clear();
+    let _ = jsonb::object_delete(value, &keys, &mut buf);
+    buf.clear();
+    let _ = jsonb::object_pick(value, &keys, &mut buf);
+    buf.clear();
+    let _ = jsonb::array_insert(value, index, new_value, &mut buf);
+    buf.clear();
+    let _ = jsonb::object_insert(value, "a", new_value, true, &mut buf);
+    buf.clear();
+    let _ = jsonb::object_insert(value, "b", new_value, false, &mut buf);
+    buf.clear();
+    let _ = jsonb::strip_nulls(value, &mut buf);
+});
@@ -0,0 +1,31 @@
+// Copyright 2024 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let valid = jsonb::validate(data).is_ok();
+    match jsonb::from_slice_checked(data) {
+        Ok(value) => {
+            let _ = value.to_vec();
+        }
+        Err(_) => assert!(!valid, "validated bytes must be decodable"),
+    }
+    let _ = jsonb::to_string(data);
+    let _ = jsonb::to_serde_json(data);
+    let mut buf = Vec::new();
+    jsonb::convert_to_comparable(data, &mut buf);
+});
@@ -0,0 +1,36 @@
+// Copyright 2024 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_main]
+
+use jsonb::jsonpath::parse_json_path;
+use libfuzzer_sys::fuzz_target;
+
+// The first line of the input is the `JSONPath`, the rest is the value.
+fuzz_target!(|data: &[u8]| {
+    let Some(pos) = data.iter().position(|b| *b == b'\n') else {
+        return;
+    };
+    let (path, value) = (&data[..pos], &data[pos + 1..]);
+    let Ok(json_path) = parse_json_path(path) else {
+        return;
+    };
+    let mut buf = Vec::new();
+    let mut offsets = Vec::new();
+    let _ = jsonb::get_by_path(value, json_path.clone(), &mut buf, &mut offsets);
+    let _ = jsonb::get_by_path_first(value, json_path.clone(), &mut buf, &mut offsets);
+    let _ = jsonb::get_by_path_array(value, json_path.clone(), &mut buf, &mut offsets);
+    let _ = jsonb::path_exists(value, json_path.clone());
+    let _ = jsonb::path_match(value, json_path);
+});
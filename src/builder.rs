@@ -17,8 +17,11 @@ use std::collections::BTreeMap;
 use byteorder::{BigEndian, WriteBytesExt};
 
 use crate::{
-    constants::{ARRAY_CONTAINER_TAG, OBJECT_CONTAINER_TAG},
+    constants::{ARRAY_CONTAINER_TAG, CONTAINER_TAG, OBJECT_CONTAINER_TAG},
+    error::Error,
+    format::extend_without_offsets,
     jentry::JEntry,
+    ser::make_container_header,
 };
 
 enum Entry<'a> {
@@ -50,19 +53,19 @@ impl<'a> ArrayBuilder<'a> {
         self.entries.push(Entry::ObjectBuilder(builder));
     }
 
-    pub(crate) fn build_into(self, buf: &mut Vec<u8>) -> usize {
-        let header = ARRAY_CONTAINER_TAG | self.entries.len() as u32;
-        buf.write_u32::<BigEndian>(header).unwrap();
+    pub(crate) fn build_into(self, buf: &mut Vec<u8>) -> Result<usize, Error> {
+        let header = make_container_header(ARRAY_CONTAINER_TAG, self.entries.len())?;
+        buf.write_u32::<BigEndian>(header)?;
 
         let mut array_len = 4 + self.entries.len() * 4;
         let mut jentry_index = reserve_jentries(buf, self.entries.len() * 4);
 
         for entry in self.entries.into_iter() {
-            let jentry = write_entry(buf, entry);
+            let jentry = write_entry(buf, entry)?;
             array_len += jentry.length as usize;
             replace_jentry(buf, jentry, &mut jentry_index);
         }
-        array_len
+        Ok(array_len)
     }
 }
 
@@ -89,9 +92,9 @@ impl<'a> ObjectBuilder<'a> {
         self.entries.insert(key, Entry::ObjectBuilder(builder));
     }
 
-    pub(crate) fn build_into(self, buf: &mut Vec<u8>) -> usize {
-        let header = OBJECT_CONTAINER_TAG | self.entries.len() as u32;
-        buf.write_u32::<BigEndian>(header).unwrap();
+    pub(crate) fn build_into(self, buf: &mut Vec<u8>) -> Result<usize, Error> {
+        let header = make_container_header(OBJECT_CONTAINER_TAG, self.entries.len())?;
+        buf.write_u32::<BigEndian>(header)?;
 
         let mut object_len = 4 + self.entries.len() * 8;
         let mut jentry_index = reserve_jentries(buf, self.entries.len() * 8);
@@ -100,32 +103,36 @@ impl<'a> ObjectBuilder<'a> {
             let key_len = key.len();
             object_len += key_len;
             buf.extend_from_slice(key.as_bytes());
-            let jentry = JEntry::make_string_jentry(key_len);
-            replace_jentry(buf, jentry, &mut jentry_index)
+            let jentry = JEntry::make_string_jentry(key_len)?;
+            replace_jentry(buf, jentry, &mut jentry_index);
         }
 
         for (_, entry) in self.entries.into_iter() {
-            let jentry = write_entry(buf, entry);
+            let jentry = write_entry(buf, entry)?;
             object_len += jentry.length as usize;
             replace_jentry(buf, jentry, &mut jentry_index);
         }
-        object_len
+        Ok(object_len)
     }
 }
 
-fn write_entry(buf: &mut Vec<u8>, entry: Entry<'_>) -> JEntry {
+fn write_entry(buf: &mut Vec<u8>, entry: Entry<'_>) -> Result<JEntry, Error> {
     match entry {
         Entry::ArrayBuilder(builder) => {
-            let size = builder.build_into(buf);
+            let size = builder.build_into(buf)?;
             JEntry::make_container_jentry(size)
         }
         Entry::ObjectBuilder(builder) => {
-            let size = builder.build_into(buf);
+            let size = builder.build_into(buf)?;
             JEntry::make_container_jentry(size)
         }
         Entry::Raw(jentry, data) => {
-            buf.extend_from_slice(data);
-            jentry
+            if jentry.type_code == CONTAINER_TAG {
+                extend_without_offsets(buf, data);
+            } else {
+                buf.extend_from_slice(data);
+            }
+            Ok(jentry)
         }
     }
 }
@@ -162,7 +169,7 @@ mod tests {
             inner_array_builder.push_raw(JEntry::make_false_jentry(), &[]);
 
             builder.push_array("arr", inner_array_builder);
-            builder.build_into(&mut from_builder);
+            builder.build_into(&mut from_builder).unwrap();
         }
         let mut from_encoder = Vec::new();
         {
@@ -181,7 +188,7 @@ mod tests {
             inner_obj_builder.push_raw("field", JEntry::make_true_jentry(), &[]);
 
             builder.push_object("obj", inner_obj_builder);
-            builder.build_into(&mut from_builder);
+            builder.build_into(&mut from_builder).unwrap();
         }
         let mut from_encoder = Vec::new();
         {
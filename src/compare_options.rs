@@ -0,0 +1,266 @@
+// Copyright 2024 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use super::constants::*;
+use super::error::Error;
+use super::util::comparable_decode_bytes;
+use super::util::comparable_encode_bytes;
+
+/// Where `JSON` null is placed relative to the values of other types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NullOrder {
+    /// Null is greater than all the other values.
+    #[default]
+    Last,
+    /// Null is less than all the other values.
+    First,
+}
+
+/// How strings and object keys are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Collation {
+    /// Compare by the UTF-8 bytes, which is the order of the code points.
+    #[default]
+    Binary,
+    /// Compare the lowercase strings, strings that only differ in case are equal.
+    CaseInsensitive,
+    /// Compare the strings ignoring case, accents and compatibility differences,
+    /// such as `"a" < "Á" < "b"`, without rules of any specific locale.
+    /// Strings equal in this way are ordered by their bytes,
+    /// so only identical strings are equal.
+    Unicode,
+}
+
+/// The order of values with different types, from the greatest to the least.
+/// The extension types are `Binary`, `Date`, `TimestampTz` and `Uuid`.
+/// `true` is always greater than `false`, null is placed by the `NullOrder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TypeOrder {
+    /// Array > Object > Extension > String > Number > Boolean.
+    #[default]
+    Jsonb,
+    /// Object > Array > Boolean > Number > String > Extension,
+    /// the order of the types in the `jsonb` type of PostgreSQL when nulls are first.
+    /// Unlike PostgreSQL, which compares arrays and objects by the number of elements first,
+    /// arrays and objects are still compared element by element.
+    Postgres,
+    /// Array > Object > Extension > String > Boolean > Number,
+    /// the same as the `VARIANT` type of Snowflake.
+    Snowflake,
+}
+
+/// Options to control how `JSONB` values are compared and converted to comparable bytes.
+/// The default options are the same as `compare` and `convert_to_comparable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompareOptions {
+    pub nulls: NullOrder,
+    pub collation: Collation,
+    pub type_order: TypeOrder,
+    /// The maximum nesting depth of arrays and objects, deeper values fail
+    /// with `Error::RecursionLimitExceeded`. Defaults to 128.
+    /// The comparable bytes store the depth in a byte, so at most 255 levels
+    /// are converted, the arrays and objects nested deeper are written without elements.
+    pub max_depth: usize,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            nulls: NullOrder::default(),
+            collation: Collation::default(),
+            type_order: TypeOrder::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+impl CompareOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options for the type order of the `jsonb` type in PostgreSQL, null is the least value.
+    /// See [`TypeOrder::Postgres`] for the differences from PostgreSQL.
+    pub fn postgres() -> Self {
+        Self::default()
+            .with_nulls(NullOrder::First)
+            .with_type_order(TypeOrder::Postgres)
+    }
+
+    /// Options for the order of the `VARIANT` type in Snowflake.
+    pub fn snowflake() -> Self {
+        Self::default().with_type_order(TypeOrder::Snowflake)
+    }
+
+    pub fn with_nulls(mut self, nulls: NullOrder) -> Self {
+        self.nulls = nulls;
+        self
+    }
+
+    pub fn with_collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
+    pub fn with_type_order(mut self, type_order: TypeOrder) -> Self {
+        self.type_order = type_order;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    // Map the default level, from `NULL_LEVEL` to `FALSE_LEVEL`, to the level of these options.
+    // The default options keep the levels unchanged, so the comparable bytes are compatible.
+    pub(crate) fn level(&self, level: u8) -> u8 {
+        if level == NULL_LEVEL {
+            return match self.nulls {
+                NullOrder::Last => NULL_LEVEL,
+                NullOrder::First => FALSE_LEVEL,
+            };
+        }
+        // from the greatest to the least
+        let levels = match self.type_order {
+            TypeOrder::Jsonb => [
+                ARRAY_LEVEL,
+                OBJECT_LEVEL,
+                EXTENSION_LEVEL,
+                STRING_LEVEL,
+                NUMBER_LEVEL,
+                TRUE_LEVEL,
+                FALSE_LEVEL,
+            ],
+            TypeOrder::Postgres => [
+                OBJECT_LEVEL,
+                ARRAY_LEVEL,
+                TRUE_LEVEL,
+                FALSE_LEVEL,
+                NUMBER_LEVEL,
+                STRING_LEVEL,
+                EXTENSION_LEVEL,
+            ],
+            TypeOrder::Snowflake => [
+                ARRAY_LEVEL,
+                OBJECT_LEVEL,
+                EXTENSION_LEVEL,
+                STRING_LEVEL,
+                TRUE_LEVEL,
+                FALSE_LEVEL,
+                NUMBER_LEVEL,
+            ],
+        };
+        let Some(pos) = levels.iter().position(|l| *l == level) else {
+            return INVALID_LEVEL;
+        };
+        let level = ARRAY_LEVEL - pos as u8;
+        match self.nulls {
+            NullOrder::Last => level,
+            NullOrder::First => level + 1,
+        }
+    }
+
+    // Compare the encoded strings, the binary collation compares the bytes directly,
+    // the others fail with `Error::InvalidJsonb` if the strings are not valid UTF-8.
+    pub(crate) fn compare_str(&self, left: &[u8], right: &[u8]) -> Result<Ordering, Error> {
+        if self.collation == Collation::Binary {
+            return Ok(left.cmp(right));
+        }
+        let left = std::str::from_utf8(left).map_err(|_| Error::InvalidJsonb)?;
+        let right = std::str::from_utf8(right).map_err(|_| Error::InvalidJsonb)?;
+        let ord = match self.collation {
+            Collation::CaseInsensitive => lowercase_chars(left).cmp(lowercase_chars(right)),
+            _ => primary_chars(left)
+                .cmp(primary_chars(right))
+                .then_with(|| left.cmp(right)),
+        };
+        Ok(ord)
+    }
+
+    // Map the level of these options back to the default level.
+    pub(crate) fn default_level(&self, level: u8) -> Option<u8> {
+        (FALSE_LEVEL..=NULL_LEVEL).find(|l| self.level(*l) == level)
+    }
+
+    // Encode the string to comparable bytes with the same order as `compare_str`,
+    // the original string is appended to `types` if the comparable bytes lose it.
+    // Strings that are not valid UTF-8 are skipped like the other corrupted values,
+    // except for the binary collation which only uses the bytes.
+    pub(crate) fn comparable_encode_str(
+        &self,
+        value: &[u8],
+        buf: &mut Vec<u8>,
+        types: &mut Vec<u8>,
+    ) {
+        let value = match std::str::from_utf8(value) {
+            Ok(value) => value,
+            Err(_) if self.collation == Collation::Binary => {
+                return comparable_encode_bytes(value, buf);
+            }
+            Err(_) => return,
+        };
+        match self.collation {
+            Collation::Binary => comparable_encode_bytes(value.as_bytes(), buf),
+            Collation::CaseInsensitive => {
+                let lowercase: String = lowercase_chars(value).collect();
+                comparable_encode_bytes(lowercase.as_bytes(), buf);
+                comparable_encode_bytes(value.as_bytes(), types);
+            }
+            Collation::Unicode => {
+                let primary: String = primary_chars(value).collect();
+                comparable_encode_bytes(primary.as_bytes(), buf);
+                comparable_encode_bytes(value.as_bytes(), buf);
+            }
+        }
+    }
+
+    // Decode the string written by `comparable_encode_str` and advance the inputs.
+    pub(crate) fn comparable_decode_str(
+        &self,
+        key: &mut &[u8],
+        types: &mut &[u8],
+    ) -> Result<String, Error> {
+        let value = match self.collation {
+            Collation::Binary => comparable_decode_bytes(key)?,
+            Collation::CaseInsensitive => {
+                comparable_decode_bytes(key)?;
+                comparable_decode_bytes(types)?
+            }
+            Collation::Unicode => {
+                comparable_decode_bytes(key)?;
+                comparable_decode_bytes(key)?
+            }
+        };
+        String::from_utf8(value).map_err(|_| Error::InvalidUtf8)
+    }
+}
+
+fn lowercase_chars(value: &str) -> impl Iterator<Item = char> + '_ {
+    value.chars().flat_map(char::to_lowercase)
+}
+
+// The characters compared at the primary strength, the compatibility decomposition
+// without the combining marks, such as accents, in lowercase.
+fn primary_chars(value: &str) -> impl Iterator<Item = char> + '_ {
+    value
+        .nfkd()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !is_combining_mark(*c))
+}
@@ -24,6 +24,12 @@ pub(crate) const SCALAR_CONTAINER_TAG: u32 = 0x20000000;
 pub(crate) const CONTAINER_HEADER_TYPE_MASK: u32 = 0xE0000000;
 pub(crate) const CONTAINER_HEADER_LEN_MASK: u32 = 0x1FFFFFFF;
 
+// JSONB version header constants
+pub(crate) const JSONB_MAGIC_0: u8 = 0xFF;
+pub(crate) const JSONB_MAGIC_1: u8 = b'J';
+pub(crate) const JSONB_MAGIC_2: u8 = b'B';
+pub(crate) const JSONB_VERSION_HEADER_LEN: usize = 4;
+
 // JSONB JEntry constants
 pub(crate) const NULL_TAG: u32 = 0x00000000;
 pub(crate) const STRING_TAG: u32 = 0x10000000;
@@ -31,6 +37,7 @@ pub(crate) const NUMBER_TAG: u32 = 0x20000000;
 pub(crate) const FALSE_TAG: u32 = 0x30000000;
 pub(crate) const TRUE_TAG: u32 = 0x40000000;
 pub(crate) const CONTAINER_TAG: u32 = 0x50000000;
+pub(crate) const EXTENSION_TAG: u32 = 0x60000000;
 
 // JSONB number constants
 pub(crate) const NUMBER_ZERO: u8 = 0x00;
@@ -40,12 +47,24 @@ pub(crate) const NUMBER_NEG_INF: u8 = 0x30;
 pub(crate) const NUMBER_INT: u8 = 0x40;
 pub(crate) const NUMBER_UINT: u8 = 0x50;
 pub(crate) const NUMBER_FLOAT: u8 = 0x60;
+pub(crate) const NUMBER_DECIMAL: u8 = 0x70;
+pub(crate) const NUMBER_RAW: u8 = 0x80;
+
+// JSONB extension constants
+pub(crate) const EXTENSION_BINARY: u8 = 0x00;
+pub(crate) const EXTENSION_DATE: u8 = 0x10;
+pub(crate) const EXTENSION_TIMESTAMP_TZ: u8 = 0x20;
+pub(crate) const EXTENSION_UUID: u8 = 0x30;
 
-// @todo support offset mode
-#[allow(dead_code)]
 pub(crate) const JENTRY_IS_OFF_FLAG: u32 = 0x80000000;
 pub(crate) const JENTRY_TYPE_MASK: u32 = 0x70000000;
 pub(crate) const JENTRY_OFF_LEN_MASK: u32 = 0x0FFFFFFF;
+// Every `JENTRY_OFFSET_STRIDE`th JEntry of a container stores an offset instead of a length
+pub(crate) const JENTRY_OFFSET_STRIDE: usize = 32;
+// Objects with at least this many keys are searched by binary search
+pub(crate) const OBJECT_KEY_SEARCH_THRESHOLD: usize = 32;
+// Default limit of nested arrays and objects
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 128;
 
 // JSON text constants
 pub(crate) const UNICODE_LEN: usize = 4;
@@ -61,18 +80,38 @@ pub(crate) const RR: char = '\x0D'; // \r Carriage Return
 pub(crate) const TT: char = '\x09'; // \t Horizontal Tab
 
 // JSONB value compare level
-pub(crate) const NULL_LEVEL: u8 = 7;
-pub(crate) const ARRAY_LEVEL: u8 = 6;
-pub(crate) const OBJECT_LEVEL: u8 = 5;
+pub(crate) const NULL_LEVEL: u8 = 8;
+pub(crate) const ARRAY_LEVEL: u8 = 7;
+pub(crate) const OBJECT_LEVEL: u8 = 6;
+pub(crate) const EXTENSION_LEVEL: u8 = 5;
 pub(crate) const STRING_LEVEL: u8 = 4;
 pub(crate) const NUMBER_LEVEL: u8 = 3;
 pub(crate) const TRUE_LEVEL: u8 = 2;
 pub(crate) const FALSE_LEVEL: u8 = 1;
 pub(crate) const INVALID_LEVEL: u8 = 0;
 
+// JSONB comparable format version, the first byte of the comparable vector,
+// the vectors written before the format was versioned start with a zero depth byte.
+pub(crate) const COMPARABLE_FORMAT_VERSION: u8 = 1;
+
+// JSONB comparable number sign markers
+pub(crate) const NUMBER_KEY_NEG_INF: u8 = 0x01;
+pub(crate) const NUMBER_KEY_NEG: u8 = 0x02;
+pub(crate) const NUMBER_KEY_ZERO: u8 = 0x03;
+pub(crate) const NUMBER_KEY_POS: u8 = 0x04;
+pub(crate) const NUMBER_KEY_INF: u8 = 0x05;
+pub(crate) const NUMBER_KEY_NAN: u8 = 0x06;
+
 pub(crate) const TYPE_STRING: &str = "string";
 pub(crate) const TYPE_NULL: &str = "null";
 pub(crate) const TYPE_BOOLEAN: &str = "boolean";
 pub(crate) const TYPE_NUMBER: &str = "number";
 pub(crate) const TYPE_ARRAY: &str = "array";
 pub(crate) const TYPE_OBJECT: &str = "object";
+pub(crate) const TYPE_BINARY: &str = "binary";
+pub(crate) const TYPE_DATE: &str = "date";
+pub(crate) const TYPE_TIMESTAMP_TZ: &str = "timestamp_tz";
+pub(crate) const TYPE_UUID: &str = "uuid";
+
+// the chunk size to read from the `Read` input
+pub(crate) const READ_CHUNK_SIZE: usize = 8 * 1024;
@@ -20,9 +20,15 @@ use byteorder::ReadBytesExt;
 
 use super::constants::*;
 use super::error::*;
+use super::extension::ExtensionValue;
+use super::format::detect_format;
+use super::format::strip_version_header;
+use super::format::Format;
+use super::format::JSONB_VERSION;
 use super::jentry::JEntry;
 use super::number::Number;
-use super::parser::parse_value;
+use super::parser::parse_value_with_options;
+use super::parser::ParseOptions;
 use super::value::Object;
 use super::value::Value;
 
@@ -30,12 +36,12 @@ use super::value::Value;
 /// This structure can be nested. Each group of structures starts with a `Header`.
 /// The upper-level `Value` will store the `Header` length or offset of
 /// the lower-level `Value`.
-
+///
 /// `Header` stores the type of the `Value`, include `Array`, `Object` and `Scalar`,
 /// `Scalar` has only one `Value`, and a corresponding `JEntry`.
 /// `Array` and `Object` are nested type, they have multiple lower-level `Values`.
 /// So the `Header` also stores the number of lower-level `Values`.
-
+///
 /// `JEntry` stores the types of `Scalar Value`, including `Null`, `True`, `False`,
 /// `Number`, `String` and `Container`. They have three different decode methods.
 /// 1. `Null`, `True` and `False` can be obtained by `JEntry`, no extra work required.
@@ -53,12 +59,47 @@ use super::value::Value;
 ///    Decode can be executed recursively.
 ///
 ///    Decode `JSONB` Value from binary bytes.
+///
+///    **The input must be trusted.** The strings and keys are borrowed as `&str` without
+///    checking that they are valid UTF-8, so corrupted or malicious bytes produce strings
+///    that break the UTF-8 guarantee of `str`, which is undefined behavior when they are used.
+///    Only pass bytes written by this crate, and use [`from_slice_checked`]
+///    for the bytes read from untrusted sources.
 pub fn from_slice(buf: &[u8]) -> Result<Value<'_>, Error> {
-    let mut decoder = Decoder::new(buf);
+    from_slice_with_max_depth(buf, DEFAULT_MAX_DEPTH)
+}
+
+/// Decode `JSONB` Value from untrusted binary bytes,
+/// strings and keys that are not valid UTF-8 fail with `Error::InvalidUtf8`.
+pub fn from_slice_checked(buf: &[u8]) -> Result<Value<'_>, Error> {
+    let decoder = Decoder::new(buf).with_utf8_check();
+    decode_or_parse(decoder, buf, DEFAULT_MAX_DEPTH)
+}
+
+/// Decode `JSONB` Value from binary bytes, arrays and objects nested deeper than
+/// `max_depth` fail with `Error::RecursionLimitExceeded` instead of overflowing the stack.
+/// `JSON` text is parsed with the same depth limit.
+/// The input must be trusted the same as [`from_slice`].
+pub fn from_slice_with_max_depth(buf: &[u8], max_depth: usize) -> Result<Value<'_>, Error> {
+    let decoder = Decoder::with_max_depth(buf, max_depth);
+    decode_or_parse(decoder, buf, max_depth)
+}
+
+fn decode_or_parse<'a>(
+    mut decoder: Decoder<'a>,
+    buf: &'a [u8],
+    max_depth: usize,
+) -> Result<Value<'a>, Error> {
     match decoder.decode() {
         Ok(value) => Ok(value),
+        Err(
+            err @ (Error::InvalidJsonbVersion | Error::RecursionLimitExceeded | Error::InvalidUtf8),
+        ) => Err(err),
         // for compatible with the first version of `JSON` text, parse it again
-        Err(_) => parse_value(buf),
+        Err(_) => {
+            let opts = ParseOptions::default().with_max_depth(max_depth);
+            parse_value_with_options(buf, &opts)
+        }
     }
 }
 
@@ -67,17 +108,40 @@ pub fn parse_jsonb(buf: &[u8]) -> Result<Value<'_>, Error> {
     decoder.decode()
 }
 
-#[repr(transparent)]
 pub struct Decoder<'a> {
     buf: &'a [u8],
+    depth: usize,
+    max_depth: usize,
+    check_utf8: bool,
 }
 
 impl<'a> Decoder<'a> {
     pub fn new(buf: &'a [u8]) -> Decoder<'a> {
-        Self { buf }
+        Self::with_max_depth(buf, DEFAULT_MAX_DEPTH)
+    }
+
+    pub fn with_max_depth(buf: &'a [u8], max_depth: usize) -> Decoder<'a> {
+        Self {
+            buf,
+            depth: 0,
+            max_depth,
+            check_utf8: false,
+        }
+    }
+
+    /// Validate the strings and keys are UTF-8 instead of trusting the bytes.
+    pub fn with_utf8_check(mut self) -> Decoder<'a> {
+        self.check_utf8 = true;
+        self
     }
 
     pub fn decode(&mut self) -> Result<Value<'a>, Error> {
+        match detect_format(self.buf) {
+            Format::VersionedJsonb(version) if !(1..=JSONB_VERSION).contains(&version) => {
+                return Err(Error::InvalidJsonbVersion);
+            }
+            _ => self.buf = strip_version_header(self.buf),
+        }
         // Valid `JSONB` Value has at least one `Header`
         if self.buf.len() < 4 {
             return Err(Error::InvalidJsonb);
@@ -90,20 +154,35 @@ impl<'a> Decoder<'a> {
     // `Scalar` has one `JEntry`
     // `Array` and `Object` store the numbers of elements
     fn decode_jsonb(&mut self) -> Result<Value<'a>, Error> {
-        let container_header = self.buf.read_u32::<BigEndian>()?;
+        let container_header = self.read_u32()?;
 
         match container_header & CONTAINER_HEADER_TYPE_MASK {
             SCALAR_CONTAINER_TAG => {
-                let encoded = self.buf.read_u32::<BigEndian>()?;
+                let encoded = self.read_u32()?;
                 let jentry = JEntry::decode_jentry(encoded);
                 self.decode_scalar(jentry)
             }
-            ARRAY_CONTAINER_TAG => self.decode_array(container_header),
-            OBJECT_CONTAINER_TAG => self.decode_object(container_header),
+            ARRAY_CONTAINER_TAG => self.decode_nested(container_header, Self::decode_array),
+            OBJECT_CONTAINER_TAG => self.decode_nested(container_header, Self::decode_object),
             _ => Err(Error::InvalidJsonbHeader),
         }
     }
 
+    // Decode an array or an object one level deeper, fails if the depth limit is exceeded.
+    fn decode_nested(
+        &mut self,
+        container_header: u32,
+        decode: fn(&mut Self, u32) -> Result<Value<'a>, Error>,
+    ) -> Result<Value<'a>, Error> {
+        if self.depth >= self.max_depth {
+            return Err(Error::RecursionLimitExceeded);
+        }
+        self.depth += 1;
+        let value = decode(self, container_header);
+        self.depth -= 1;
+        value
+    }
+
     // Decode `Value` based on the `JEntry`
     // `Null` and `Boolean` don't need to read extra data
     // `Number` and `String` `JEntry` stores the length or offset of the data,
@@ -116,8 +195,12 @@ impl<'a> Decoder<'a> {
             FALSE_TAG => Ok(Value::Bool(false)),
             STRING_TAG => {
                 let offset = jentry.length as usize;
-                let string = &self.buf.get(..offset).ok_or(Error::InvalidUtf8)?;
-                let s = unsafe { std::str::from_utf8_unchecked(string) };
+                let string = self.buf.get(..offset).ok_or(Error::InvalidJsonb)?;
+                let s = if self.check_utf8 {
+                    simdutf8::basic::from_utf8(string).map_err(|_| Error::InvalidUtf8)?
+                } else {
+                    unsafe { std::str::from_utf8_unchecked(string) }
+                };
                 self.buf = &self.buf[offset..];
                 Ok(Value::String(Cow::Borrowed(s)))
             }
@@ -128,6 +211,13 @@ impl<'a> Decoder<'a> {
                 self.buf = &self.buf[offset..];
                 Ok(Value::Number(n))
             }
+            EXTENSION_TAG => {
+                let offset = jentry.length as usize;
+                let data = self.buf.get(..offset).ok_or(Error::InvalidJsonb)?;
+                let v = ExtensionValue::decode(data)?;
+                self.buf = &self.buf[offset..];
+                Ok(v.into_value())
+            }
             CONTAINER_TAG => self.decode_jsonb(),
             _ => Err(Error::InvalidJsonbJEntry),
         }
@@ -167,7 +257,7 @@ impl<'a> Decoder<'a> {
         // decode all values
         for _ in 0..length {
             let key = keys.pop_front().unwrap();
-            let k = key.as_str().unwrap();
+            let k = key.as_str().ok_or(Error::InvalidJsonbJEntry)?;
             let jentry = jentries.pop_front().unwrap();
             let value = self.decode_scalar(jentry)?;
             obj.insert(k.to_string(), value);
@@ -177,12 +267,27 @@ impl<'a> Decoder<'a> {
         Ok(value)
     }
 
+    // Read the next `Header` or `JEntry`, running out of bytes is an `InvalidEOF` error.
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        self.buf
+            .read_u32::<BigEndian>()
+            .map_err(|_| Error::InvalidEOF)
+    }
+
     // Decode `JEntries` for `Array` and `Object`
     fn decode_jentries(&mut self, length: usize) -> Result<VecDeque<JEntry>, Error> {
+        // Corrupted headers may claim more `JEntries` than the buffer can hold,
+        // check it before allocating to avoid huge memory allocations.
+        if length > self.buf.len() / 4 {
+            return Err(Error::InvalidJsonb);
+        }
         let mut jentries: VecDeque<JEntry> = VecDeque::with_capacity(length);
-        for _ in 0..length {
-            let encoded = self.buf.read_u32::<BigEndian>()?;
-            let jentry = JEntry::decode_jentry(encoded);
+        // positions relative to the first `JEntry`
+        let mut val_offset = 4 * length;
+        for i in 0..length {
+            let encoded = self.read_u32()?;
+            let jentry = JEntry::decode_jentry_at(encoded, 4 * i, val_offset)?;
+            val_offset += jentry.length as usize;
             jentries.push_back(jentry);
         }
         Ok(jentries)
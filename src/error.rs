@@ -26,12 +26,14 @@ pub enum ParseErrorCode {
     ExpectedObjectCommaOrEnd,
     UnexpectedTrailingCharacters,
     KeyMustBeAString,
+    ObjectDuplicateKey(String),
     ControlCharacterWhileParsingString,
     InvalidEscaped(u8),
     InvalidHex(u8),
     InvalidLoneLeadingSurrogateInHexEscape(u16),
     InvalidSurrogateInHexEscape(u16),
     UnexpectedEndOfHexEscape,
+    RecursionLimitExceeded,
 }
 
 impl Display for ParseErrorCode {
@@ -47,6 +49,9 @@ impl Display for ParseErrorCode {
             ParseErrorCode::ExpectedObjectCommaOrEnd => f.write_str("expected `,` or `}`"),
             ParseErrorCode::UnexpectedTrailingCharacters => f.write_str("trailing characters"),
             ParseErrorCode::KeyMustBeAString => f.write_str("key must be a string"),
+            ParseErrorCode::ObjectDuplicateKey(ref k) => {
+                write!(f, "duplicate object key `{}`", k)
+            }
             ParseErrorCode::ControlCharacterWhileParsingString => {
                 f.write_str("control character (\\u0000-\\u001F) found while parsing a string")
             }
@@ -63,10 +68,68 @@ impl Display for ParseErrorCode {
                 write!(f, "invalid surrogate in hex escape '{:X}'", n)
             }
             ParseErrorCode::UnexpectedEndOfHexEscape => f.write_str("unexpected end of hex escape"),
+            ParseErrorCode::RecursionLimitExceeded => f.write_str("recursion limit exceeded"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ValidationErrorCode {
+    UnexpectedEOF,
+    InvalidVersion,
+    InvalidHeader,
+    InvalidJEntry,
+    InvalidLength,
+    InvalidUtf8,
+    InvalidNumber,
+    InvalidExtension,
+    KeyMustBeAString,
+    UnsortedKeys,
+    DuplicateKey,
+    DepthLimitExceeded,
+    TrailingBytes,
+}
+
+impl Display for ValidationErrorCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self {
+            ValidationErrorCode::UnexpectedEOF => f.write_str("unexpected end of data"),
+            ValidationErrorCode::InvalidVersion => f.write_str("unsupported version"),
+            ValidationErrorCode::InvalidHeader => f.write_str("invalid container header"),
+            ValidationErrorCode::InvalidJEntry => f.write_str("invalid JEntry"),
+            ValidationErrorCode::InvalidLength => f.write_str("length out of bounds"),
+            ValidationErrorCode::InvalidUtf8 => f.write_str("invalid UTF-8 string"),
+            ValidationErrorCode::InvalidNumber => f.write_str("invalid number"),
+            ValidationErrorCode::InvalidExtension => f.write_str("invalid extension value"),
+            ValidationErrorCode::KeyMustBeAString => f.write_str("key must be a string"),
+            ValidationErrorCode::UnsortedKeys => f.write_str("object keys are not sorted"),
+            ValidationErrorCode::DuplicateKey => f.write_str("duplicate object key"),
+            ValidationErrorCode::DepthLimitExceeded => f.write_str("nesting depth limit exceeded"),
+            ValidationErrorCode::TrailingBytes => f.write_str("trailing bytes"),
         }
     }
 }
 
+/// Error of [`validate`](crate::validate), with the byte offset of the first problem.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ValidationError {
+    pub code: ValidationErrorCode,
+    pub offset: usize,
+}
+
+impl ValidationError {
+    pub(crate) fn new(code: ValidationErrorCode, offset: usize) -> Self {
+        Self { code, offset }
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}, offset {}", self.code, self.offset)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[non_exhaustive]
 pub enum Error {
@@ -80,6 +143,12 @@ pub enum Error {
     InvalidJsonbHeader,
     InvalidJsonbJEntry,
     InvalidJsonbNumber,
+    InvalidJsonbVersion,
+    /// The encoded length of a value exceeds the 256 MiB limit of a `JEntry`,
+    /// or a container has more elements than its header can hold.
+    ValueTooLarge,
+    /// Arrays and objects of a `JSONB` value are nested deeper than the depth limit.
+    RecursionLimitExceeded,
 
     InvalidJsonPath,
     InvalidJsonPathPredicate,
@@ -88,22 +157,33 @@ pub enum Error {
     InvalidJsonType,
     InvalidObject,
     ObjectDuplicateKey,
+    /// A `NaN` or infinite number can't be written as `JSON` text.
+    NonFiniteNumber,
+
+    /// Error of the underlying reader or writer.
+    Io(std::io::ErrorKind),
 
     Syntax(ParseErrorCode, usize),
+    /// Error in a record of a `JSON` stream, with the index of the record
+    /// and the byte offset where the record starts in the stream.
+    Record(usize, usize, Box<Error>),
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Error::Syntax(code, pos) => write!(f, "{}, pos {}", code, pos),
+            Error::Record(index, offset, err) => {
+                write!(f, "{}, record {} at offset {}", err, index, offset)
+            }
             _ => write!(f, "{:?}", self),
         }
     }
 }
 
 impl From<std::io::Error> for Error {
-    fn from(_error: std::io::Error) -> Self {
-        Error::InvalidUtf8
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error.kind())
     }
 }
 
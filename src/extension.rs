@@ -0,0 +1,368 @@
+// Copyright 2024 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Write;
+use std::hash::Hash;
+use std::hash::Hasher;
+
+use super::constants::*;
+use super::error::Error;
+use super::util::comparable_decode_bytes;
+use super::util::comparable_encode_bytes;
+use super::util::read_exact;
+use super::value::Value;
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+/// A date, the value is the number of days since `1970-01-01`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub value: i32,
+}
+
+/// A timestamp with time zone, the value is the number of microseconds
+/// since `1970-01-01 00:00:00 UTC`, and the offset is the seconds east of UTC.
+/// Timestamps are compared by the instant, the offset is only used for rendering.
+#[derive(Debug, Clone, Copy)]
+pub struct TimestampTz {
+    pub value: i64,
+    pub offset: i32,
+}
+
+/// A UUID in the big-endian byte order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid {
+    pub value: [u8; 16],
+}
+
+impl Date {
+    /// Make a date from the year, month and day, returns `None` if the date is invalid.
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Option<Date> {
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year as i64, month) {
+            return None;
+        }
+        let days = days_from_civil(year as i64, month, day);
+        Some(Date {
+            value: days.try_into().ok()?,
+        })
+    }
+}
+
+impl TimestampTz {
+    pub fn new(value: i64, offset: i32) -> TimestampTz {
+        TimestampTz { value, offset }
+    }
+}
+
+impl PartialEq for TimestampTz {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Eq for TimestampTz {}
+
+impl PartialOrd for TimestampTz {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimestampTz {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl Hash for TimestampTz {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (year, month, day) = civil_from_days(self.value as i64);
+        write_ymd(f, year, month, day)
+    }
+}
+
+impl Display for TimestampTz {
+    // Rendered as RFC 3339 in the local time of the offset,
+    // such as `2024-01-31T12:34:56.789+08:00`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let local = self.value as i128 + self.offset as i128 * MICROS_PER_SECOND as i128;
+        let days = local.div_euclid(MICROS_PER_DAY as i128) as i64;
+        let micros = local.rem_euclid(MICROS_PER_DAY as i128) as i64;
+        let (year, month, day) = civil_from_days(days);
+        write_ymd(f, year, month, day)?;
+        let secs = micros / MICROS_PER_SECOND;
+        write!(
+            f,
+            "T{:02}:{:02}:{:02}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )?;
+        let fraction = micros % MICROS_PER_SECOND;
+        if fraction > 0 {
+            let fraction = format!("{:06}", fraction);
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let offset = self.offset.unsigned_abs();
+        write!(f, "{}{:02}:{:02}", sign, offset / 3600, offset / 60 % 60)?;
+        if offset % 60 > 0 {
+            write!(f, ":{:02}", offset % 60)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Uuid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, b) in self.value.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                write!(f, "-")?;
+            }
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+// The extension scalar values, encoded as a type byte followed by the data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum ExtensionValue<'a> {
+    Binary(&'a [u8]),
+    Date(Date),
+    TimestampTz(TimestampTz),
+    Uuid(Uuid),
+}
+
+impl<'a> ExtensionValue<'a> {
+    pub(crate) fn from_value(value: &'a Value<'_>) -> Option<ExtensionValue<'a>> {
+        match value {
+            Value::Binary(v) => Some(ExtensionValue::Binary(v)),
+            Value::Date(v) => Some(ExtensionValue::Date(*v)),
+            Value::TimestampTz(v) => Some(ExtensionValue::TimestampTz(*v)),
+            Value::Uuid(v) => Some(ExtensionValue::Uuid(*v)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn into_value(self) -> Value<'a> {
+        match self {
+            ExtensionValue::Binary(v) => Value::Binary(Cow::Borrowed(v)),
+            ExtensionValue::Date(v) => Value::Date(v),
+            ExtensionValue::TimestampTz(v) => Value::TimestampTz(v),
+            ExtensionValue::Uuid(v) => Value::Uuid(v),
+        }
+    }
+
+    fn type_tag(&self) -> u8 {
+        match self {
+            ExtensionValue::Binary(_) => EXTENSION_BINARY,
+            ExtensionValue::Date(_) => EXTENSION_DATE,
+            ExtensionValue::TimestampTz(_) => EXTENSION_TIMESTAMP_TZ,
+            ExtensionValue::Uuid(_) => EXTENSION_UUID,
+        }
+    }
+
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            ExtensionValue::Binary(_) => TYPE_BINARY,
+            ExtensionValue::Date(_) => TYPE_DATE,
+            ExtensionValue::TimestampTz(_) => TYPE_TIMESTAMP_TZ,
+            ExtensionValue::Uuid(_) => TYPE_UUID,
+        }
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) -> usize {
+        let old_len = buf.len();
+        buf.push(self.type_tag());
+        match self {
+            ExtensionValue::Binary(v) => buf.extend_from_slice(v),
+            ExtensionValue::Date(v) => buf.extend_from_slice(&v.value.to_be_bytes()),
+            ExtensionValue::TimestampTz(v) => {
+                buf.extend_from_slice(&v.value.to_be_bytes());
+                buf.extend_from_slice(&v.offset.to_be_bytes());
+            }
+            ExtensionValue::Uuid(v) => buf.extend_from_slice(&v.value),
+        }
+        buf.len() - old_len
+    }
+
+    pub(crate) fn decode(bytes: &'a [u8]) -> Result<ExtensionValue<'a>, Error> {
+        let Some((ty, data)) = bytes.split_first() else {
+            return Err(Error::InvalidJsonb);
+        };
+        let value = match (*ty, data.len()) {
+            (EXTENSION_BINARY, _) => ExtensionValue::Binary(data),
+            (EXTENSION_DATE, 4) => ExtensionValue::Date(Date {
+                value: i32::from_be_bytes(data.try_into().unwrap()),
+            }),
+            (EXTENSION_TIMESTAMP_TZ, 12) => ExtensionValue::TimestampTz(TimestampTz {
+                value: i64::from_be_bytes(data[..8].try_into().unwrap()),
+                offset: i32::from_be_bytes(data[8..].try_into().unwrap()),
+            }),
+            (EXTENSION_UUID, 16) => ExtensionValue::Uuid(Uuid {
+                value: data.try_into().unwrap(),
+            }),
+            (_, _) => {
+                return Err(Error::InvalidJsonb);
+            }
+        };
+        Ok(value)
+    }
+
+    // Encode to comparable bytes with the same order as the `Ord`,
+    // the offset of timestamps is appended to `types` as it is not compared.
+    pub(crate) fn comparable_encode(&self, buf: &mut Vec<u8>, types: &mut Vec<u8>) {
+        buf.push(self.type_tag());
+        match self {
+            // binary values have variable lengths, the escaped bytes with a terminator
+            // keep a binary less than the longer binaries starting with it,
+            // whatever follows it in the arrays and objects.
+            ExtensionValue::Binary(v) => comparable_encode_bytes(v, buf),
+            ExtensionValue::Date(v) => {
+                // Toggle the sign bit to ensure consistent sort order
+                buf.extend_from_slice(&(v.value as u32 ^ 0x80000000).to_be_bytes())
+            }
+            ExtensionValue::TimestampTz(v) => {
+                buf.extend_from_slice(&(v.value as u64 ^ 0x8000000000000000).to_be_bytes());
+                types.extend_from_slice(&v.offset.to_be_bytes());
+            }
+            ExtensionValue::Uuid(v) => buf.extend_from_slice(&v.value),
+        }
+    }
+
+    // Decode the value written by `comparable_encode` and advance the inputs.
+    pub(crate) fn comparable_decode(
+        key: &mut &[u8],
+        types: &mut &[u8],
+    ) -> Result<Value<'static>, Error> {
+        let value = match read_exact(key, 1)?[0] {
+            EXTENSION_BINARY => Value::Binary(Cow::Owned(comparable_decode_bytes(key)?)),
+            EXTENSION_DATE => {
+                let value = u32::from_be_bytes(read_exact(key, 4)?.try_into().unwrap());
+                Value::Date(Date {
+                    value: (value ^ 0x80000000) as i32,
+                })
+            }
+            EXTENSION_TIMESTAMP_TZ => {
+                let value = u64::from_be_bytes(read_exact(key, 8)?.try_into().unwrap());
+                let offset = i32::from_be_bytes(read_exact(types, 4)?.try_into().unwrap());
+                Value::TimestampTz(TimestampTz {
+                    value: (value ^ 0x8000000000000000) as i64,
+                    offset,
+                })
+            }
+            EXTENSION_UUID => Value::Uuid(Uuid {
+                value: read_exact(key, 16)?.try_into().unwrap(),
+            }),
+            _ => return Err(Error::InvalidJsonb),
+        };
+        Ok(value)
+    }
+}
+
+// Different extension types are compared by the type, then the values.
+impl PartialOrd for ExtensionValue<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ExtensionValue<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (ExtensionValue::Binary(l), ExtensionValue::Binary(r)) => l.cmp(r),
+            (ExtensionValue::Date(l), ExtensionValue::Date(r)) => l.cmp(r),
+            (ExtensionValue::TimestampTz(l), ExtensionValue::TimestampTz(r)) => l.cmp(r),
+            (ExtensionValue::Uuid(l), ExtensionValue::Uuid(r)) => l.cmp(r),
+            (_, _) => self.type_tag().cmp(&other.type_tag()),
+        }
+    }
+}
+
+// The extension values are rendered as JSON strings,
+// binary values are rendered in lowercase hex.
+impl Display for ExtensionValue<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExtensionValue::Binary(v) => {
+                for b in v.iter() {
+                    write!(f, "{:02x}", b)?;
+                }
+                Ok(())
+            }
+            ExtensionValue::Date(v) => write!(f, "{}", v),
+            ExtensionValue::TimestampTz(v) => write!(f, "{}", v),
+            ExtensionValue::Uuid(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+fn write_ymd(f: &mut Formatter<'_>, year: i64, month: u32, day: u32) -> std::fmt::Result {
+    if year < 0 {
+        f.write_char('-')?;
+    }
+    write!(f, "{:04}-{:02}-{:02}", year.unsigned_abs(), month, day)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// The number of days since `1970-01-01` of the proleptic Gregorian date.
+// https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// The proleptic Gregorian date of the number of days since `1970-01-01`.
+// https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
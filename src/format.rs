@@ -0,0 +1,128 @@
+// Copyright 2024 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::constants::*;
+use super::jentry::JEntry;
+
+/// The format of the stored bytes, as detected by [`detect_format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `JSON` text.
+    Json,
+    /// `JSONB` without a version header, as written by `Value::to_vec`
+    /// and by all the releases before the header was introduced.
+    Jsonb,
+    /// `JSONB` starting with a version header, holding the version of the encoding.
+    /// Only versions up to [`JSONB_VERSION`] can be read by this release.
+    ///
+    /// The functions write their results without the header, containers copied out of it,
+    /// such as by [`get_by_name`](crate::get_by_name), are rewritten to store lengths only.
+    VersionedJsonb(u8),
+}
+
+/// The latest encoding version written in the version header.
+///
+/// - Version 1: every JEntry stores the length of its value.
+/// - Version 2: every 32nd JEntry of a container stores an offset instead of a length,
+///   and the keys of objects are sorted without duplicates, large objects are searched
+///   by binary search.
+pub const JSONB_VERSION: u8 = 2;
+
+/// Detect the format of the bytes without decoding them.
+///
+/// The version header is `0xFF 'J' 'B' <version>`. `0xFF` is neither a valid
+/// first byte of `JSON` text nor of a `JSONB` container header,
+/// so the header can never be confused with data written without it.
+pub fn detect_format(value: &[u8]) -> Format {
+    match value {
+        [JSONB_MAGIC_0, JSONB_MAGIC_1, JSONB_MAGIC_2, version, ..] => {
+            Format::VersionedJsonb(*version)
+        }
+        [ARRAY_PREFIX, ..] | [OBJECT_PREFIX, ..] => Format::Jsonb,
+        // A scalar header is always `0x20000000`, a space followed by NUL bytes
+        // is never valid `JSON` text.
+        [SCALAR_PREFIX, 0, 0, 0, ..] => Format::Jsonb,
+        _ => Format::Json,
+    }
+}
+
+// Strip the version header from `JSONB` with a supported version,
+// the remaining bytes are encoded the same as `JSONB` without the header.
+// Other values are returned unchanged.
+pub(crate) fn strip_version_header(value: &[u8]) -> &[u8] {
+    match detect_format(value) {
+        Format::VersionedJsonb(version) if (1..=JSONB_VERSION).contains(&version) => {
+            &value[JSONB_VERSION_HEADER_LEN..]
+        }
+        _ => value,
+    }
+}
+
+// Write the version header for the latest encoding version.
+pub(crate) fn write_version_header(buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&[JSONB_MAGIC_0, JSONB_MAGIC_1, JSONB_MAGIC_2, JSONB_VERSION]);
+}
+
+// Copy a container into `buf`, rewriting the offset JEntries of version 2 back to lengths,
+// so the copied bytes can be read without the version header, also by the older releases.
+// The JEntries have a fixed size, the rewrite doesn't move any data.
+// Corrupted data is copied as it is, the readers report the error.
+pub(crate) fn extend_without_offsets(buf: &mut Vec<u8>, container: &[u8]) {
+    let start = buf.len();
+    buf.extend_from_slice(container);
+    strip_jentry_offsets(&mut buf[start..], 0);
+}
+
+fn strip_jentry_offsets(container: &mut [u8], depth: usize) {
+    if depth > DEFAULT_MAX_DEPTH {
+        return;
+    }
+    let Some(header) = read_u32(container, 0) else {
+        return;
+    };
+    let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
+    let count = match header & CONTAINER_HEADER_TYPE_MASK {
+        ARRAY_CONTAINER_TAG => length,
+        OBJECT_CONTAINER_TAG => length.saturating_mul(2),
+        _ => return,
+    };
+    let mut jentry_offset = 4;
+    let mut val_offset = count.saturating_mul(4).saturating_add(4);
+    for _ in 0..count {
+        let Some(encoded) = read_u32(container, jentry_offset) else {
+            return;
+        };
+        let Ok(jentry) = JEntry::decode_jentry_at(encoded, jentry_offset, val_offset) else {
+            return;
+        };
+        if encoded & JENTRY_IS_OFF_FLAG != 0 {
+            container[jentry_offset..jentry_offset + 4]
+                .copy_from_slice(&jentry.encoded().to_be_bytes());
+        }
+        let val_end = val_offset + jentry.length as usize;
+        if jentry.type_code == CONTAINER_TAG {
+            let Some(value) = container.get_mut(val_offset..val_end) else {
+                return;
+            };
+            strip_jentry_offsets(value, depth + 1);
+        }
+        jentry_offset += 4;
+        val_offset = val_end;
+    }
+}
+
+fn read_u32(buf: &[u8], idx: usize) -> Option<u32> {
+    let bytes = buf.get(idx..idx + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().ok()?))
+}
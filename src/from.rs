@@ -20,6 +20,7 @@ use serde_json::Map as JsonMap;
 use serde_json::Number as JsonNumber;
 use serde_json::Value as JsonValue;
 
+use super::extension::ExtensionValue;
 use super::number::Number;
 use super::value::Object;
 use super::value::Value;
@@ -197,6 +198,20 @@ impl<'a> From<Value<'a>> for JsonValue {
                 Number::Int64(v) => JsonValue::Number(v.into()),
                 Number::UInt64(v) => JsonValue::Number(v.into()),
                 Number::Float64(v) => JsonValue::Number(JsonNumber::from_f64(v).unwrap()),
+                Number::Decimal(v) => match v.as_i128() {
+                    Some(n) if i64::try_from(n).is_ok() => JsonValue::Number((n as i64).into()),
+                    Some(n) if u64::try_from(n).is_ok() => JsonValue::Number((n as u64).into()),
+                    _ => JsonValue::Number(JsonNumber::from_f64(v.to_f64()).unwrap()),
+                },
+                Number::Int128(v) => match i64::try_from(v) {
+                    Ok(v) => JsonValue::Number(v.into()),
+                    Err(_) => JsonValue::Number(JsonNumber::from_f64(v as f64).unwrap()),
+                },
+                Number::UInt128(v) => match u64::try_from(v) {
+                    Ok(v) => JsonValue::Number(v.into()),
+                    Err(_) => JsonValue::Number(JsonNumber::from_f64(v as f64).unwrap()),
+                },
+                Number::Raw(_) => Value::Number(v.numeric().clone()).into(),
             },
             Value::String(v) => JsonValue::String(v.to_string()),
             Value::Array(arr) => {
@@ -214,6 +229,10 @@ impl<'a> From<Value<'a>> for JsonValue {
                 }
                 JsonValue::Object(map)
             }
+            Value::Binary(_) | Value::Date(_) | Value::TimestampTz(_) | Value::Uuid(_) => {
+                let v = ExtensionValue::from_value(&value).unwrap();
+                JsonValue::String(v.to_string())
+            }
         }
     }
 }
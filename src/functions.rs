@@ -18,25 +18,41 @@ use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::VecDeque;
+use std::hash::Hash;
+use std::hash::Hasher;
 use std::str::from_utf8;
 use std::str::from_utf8_unchecked;
 use std::str::FromStr;
 
 use crate::builder::ArrayBuilder;
 use crate::builder::ObjectBuilder;
+use crate::compare_options::CompareOptions;
 use crate::constants::*;
 use crate::error::*;
+use crate::extension::ExtensionValue;
+use crate::format::detect_format;
+use crate::format::extend_without_offsets;
+use crate::format::strip_version_header;
+use crate::format::Format;
+use crate::format::JSONB_VERSION;
 use crate::from_slice;
+use crate::from_slice_checked;
+use crate::iterator::get_jentry_at;
+use crate::iterator::is_sorted_object;
 use crate::iterator::iteate_object_keys;
 use crate::iterator::iterate_array;
 use crate::iterator::iterate_object_entries;
+use crate::iterator::search_object_value;
 use crate::jentry::JEntry;
+use crate::json_format::JsonFormatOptions;
 use crate::jsonpath::JsonPath;
 use crate::jsonpath::Mode;
 use crate::jsonpath::Selector;
 use crate::keypath::KeyPath;
 use crate::number::Number;
 use crate::parser::parse_value;
+use crate::ser::make_container_header;
+use crate::util::read_exact;
 use crate::value::Object;
 use crate::value::Value;
 use rand::distributions::Alphanumeric;
@@ -53,36 +69,15 @@ pub fn build_array<'a>(
     items: impl IntoIterator<Item = &'a [u8]>,
     buf: &mut Vec<u8>,
 ) -> Result<(), Error> {
-    let start = buf.len();
-    // reserve space for header
-    buf.resize(start + 4, 0);
-    let mut len: u32 = 0;
+    let mut jentries = Vec::new();
     let mut data = Vec::new();
     for value in items.into_iter() {
-        let header = read_u32(value, 0)?;
-        let encoded_jentry = match header & CONTAINER_HEADER_TYPE_MASK {
-            SCALAR_CONTAINER_TAG => {
-                let jentry = &value[4..8];
-                data.extend_from_slice(&value[8..]);
-                jentry.try_into().unwrap()
-            }
-            ARRAY_CONTAINER_TAG | OBJECT_CONTAINER_TAG => {
-                data.extend_from_slice(value);
-                (CONTAINER_TAG | value.len() as u32).to_be_bytes()
-            }
-            _ => return Err(Error::InvalidJsonbHeader),
-        };
-        len += 1;
-        buf.extend_from_slice(&encoded_jentry);
-    }
-    // write header
-    let header = ARRAY_CONTAINER_TAG | len;
-    for (i, b) in header.to_be_bytes().iter().enumerate() {
-        buf[start + i] = *b;
+        let value = strip_version_header(value);
+        let jentry = build_item_jentry(value, &mut data)?;
+        jentries.push(jentry);
     }
-    buf.extend_from_slice(&data);
-
-    Ok(())
+    let header = make_container_header(ARRAY_CONTAINER_TAG, jentries.len())?;
+    write_container(header, &jentries, &[&data], buf)
 }
 
 /// Build `JSONB` object from items.
@@ -91,54 +86,63 @@ pub fn build_object<'a, K: AsRef<str>>(
     items: impl IntoIterator<Item = (K, &'a [u8])>,
     buf: &mut Vec<u8>,
 ) -> Result<(), Error> {
-    let start = buf.len();
-    // reserve space for header
-    buf.resize(start + 4, 0);
-    let mut len: u32 = 0;
+    let mut key_jentries = Vec::new();
     let mut key_data = Vec::new();
+    let mut val_jentries = Vec::new();
     let mut val_data = Vec::new();
-    let mut val_jentries = VecDeque::new();
     for (key, value) in items.into_iter() {
         let key = key.as_ref();
-        // write key jentry and key data
-        let encoded_key_jentry = (STRING_TAG | key.len() as u32).to_be_bytes();
-        buf.extend_from_slice(&encoded_key_jentry);
+        // build key jentry and key data
+        key_jentries.push(JEntry::make_string_jentry(key.len())?);
         key_data.extend_from_slice(key.as_bytes());
 
-        // build value jentry and write value data
-        let header = read_u32(value, 0)?;
-        let encoded_val_jentry = match header & CONTAINER_HEADER_TYPE_MASK {
-            SCALAR_CONTAINER_TAG => {
-                let jentry = &value[4..8];
-                val_data.extend_from_slice(&value[8..]);
-                jentry.try_into().unwrap()
-            }
-            ARRAY_CONTAINER_TAG | OBJECT_CONTAINER_TAG => {
-                val_data.extend_from_slice(value);
-                (CONTAINER_TAG | value.len() as u32).to_be_bytes()
-            }
-            _ => return Err(Error::InvalidJsonbHeader),
-        };
-        val_jentries.push_back(encoded_val_jentry);
-        len += 1;
-    }
-    // write header and value jentry
-    let header = OBJECT_CONTAINER_TAG | len;
-    for (i, b) in header.to_be_bytes().iter().enumerate() {
-        buf[start + i] = *b;
+        // build value jentry and value data
+        let value = strip_version_header(value);
+        let jentry = build_item_jentry(value, &mut val_data)?;
+        val_jentries.push(jentry);
     }
-    while let Some(val_jentry) = val_jentries.pop_front() {
-        buf.extend_from_slice(&val_jentry);
+    let header = make_container_header(OBJECT_CONTAINER_TAG, val_jentries.len())?;
+    key_jentries.append(&mut val_jentries);
+    write_container(header, &key_jentries, &[&key_data, &val_data], buf)
+}
+
+// Build the jentry of an item and write the item data.
+fn build_item_jentry(value: &[u8], data: &mut Vec<u8>) -> Result<JEntry, Error> {
+    let header = read_u32(value, 0)?;
+    match header & CONTAINER_HEADER_TYPE_MASK {
+        SCALAR_CONTAINER_TAG => {
+            let jentry = JEntry::decode_jentry(read_u32(value, 4)?);
+            data.extend_from_slice(&value[8..]);
+            Ok(jentry)
+        }
+        ARRAY_CONTAINER_TAG | OBJECT_CONTAINER_TAG => {
+            extend_without_offsets(data, value);
+            JEntry::make_container_jentry(value.len())
+        }
+        _ => Err(Error::InvalidJsonbHeader),
     }
-    // write key data and value data
-    buf.extend_from_slice(&key_data);
-    buf.extend_from_slice(&val_data);
+}
 
+// Write the container header, the jentries and the data of the values.
+fn write_container(
+    header: u32,
+    jentries: &[JEntry],
+    data: &[&[u8]],
+    buf: &mut Vec<u8>,
+) -> Result<(), Error> {
+    buf.extend_from_slice(&header.to_be_bytes());
+    for jentry in jentries.iter() {
+        buf.extend_from_slice(&jentry.encoded().to_be_bytes());
+    }
+    for data in data {
+        buf.extend_from_slice(data);
+    }
     Ok(())
 }
 
 /// Get the length of `JSONB` array.
 pub fn array_length(value: &[u8]) -> Option<usize> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => val.array_length(),
@@ -157,6 +161,7 @@ pub fn array_length(value: &[u8]) -> Option<usize> {
 
 /// Checks whether the JSON path returns any item for the `JSONB` value.
 pub fn path_exists<'a>(value: &'a [u8], json_path: JsonPath<'a>) -> Result<bool, Error> {
+    let value = strip_version_header(value);
     let selector = Selector::new(json_path, Mode::Mixed);
     if !is_jsonb(value) {
         match parse_value(value) {
@@ -173,6 +178,7 @@ pub fn path_exists<'a>(value: &'a [u8], json_path: JsonPath<'a>) -> Result<bool,
 
 /// Returns the result of a JSON path predicate check for the specified `JSONB` value.
 pub fn path_match<'a>(value: &'a [u8], json_path: JsonPath<'a>) -> Result<bool, Error> {
+    let value = strip_version_header(value);
     let selector = Selector::new(json_path, Mode::First);
     if !is_jsonb(value) {
         let val = parse_value(value)?;
@@ -190,6 +196,7 @@ pub fn get_by_path<'a>(
     data: &mut Vec<u8>,
     offsets: &mut Vec<u64>,
 ) -> Result<(), Error> {
+    let value = strip_version_header(value);
     let selector = Selector::new(json_path, Mode::Mixed);
     if !is_jsonb(value) {
         if let Ok(val) = parse_value(value) {
@@ -210,6 +217,7 @@ pub fn get_by_path_first<'a>(
     data: &mut Vec<u8>,
     offsets: &mut Vec<u64>,
 ) -> Result<(), Error> {
+    let value = strip_version_header(value);
     let selector = Selector::new(json_path, Mode::First);
     if !is_jsonb(value) {
         if let Ok(val) = parse_value(value) {
@@ -230,6 +238,7 @@ pub fn get_by_path_array<'a>(
     data: &mut Vec<u8>,
     offsets: &mut Vec<u64>,
 ) -> Result<(), Error> {
+    let value = strip_version_header(value);
     let selector = Selector::new(json_path, Mode::Array);
     if !is_jsonb(value) {
         if let Ok(val) = parse_value(value) {
@@ -244,6 +253,7 @@ pub fn get_by_path_array<'a>(
 
 /// Get the inner element of `JSONB` Array by index.
 pub fn get_by_index(value: &[u8], index: usize) -> Option<Vec<u8>> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => match val {
@@ -256,11 +266,8 @@ pub fn get_by_index(value: &[u8], index: usize) -> Option<Vec<u8>> {
 
     let header = read_u32(value, 0).ok()?;
     match header & CONTAINER_HEADER_TYPE_MASK {
-        ARRAY_CONTAINER_TAG => {
-            get_jentry_by_index(value, 0, header, index).map(|(jentry, encoded, val_offset)| {
-                extract_by_jentry(&jentry, encoded, val_offset, value)
-            })
-        }
+        ARRAY_CONTAINER_TAG => get_jentry_by_index(value, 0, header, index)
+            .and_then(|(jentry, val_offset)| extract_by_jentry(&jentry, val_offset, value)),
         _ => None,
     }
 }
@@ -268,6 +275,7 @@ pub fn get_by_index(value: &[u8], index: usize) -> Option<Vec<u8>> {
 /// Get the inner element of `JSONB` Object by key name,
 /// if `ignore_case` is true, enables case-insensitive matching.
 pub fn get_by_name(value: &[u8], name: &str, ignore_case: bool) -> Option<Vec<u8>> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => {
@@ -286,9 +294,8 @@ pub fn get_by_name(value: &[u8], name: &str, ignore_case: bool) -> Option<Vec<u8
 
     let header = read_u32(value, 0).ok()?;
     match header & CONTAINER_HEADER_TYPE_MASK {
-        OBJECT_CONTAINER_TAG => get_jentry_by_name(value, 0, header, name, ignore_case).map(
-            |(jentry, encoded, val_offset)| extract_by_jentry(&jentry, encoded, val_offset, value),
-        ),
+        OBJECT_CONTAINER_TAG => get_jentry_by_name(value, 0, header, name, ignore_case)
+            .and_then(|(jentry, val_offset)| extract_by_jentry(&jentry, val_offset, value)),
         _ => None,
     }
 }
@@ -299,6 +306,7 @@ pub fn get_by_keypath<'a, I: Iterator<Item = &'a KeyPath<'a>>>(
     value: &[u8],
     keypaths: I,
 ) -> Option<Vec<u8>> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => {
@@ -338,7 +346,6 @@ pub fn get_by_keypath<'a, I: Iterator<Item = &'a KeyPath<'a>>>(
     }
 
     let mut curr_val_offset = 0;
-    let mut curr_jentry_encoded = 0;
     let mut curr_jentry: Option<JEntry> = None;
 
     for path in keypaths {
@@ -352,8 +359,7 @@ pub fn get_by_keypath<'a, I: Iterator<Item = &'a KeyPath<'a>>>(
         match (path, header & CONTAINER_HEADER_TYPE_MASK) {
             (KeyPath::QuotedName(name) | KeyPath::Name(name), OBJECT_CONTAINER_TAG) => {
                 match get_jentry_by_name(value, curr_val_offset, header, name, false) {
-                    Some((jentry, encoded, value_offset)) => {
-                        curr_jentry_encoded = encoded;
+                    Some((jentry, value_offset)) => {
                         curr_jentry = Some(jentry);
                         curr_val_offset = value_offset;
                     }
@@ -370,8 +376,7 @@ pub fn get_by_keypath<'a, I: Iterator<Item = &'a KeyPath<'a>>>(
                         (length + *idx) as usize
                     };
                     match get_jentry_by_index(value, curr_val_offset, header, idx) {
-                        Some((jentry, encoded, value_offset)) => {
-                            curr_jentry_encoded = encoded;
+                        Some((jentry, value_offset)) => {
                             curr_jentry = Some(jentry);
                             curr_val_offset = value_offset;
                         }
@@ -384,14 +389,16 @@ pub fn get_by_keypath<'a, I: Iterator<Item = &'a KeyPath<'a>>>(
     }
     // If the key paths is empty, return original value.
     if curr_val_offset == 0 {
-        return Some(value.to_vec());
+        let mut buf = Vec::with_capacity(value.len());
+        extend_without_offsets(&mut buf, value);
+        return Some(buf);
     }
-    curr_jentry
-        .map(|jentry| extract_by_jentry(&jentry, curr_jentry_encoded, curr_val_offset, value))
+    curr_jentry.and_then(|jentry| extract_by_jentry(&jentry, curr_val_offset, value))
 }
 
 /// Checks whether all of the strings exist as top-level keys or array elements.
 pub fn exists_all_keys<'a, I: Iterator<Item = &'a [u8]>>(value: &[u8], keys: I) -> bool {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         match parse_value(value) {
             Ok(val) => {
@@ -428,6 +435,7 @@ pub fn exists_all_keys<'a, I: Iterator<Item = &'a [u8]>>(value: &[u8], keys: I)
 
 /// Checks whether any of the strings exist as top-level keys or array elements.
 pub fn exists_any_keys<'a, I: Iterator<Item = &'a [u8]>>(value: &[u8], keys: I) -> bool {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         if let Ok(val) = parse_value(value) {
             for key in keys {
@@ -477,6 +485,10 @@ fn exists_value_key(value: &Value, key: &str) -> bool {
 fn exists_jsonb_key(value: &[u8], header: u32, key: &str) -> bool {
     match header & CONTAINER_HEADER_TYPE_MASK {
         OBJECT_CONTAINER_TAG => {
+            let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
+            if is_sorted_object(value, 0, length) {
+                return search_object_value(value, 0, length, key).is_some();
+            }
             let mut matches = false;
             for obj_key in iteate_object_keys(value, header) {
                 if obj_key.eq(key) {
@@ -505,17 +517,25 @@ fn exists_jsonb_key(value: &[u8], header: u32, key: &str) -> bool {
 }
 
 /// Checks whether the right value contains in the left value.
+/// Values nested deeper than 128 levels are never contained.
 pub fn contains(left: &[u8], right: &[u8]) -> bool {
+    let left = strip_version_header(left);
+    let right = strip_version_header(right);
     if !is_jsonb(left) || !is_jsonb(right) {
         return match (from_slice(left), from_slice(right)) {
-            (Ok(left), Ok(right)) => contains_value(&left, &right),
+            (Ok(left), Ok(right)) => contains_value(&left, &right, 1),
             _ => false,
         };
     }
-    contains_jsonb(left, right).unwrap_or(false)
+    contains_jsonb(left, right, 1).unwrap_or(false)
 }
 
-fn contains_value(left: &Value, right: &Value) -> bool {
+// `depth` is the nesting depth of the containers being checked,
+// values nested deeper than the depth limit are never contained.
+fn contains_value(left: &Value, right: &Value, depth: usize) -> bool {
+    if depth > DEFAULT_MAX_DEPTH {
+        return false;
+    }
     // special case for the left array and the right scalar
     if left.is_array() && right.is_scalar() {
         return left.as_array().unwrap().contains(right);
@@ -539,7 +559,7 @@ fn contains_value(left: &Value, right: &Value) -> bool {
                             if !l_val.eq(r_val) {
                                 return false;
                             }
-                        } else if !contains_value(l_val, r_val) {
+                        } else if !contains_value(l_val, r_val, depth + 1) {
                             return false;
                         }
                     }
@@ -562,7 +582,7 @@ fn contains_value(left: &Value, right: &Value) -> bool {
                     let mut contains_nested = false;
 
                     for l_nested_val in l_nested {
-                        if contains_value(l_nested_val, r_val) {
+                        if contains_value(l_nested_val, r_val, depth + 1) {
                             contains_nested = true;
                             break;
                         }
@@ -578,7 +598,8 @@ fn contains_value(left: &Value, right: &Value) -> bool {
     }
 }
 
-fn contains_jsonb(left: &[u8], right: &[u8]) -> Result<bool, Error> {
+fn contains_jsonb(left: &[u8], right: &[u8], depth: usize) -> Result<bool, Error> {
+    check_depth(depth, DEFAULT_MAX_DEPTH)?;
     let l_header = read_u32(left, 0)?;
     let r_header = read_u32(right, 0)?;
 
@@ -604,16 +625,16 @@ fn contains_jsonb(left: &[u8], right: &[u8]) -> Result<bool, Error> {
             }
             for (r_key, r_jentry, r_val) in iterate_object_entries(right, r_header) {
                 match get_jentry_by_name(left, 0, l_header, r_key, false) {
-                    Some((l_jentry, _, l_val_offset)) => {
+                    Some((l_jentry, l_val_offset)) => {
                         if l_jentry.type_code != r_jentry.type_code {
                             return Ok(false);
                         }
-                        let l_val = &left[l_val_offset..l_val_offset + l_jentry.length as usize];
+                        let l_val = read_bytes(left, l_val_offset, l_jentry.length as usize)?;
                         if r_jentry.type_code != CONTAINER_TAG {
                             if !l_val.eq(r_val) {
                                 return Ok(false);
                             }
-                        } else if !contains_jsonb(l_val, r_val)? {
+                        } else if !contains_jsonb(l_val, r_val, depth + 1)? {
                             return Ok(false);
                         }
                     }
@@ -637,7 +658,7 @@ fn contains_jsonb(left: &[u8], right: &[u8]) -> Result<bool, Error> {
                     let mut contains_nested = false;
 
                     for l_nested_val in l_nested {
-                        if contains_jsonb(l_nested_val, r_val)? {
+                        if contains_jsonb(l_nested_val, r_val, depth + 1)? {
                             contains_nested = true;
                             break;
                         }
@@ -659,15 +680,23 @@ fn get_jentry_by_name(
     header: u32,
     name: &str,
     ignore_case: bool,
-) -> Option<(JEntry, u32, usize)> {
+) -> Option<(JEntry, usize)> {
     let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
+    // binary search for the same name in large objects with sorted keys,
+    // if not found, scan all the keys for the ignoring case name.
+    if is_sorted_object(value, offset, length) {
+        let result = search_object_value(value, offset, length, name);
+        if result.is_some() || !ignore_case {
+            return result;
+        }
+    }
     let mut jentry_offset = offset + 4;
     let mut val_offset = offset + 8 * length + 4;
 
-    let mut key_jentries: VecDeque<JEntry> = VecDeque::with_capacity(length);
+    let mut key_jentries: VecDeque<JEntry> = VecDeque::with_capacity(capacity_of(length, value));
     for _ in 0..length {
         let encoded = read_u32(value, jentry_offset).ok()?;
-        let key_jentry = JEntry::decode_jentry(encoded);
+        let key_jentry = JEntry::decode_jentry_at(encoded, jentry_offset, val_offset).ok()?;
 
         jentry_offset += 4;
         val_offset += key_jentry.length as usize;
@@ -680,19 +709,19 @@ fn get_jentry_by_name(
     while let Some(key_jentry) = key_jentries.pop_front() {
         let prev_key_offset = key_offset;
         key_offset += key_jentry.length as usize;
-        let key = unsafe { std::str::from_utf8_unchecked(&value[prev_key_offset..key_offset]) };
+        let key = unsafe { std::str::from_utf8_unchecked(value.get(prev_key_offset..key_offset)?) };
 
         let val_encoded = read_u32(value, jentry_offset).ok()?;
-        let val_jentry = JEntry::decode_jentry(val_encoded);
+        let val_jentry = JEntry::decode_jentry_at(val_encoded, jentry_offset, val_offset).ok()?;
         let val_length = val_jentry.length as usize;
 
         // first match the value with the same name, if not found,
         // then match the value with the ignoring case name.
         if name.eq(key) {
-            result = Some((val_jentry, val_encoded, val_offset));
+            result = Some((val_jentry, val_offset));
             break;
         } else if ignore_case && name.eq_ignore_ascii_case(key) && result.is_none() {
-            result = Some((val_jentry, val_encoded, val_offset));
+            result = Some((val_jentry, val_offset));
         }
 
         jentry_offset += 4;
@@ -706,30 +735,19 @@ fn get_jentry_by_index(
     offset: usize,
     header: u32,
     index: usize,
-) -> Option<(JEntry, u32, usize)> {
+) -> Option<(JEntry, usize)> {
     let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
     if index >= length {
         return None;
     }
-    let mut jentry_offset = offset + 4;
-    let mut val_offset = offset + 4 * length + 4;
-
-    for i in 0..length {
-        let encoded = read_u32(value, jentry_offset).ok()?;
-        let jentry = JEntry::decode_jentry(encoded);
-        let val_length = jentry.length as usize;
-        if i < index {
-            jentry_offset += 4;
-            val_offset += val_length;
-            continue;
-        }
-        return Some((jentry, encoded, val_offset));
-    }
-    None
+    let jentry_offset = offset + 4;
+    let val_offset = offset + 4 * length + 4;
+    get_jentry_at(value, jentry_offset, val_offset, index)
 }
 
 /// Get the keys of a `JSONB` object.
 pub fn object_keys(value: &[u8]) -> Option<Vec<u8>> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => val.object_keys().map(|val| val.to_vec()),
@@ -740,30 +758,24 @@ pub fn object_keys(value: &[u8]) -> Option<Vec<u8>> {
     let header = read_u32(value, 0).ok()?;
     match header & CONTAINER_HEADER_TYPE_MASK {
         OBJECT_CONTAINER_TAG => {
-            let mut buf: Vec<u8> = Vec::new();
             let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
-            let key_header = ARRAY_CONTAINER_TAG | length as u32;
-            buf.extend_from_slice(&key_header.to_be_bytes());
-
             let mut jentry_offset = 4;
             let mut key_offset = 8 * length + 4;
-            let mut key_offsets = Vec::with_capacity(length);
+            let mut key_jentries = Vec::with_capacity(capacity_of(length, value));
             for _ in 0..length {
                 let key_encoded = read_u32(value, jentry_offset).ok()?;
-                let key_jentry = JEntry::decode_jentry(key_encoded);
-                buf.extend_from_slice(&key_encoded.to_be_bytes());
+                let key_jentry =
+                    JEntry::decode_jentry_at(key_encoded, jentry_offset, key_offset).ok()?;
 
                 jentry_offset += 4;
                 key_offset += key_jentry.length as usize;
-                key_offsets.push(key_offset);
-            }
-            let mut prev_key_offset = 8 * length + 4;
-            for key_offset in key_offsets {
-                if key_offset > prev_key_offset {
-                    buf.extend_from_slice(&value[prev_key_offset..key_offset]);
-                }
-                prev_key_offset = key_offset;
+                key_jentries.push(key_jentry);
             }
+            // the keys are stored continuously, write them as the values of the array
+            let key_data = value.get(8 * length + 4..key_offset)?;
+            let mut buf: Vec<u8> = Vec::new();
+            let key_header = ARRAY_CONTAINER_TAG | length as u32;
+            write_container(key_header, &key_jentries, &[key_data], &mut buf).ok()?;
             Some(buf)
         }
         _ => None,
@@ -772,6 +784,7 @@ pub fn object_keys(value: &[u8]) -> Option<Vec<u8>> {
 
 /// Convert the values of a `JSONB` object to vector of key-value pairs.
 pub fn object_each(value: &[u8]) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => match val {
@@ -793,29 +806,33 @@ pub fn object_each(value: &[u8]) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
     match header & CONTAINER_HEADER_TYPE_MASK {
         OBJECT_CONTAINER_TAG => {
             let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
-            let mut items: Vec<(Vec<u8>, Vec<u8>)> = Vec::with_capacity(length);
-            let mut jentries: VecDeque<(JEntry, u32)> = VecDeque::with_capacity(length * 2);
+            let mut items: Vec<(Vec<u8>, Vec<u8>)> = Vec::with_capacity(capacity_of(length, value));
+            let mut jentries: VecDeque<JEntry> =
+                VecDeque::with_capacity(capacity_of(length * 2, value));
             let mut offset = 4;
+            let mut val_offset = 4 + length * 8;
 
             for _ in 0..length * 2 {
                 let encoded = read_u32(value, offset).ok()?;
+                let jentry = JEntry::decode_jentry_at(encoded, offset, val_offset).ok()?;
                 offset += 4;
-                jentries.push_back((JEntry::decode_jentry(encoded), encoded));
+                val_offset += jentry.length as usize;
+                jentries.push_back(jentry);
             }
 
-            let mut keys: VecDeque<Vec<u8>> = VecDeque::with_capacity(length);
+            let mut keys: VecDeque<Vec<u8>> = VecDeque::with_capacity(capacity_of(length, value));
             for _ in 0..length {
-                let (jentry, _) = jentries.pop_front().unwrap();
+                let jentry = jentries.pop_front().unwrap();
                 let key_len = jentry.length as usize;
-                keys.push_back(value[offset..offset + key_len].to_vec());
+                keys.push_back(value.get(offset..offset + key_len)?.to_vec());
                 offset += key_len;
             }
 
             for _ in 0..length {
-                let (jentry, encoded) = jentries.pop_front().unwrap();
+                let jentry = jentries.pop_front().unwrap();
                 let key = keys.pop_front().unwrap();
                 let val_length = jentry.length as usize;
-                let val = extract_by_jentry(&jentry, encoded, offset, value);
+                let val = extract_by_jentry(&jentry, offset, value)?;
                 offset += val_length;
                 items.push((key, val));
             }
@@ -827,6 +844,7 @@ pub fn object_each(value: &[u8]) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
 
 /// Convert the values of a `JSONB` array to vector.
 pub fn array_values(value: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => match val {
@@ -845,12 +863,12 @@ pub fn array_values(value: &[u8]) -> Option<Vec<Vec<u8>>> {
             let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
             let mut jentry_offset = 4;
             let mut val_offset = 4 * length + 4;
-            let mut items = Vec::with_capacity(length);
+            let mut items = Vec::with_capacity(capacity_of(length, value));
             for _ in 0..length {
                 let encoded = read_u32(value, jentry_offset).ok()?;
-                let jentry = JEntry::decode_jentry(encoded);
+                let jentry = JEntry::decode_jentry_at(encoded, jentry_offset, val_offset).ok()?;
                 let val_length = jentry.length as usize;
-                let item = extract_by_jentry(&jentry, encoded, val_offset, value);
+                let item = extract_by_jentry(&jentry, val_offset, value)?;
                 items.push(item);
 
                 jentry_offset += 4;
@@ -867,6 +885,19 @@ pub fn array_values(value: &[u8]) -> Option<Vec<Vec<u8>>> {
 /// In first level header, values compare as the following order:
 /// Scalar Null > Array > Object > Other Scalars(String > Number > Boolean).
 pub fn compare(left: &[u8], right: &[u8]) -> Result<Ordering, Error> {
+    compare_with_options(left, right, &CompareOptions::default())
+}
+
+/// Compare `JSONB` values with the options, which control the order of values
+/// with different types, the placement of null and the collation of strings.
+/// `convert_to_comparable_with_options` with the same options produces keys in the same order.
+pub fn compare_with_options(
+    left: &[u8],
+    right: &[u8],
+    opts: &CompareOptions,
+) -> Result<Ordering, Error> {
+    let left = strip_version_header(left);
+    let right = strip_version_header(right);
     if !is_jsonb(left) && !is_jsonb(right) {
         let lres = parse_value(left);
         let rres = parse_value(right);
@@ -874,7 +905,7 @@ pub fn compare(left: &[u8], right: &[u8]) -> Result<Ordering, Error> {
             (Ok(lval), Ok(rval)) => {
                 let lbuf = lval.to_vec();
                 let rbuf = rval.to_vec();
-                return compare(&lbuf, &rbuf);
+                return compare_with_options(&lbuf, &rbuf, opts);
             }
             (Ok(_), Err(_)) => {
                 return Ok(Ordering::Greater);
@@ -890,7 +921,7 @@ pub fn compare(left: &[u8], right: &[u8]) -> Result<Ordering, Error> {
         match parse_value(left) {
             Ok(lval) => {
                 let lbuf = lval.to_vec();
-                return compare(&lbuf, right);
+                return compare_with_options(&lbuf, right, opts);
             }
             Err(_) => {
                 return Ok(Ordering::Less);
@@ -900,7 +931,7 @@ pub fn compare(left: &[u8], right: &[u8]) -> Result<Ordering, Error> {
         match parse_value(right) {
             Ok(rval) => {
                 let rbuf = rval.to_vec();
-                return compare(left, &rbuf);
+                return compare_with_options(left, &rbuf, opts);
             }
             Err(_) => {
                 return Ok(Ordering::Greater);
@@ -919,57 +950,76 @@ pub fn compare(left: &[u8], right: &[u8]) -> Result<Ordering, Error> {
             let left_jentry = JEntry::decode_jentry(left_encoded);
             let right_encoded = read_u32(right, 4)?;
             let right_jentry = JEntry::decode_jentry(right_encoded);
-            compare_scalar(&left_jentry, &left[8..], &right_jentry, &right[8..])
+            compare_scalar(
+                &left_jentry,
+                &left[8..],
+                &right_jentry,
+                &right[8..],
+                0,
+                opts,
+            )
         }
         (ARRAY_CONTAINER_TAG, ARRAY_CONTAINER_TAG) => {
-            compare_array(left_header, &left[4..], right_header, &right[4..])
+            compare_array(left_header, &left[4..], right_header, &right[4..], 1, opts)
         }
         (OBJECT_CONTAINER_TAG, OBJECT_CONTAINER_TAG) => {
-            compare_object(left_header, &left[4..], right_header, &right[4..])
-        }
-        (SCALAR_CONTAINER_TAG, ARRAY_CONTAINER_TAG | OBJECT_CONTAINER_TAG) => {
-            let left_encoded = read_u32(left, 4)?;
-            let left_jentry = JEntry::decode_jentry(left_encoded);
-            match left_jentry.type_code {
-                NULL_TAG => Ok(Ordering::Greater),
-                _ => Ok(Ordering::Less),
-            }
+            compare_object(left_header, &left[4..], right_header, &right[4..], 1, opts)
         }
-        (ARRAY_CONTAINER_TAG | OBJECT_CONTAINER_TAG, SCALAR_CONTAINER_TAG) => {
-            let right_encoded = read_u32(right, 4)?;
-            let right_jentry = JEntry::decode_jentry(right_encoded);
-            match right_jentry.type_code {
-                NULL_TAG => Ok(Ordering::Less),
-                _ => Ok(Ordering::Greater),
-            }
+        (
+            SCALAR_CONTAINER_TAG | ARRAY_CONTAINER_TAG | OBJECT_CONTAINER_TAG,
+            SCALAR_CONTAINER_TAG | ARRAY_CONTAINER_TAG | OBJECT_CONTAINER_TAG,
+        ) => {
+            let left_level = opts.level(header_compare_level(left_header, left)?);
+            let right_level = opts.level(header_compare_level(right_header, right)?);
+            Ok(left_level.cmp(&right_level))
         }
-        (ARRAY_CONTAINER_TAG, OBJECT_CONTAINER_TAG) => Ok(Ordering::Greater),
-        (OBJECT_CONTAINER_TAG, ARRAY_CONTAINER_TAG) => Ok(Ordering::Less),
         (_, _) => Err(Error::InvalidJsonbHeader),
     }
 }
 
-fn extract_by_jentry(jentry: &JEntry, encoded: u32, offset: usize, value: &[u8]) -> Vec<u8> {
+// The level of the value by the first level header, the scalar is leveled by its `JEntry`.
+fn header_compare_level(header: u32, value: &[u8]) -> Result<u8, Error> {
+    match header & CONTAINER_HEADER_TYPE_MASK {
+        ARRAY_CONTAINER_TAG => Ok(ARRAY_LEVEL),
+        OBJECT_CONTAINER_TAG => Ok(OBJECT_LEVEL),
+        _ => {
+            let encoded = read_u32(value, 4)?;
+            let jentry = JEntry::decode_jentry(encoded);
+            Ok(jentry_compare_level(&jentry, &value[8..]))
+        }
+    }
+}
+
+fn extract_by_jentry(jentry: &JEntry, offset: usize, value: &[u8]) -> Option<Vec<u8>> {
     let length = jentry.length as usize;
+    let data = value.get(offset..offset + length)?;
     match jentry.type_code {
-        CONTAINER_TAG => value[offset..offset + length].to_vec(),
+        CONTAINER_TAG => {
+            let mut buf = Vec::with_capacity(length);
+            extend_without_offsets(&mut buf, data);
+            Some(buf)
+        }
         _ => {
             let mut buf = Vec::with_capacity(8 + length);
             buf.extend_from_slice(&SCALAR_CONTAINER_TAG.to_be_bytes());
-            buf.extend_from_slice(&encoded.to_be_bytes());
-            if jentry.length > 0 {
-                buf.extend_from_slice(&value[offset..offset + length]);
-            }
-            buf
+            buf.extend_from_slice(&jentry.encoded().to_be_bytes());
+            buf.extend_from_slice(data);
+            Some(buf)
         }
     }
 }
 
-// Different types of values have different levels and are definitely not equal
-fn jentry_compare_level(jentry: &JEntry) -> u8 {
+// Different types of values have different levels and are definitely not equal,
+// the containers are leveled by the header of the `value`.
+fn jentry_compare_level(jentry: &JEntry, value: &[u8]) -> u8 {
     match jentry.type_code {
         NULL_TAG => NULL_LEVEL,
-        CONTAINER_TAG => OBJECT_LEVEL,
+        CONTAINER_TAG => match read_u32(value, 0).map(|h| h & CONTAINER_HEADER_TYPE_MASK) {
+            Ok(ARRAY_CONTAINER_TAG) => ARRAY_LEVEL,
+            Ok(OBJECT_CONTAINER_TAG) => OBJECT_LEVEL,
+            _ => INVALID_LEVEL,
+        },
+        EXTENSION_TAG => EXTENSION_LEVEL,
         STRING_TAG => STRING_LEVEL,
         NUMBER_TAG => NUMBER_LEVEL,
         TRUE_TAG => TRUE_LEVEL,
@@ -978,44 +1028,60 @@ fn jentry_compare_level(jentry: &JEntry) -> u8 {
     }
 }
 
-// `Scalar` values compare as the following order
-// Null > Container(Array > Object) > String > Number > Boolean
+// `Scalar` values compare as the following order by default
+// Null > Container(Array > Object) > Extension > String > Number > Boolean
+// `depth` is the nesting depth of the container holding the values.
 fn compare_scalar(
     left_jentry: &JEntry,
     left: &[u8],
     right_jentry: &JEntry,
     right: &[u8],
+    depth: usize,
+    opts: &CompareOptions,
 ) -> Result<Ordering, Error> {
-    let left_level = jentry_compare_level(left_jentry);
-    let right_level = jentry_compare_level(right_jentry);
+    let left_level = opts.level(jentry_compare_level(left_jentry, left));
+    let right_level = opts.level(jentry_compare_level(right_jentry, right));
     if left_level != right_level {
         return Ok(left_level.cmp(&right_level));
     }
 
     match (left_jentry.type_code, right_jentry.type_code) {
         (NULL_TAG, NULL_TAG) => Ok(Ordering::Equal),
-        (CONTAINER_TAG, CONTAINER_TAG) => compare_container(left, right),
+        (CONTAINER_TAG, CONTAINER_TAG) => compare_container(left, right, depth + 1, opts),
         (STRING_TAG, STRING_TAG) => {
             let left_offset = left_jentry.length as usize;
-            let left_str = unsafe { std::str::from_utf8_unchecked(&left[..left_offset]) };
+            let left_str = read_bytes(left, 0, left_offset)?;
             let right_offset = right_jentry.length as usize;
-            let right_str = unsafe { std::str::from_utf8_unchecked(&right[..right_offset]) };
-            Ok(left_str.cmp(right_str))
+            let right_str = read_bytes(right, 0, right_offset)?;
+            opts.compare_str(left_str, right_str)
         }
         (NUMBER_TAG, NUMBER_TAG) => {
             let left_offset = left_jentry.length as usize;
-            let left_num = Number::decode(&left[..left_offset])?;
+            let left_num = Number::decode(read_bytes(left, 0, left_offset)?)?;
             let right_offset = right_jentry.length as usize;
-            let right_num = Number::decode(&right[..right_offset])?;
+            let right_num = Number::decode(read_bytes(right, 0, right_offset)?)?;
             Ok(left_num.cmp(&right_num))
         }
+        (EXTENSION_TAG, EXTENSION_TAG) => {
+            let left_offset = left_jentry.length as usize;
+            let left_ext = ExtensionValue::decode(read_bytes(left, 0, left_offset)?)?;
+            let right_offset = right_jentry.length as usize;
+            let right_ext = ExtensionValue::decode(read_bytes(right, 0, right_offset)?)?;
+            Ok(left_ext.cmp(&right_ext))
+        }
         (TRUE_TAG, TRUE_TAG) => Ok(Ordering::Equal),
         (FALSE_TAG, FALSE_TAG) => Ok(Ordering::Equal),
         (_, _) => Err(Error::InvalidJsonbJEntry),
     }
 }
 
-fn compare_container(left: &[u8], right: &[u8]) -> Result<Ordering, Error> {
+fn compare_container(
+    left: &[u8],
+    right: &[u8],
+    depth: usize,
+    opts: &CompareOptions,
+) -> Result<Ordering, Error> {
+    check_depth(depth, opts.max_depth)?;
     let left_header = read_u32(left, 0)?;
     let right_header = read_u32(right, 0)?;
 
@@ -1023,14 +1089,23 @@ fn compare_container(left: &[u8], right: &[u8]) -> Result<Ordering, Error> {
         left_header & CONTAINER_HEADER_TYPE_MASK,
         right_header & CONTAINER_HEADER_TYPE_MASK,
     ) {
-        (ARRAY_CONTAINER_TAG, ARRAY_CONTAINER_TAG) => {
-            compare_array(left_header, &left[4..], right_header, &right[4..])
-        }
-        (OBJECT_CONTAINER_TAG, OBJECT_CONTAINER_TAG) => {
-            compare_object(left_header, &left[4..], right_header, &right[4..])
-        }
-        (ARRAY_CONTAINER_TAG, OBJECT_CONTAINER_TAG) => Ok(Ordering::Greater),
-        (OBJECT_CONTAINER_TAG, ARRAY_CONTAINER_TAG) => Ok(Ordering::Less),
+        (ARRAY_CONTAINER_TAG, ARRAY_CONTAINER_TAG) => compare_array(
+            left_header,
+            &left[4..],
+            right_header,
+            &right[4..],
+            depth,
+            opts,
+        ),
+        (OBJECT_CONTAINER_TAG, OBJECT_CONTAINER_TAG) => compare_object(
+            left_header,
+            &left[4..],
+            right_header,
+            &right[4..],
+            depth,
+            opts,
+        ),
+        // arrays and objects are already compared by the levels
         (_, _) => Err(Error::InvalidJsonbHeader),
     }
 }
@@ -1041,6 +1116,8 @@ fn compare_array(
     left: &[u8],
     right_header: u32,
     right: &[u8],
+    depth: usize,
+    opts: &CompareOptions,
 ) -> Result<Ordering, Error> {
     let left_length = (left_header & CONTAINER_HEADER_LEN_MASK) as usize;
     let right_length = (right_header & CONTAINER_HEADER_LEN_MASK) as usize;
@@ -1055,15 +1132,18 @@ fn compare_array(
     };
     for _ in 0..length {
         let left_encoded = read_u32(left, jentry_offset)?;
-        let left_jentry = JEntry::decode_jentry(left_encoded);
+        let left_jentry = JEntry::decode_jentry_at(left_encoded, jentry_offset, left_val_offset)?;
         let right_encoded = read_u32(right, jentry_offset)?;
-        let right_jentry = JEntry::decode_jentry(right_encoded);
+        let right_jentry =
+            JEntry::decode_jentry_at(right_encoded, jentry_offset, right_val_offset)?;
 
         let order = compare_scalar(
             &left_jentry,
-            &left[left_val_offset..],
+            left.get(left_val_offset..).ok_or(Error::InvalidJsonb)?,
             &right_jentry,
-            &right[right_val_offset..],
+            right.get(right_val_offset..).ok_or(Error::InvalidJsonb)?,
+            depth,
+            opts,
         )?;
         if order != Ordering::Equal {
             return Ok(order);
@@ -1085,6 +1165,8 @@ fn compare_object(
     left: &[u8],
     right_header: u32,
     right: &[u8],
+    depth: usize,
+    opts: &CompareOptions,
 ) -> Result<Ordering, Error> {
     let left_length = (left_header & CONTAINER_HEADER_LEN_MASK) as usize;
     let right_length = (right_header & CONTAINER_HEADER_LEN_MASK) as usize;
@@ -1097,11 +1179,14 @@ fn compare_object(
     // read all left key jentries and right key jentries first.
     // Note: since the values are stored after the keys,
     // we must first read all the key jentries to get the correct value offset.
-    let mut left_key_jentries: VecDeque<JEntry> = VecDeque::with_capacity(left_length);
-    let mut right_key_jentries: VecDeque<JEntry> = VecDeque::with_capacity(right_length);
+    let mut left_key_jentries: VecDeque<JEntry> =
+        VecDeque::with_capacity(capacity_of(left_length, left));
+    let mut right_key_jentries: VecDeque<JEntry> =
+        VecDeque::with_capacity(capacity_of(right_length, right));
     for _ in 0..left_length {
         let left_encoded = read_u32(left, left_jentry_offset)?;
-        let left_key_jentry = JEntry::decode_jentry(left_encoded);
+        let left_key_jentry =
+            JEntry::decode_jentry_at(left_encoded, left_jentry_offset, left_val_offset)?;
 
         left_jentry_offset += 4;
         left_val_offset += left_key_jentry.length as usize;
@@ -1109,7 +1194,8 @@ fn compare_object(
     }
     for _ in 0..right_length {
         let right_encoded = read_u32(right, right_jentry_offset)?;
-        let right_key_jentry = JEntry::decode_jentry(right_encoded);
+        let right_key_jentry =
+            JEntry::decode_jentry_at(right_encoded, right_jentry_offset, right_val_offset)?;
 
         right_jentry_offset += 4;
         right_val_offset += right_key_jentry.length as usize;
@@ -1131,24 +1217,30 @@ fn compare_object(
 
         let key_order = compare_scalar(
             &left_key_jentry,
-            &left[left_key_offset..],
+            left.get(left_key_offset..).ok_or(Error::InvalidJsonb)?,
             &right_key_jentry,
-            &right[right_key_offset..],
+            right.get(right_key_offset..).ok_or(Error::InvalidJsonb)?,
+            depth,
+            opts,
         )?;
         if key_order != Ordering::Equal {
             return Ok(key_order);
         }
 
         let left_encoded = read_u32(left, left_jentry_offset)?;
-        let left_val_jentry = JEntry::decode_jentry(left_encoded);
+        let left_val_jentry =
+            JEntry::decode_jentry_at(left_encoded, left_jentry_offset, left_val_offset)?;
         let right_encoded = read_u32(right, right_jentry_offset)?;
-        let right_val_jentry = JEntry::decode_jentry(right_encoded);
+        let right_val_jentry =
+            JEntry::decode_jentry_at(right_encoded, right_jentry_offset, right_val_offset)?;
 
         let val_order = compare_scalar(
             &left_val_jentry,
-            &left[left_val_offset..],
+            left.get(left_val_offset..).ok_or(Error::InvalidJsonb)?,
             &right_val_jentry,
-            &right[right_val_offset..],
+            right.get(right_val_offset..).ok_or(Error::InvalidJsonb)?,
+            depth,
+            opts,
         )?;
         if val_order != Ordering::Equal {
             return Ok(val_order);
@@ -1165,6 +1257,93 @@ fn compare_object(
     Ok(left_length.cmp(&right_length))
 }
 
+/// Feed the `JSONB` value into the hasher without decoding all values.
+/// Values that `compare` reports as equal have the same hash, such as `1` and `1.0`,
+/// and the hash is the same as the `Hash` of the decoded `Value`.
+/// `JSON` text is parsed first, invalid text is hashed by its bytes.
+/// Values nested deeper than 128 levels fail with `Error::RecursionLimitExceeded`.
+pub fn hash_value<H: Hasher>(value: &[u8], state: &mut H) -> Result<(), Error> {
+    let value = strip_version_header(value);
+    if !is_jsonb(value) {
+        match parse_value(value) {
+            Ok(val) => val.hash(state),
+            Err(_) => {
+                state.write_u8(INVALID_LEVEL);
+                value.hash(state);
+            }
+        }
+        return Ok(());
+    }
+
+    let header = read_u32(value, 0)?;
+    match header & CONTAINER_HEADER_TYPE_MASK {
+        SCALAR_CONTAINER_TAG => {
+            let encoded = read_u32(value, 4)?;
+            let jentry = JEntry::decode_jentry(encoded);
+            let item = read_bytes(value, 8, jentry.length as usize)?;
+            hash_scalar(&jentry, item, 0, state)
+        }
+        ARRAY_CONTAINER_TAG | OBJECT_CONTAINER_TAG => hash_container(value, 1, state),
+        _ => Err(Error::InvalidJsonbHeader),
+    }
+}
+
+// Hash the scalar in the same way as the `Hash` of `Value`,
+// `depth` is the nesting depth of the container holding the value.
+fn hash_scalar<H: Hasher>(
+    jentry: &JEntry,
+    item: &[u8],
+    depth: usize,
+    state: &mut H,
+) -> Result<(), Error> {
+    match jentry.type_code {
+        NULL_TAG => state.write_u8(NULL_LEVEL),
+        TRUE_TAG => state.write_u8(TRUE_LEVEL),
+        FALSE_TAG => state.write_u8(FALSE_LEVEL),
+        STRING_TAG => {
+            state.write_u8(STRING_LEVEL);
+            let s = from_utf8(item).map_err(|_| Error::InvalidJsonb)?;
+            s.hash(state);
+        }
+        NUMBER_TAG => {
+            state.write_u8(NUMBER_LEVEL);
+            Number::decode(item)?.hash(state);
+        }
+        EXTENSION_TAG => {
+            state.write_u8(EXTENSION_LEVEL);
+            ExtensionValue::decode(item)?.hash(state);
+        }
+        CONTAINER_TAG => hash_container(item, depth + 1, state)?,
+        _ => return Err(Error::InvalidJsonbJEntry),
+    }
+    Ok(())
+}
+
+fn hash_container<H: Hasher>(value: &[u8], depth: usize, state: &mut H) -> Result<(), Error> {
+    check_depth(depth, DEFAULT_MAX_DEPTH)?;
+    let header = read_u32(value, 0)?;
+    let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
+    match header & CONTAINER_HEADER_TYPE_MASK {
+        ARRAY_CONTAINER_TAG => {
+            state.write_u8(ARRAY_LEVEL);
+            length.hash(state);
+            for (jentry, item) in iterate_array(value, header) {
+                hash_scalar(&jentry, item, depth, state)?;
+            }
+        }
+        OBJECT_CONTAINER_TAG => {
+            state.write_u8(OBJECT_LEVEL);
+            length.hash(state);
+            for (key, jentry, item) in iterate_object_entries(value, header) {
+                key.hash(state);
+                hash_scalar(&jentry, item, depth, state)?;
+            }
+        }
+        _ => return Err(Error::InvalidJsonbHeader),
+    }
+    Ok(())
+}
+
 /// Returns true if the `JSONB` is a Null.
 pub fn is_null(value: &[u8]) -> bool {
     as_null(value).is_some()
@@ -1172,6 +1351,7 @@ pub fn is_null(value: &[u8]) -> bool {
 
 /// If the `JSONB` is a Null, returns (). Returns None otherwise.
 pub fn as_null(value: &[u8]) -> Option<()> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => val.as_null(),
@@ -1198,6 +1378,7 @@ pub fn is_boolean(value: &[u8]) -> bool {
 
 /// If the `JSONB` is a Boolean, returns the associated bool. Returns None otherwise.
 pub fn as_bool(value: &[u8]) -> Option<bool> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => val.as_bool(),
@@ -1220,6 +1401,7 @@ pub fn as_bool(value: &[u8]) -> Option<bool> {
 
 /// Cast `JSONB` value to Boolean
 pub fn to_bool(value: &[u8]) -> Result<bool, Error> {
+    let value = strip_version_header(value);
     if let Some(v) = as_bool(value) {
         return Ok(v);
     } else if let Some(v) = as_str(value) {
@@ -1239,6 +1421,7 @@ pub fn is_number(value: &[u8]) -> bool {
 
 /// If the `JSONB` is a Number, returns the Number. Returns None otherwise.
 pub fn as_number(value: &[u8]) -> Option<Number> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => val.as_number().cloned(),
@@ -1253,7 +1436,7 @@ pub fn as_number(value: &[u8]) -> Option<Number> {
             match jentry.type_code {
                 NUMBER_TAG => {
                     let length = jentry.length as usize;
-                    Number::decode(&value[8..8 + length]).ok()
+                    Number::decode(value.get(8..8 + length)?).ok()
                 }
                 _ => None,
             }
@@ -1269,6 +1452,7 @@ pub fn is_i64(value: &[u8]) -> bool {
 
 /// Cast `JSONB` value to i64
 pub fn to_i64(value: &[u8]) -> Result<i64, Error> {
+    let value = strip_version_header(value);
     if let Some(v) = as_i64(value) {
         return Ok(v);
     } else if let Some(v) = as_bool(value) {
@@ -1287,6 +1471,7 @@ pub fn to_i64(value: &[u8]) -> Result<i64, Error> {
 
 /// If the `JSONB` is a Number, represent it as i64 if possible. Returns None otherwise.
 pub fn as_i64(value: &[u8]) -> Option<i64> {
+    let value = strip_version_header(value);
     match as_number(value) {
         Some(num) => num.as_i64(),
         None => None,
@@ -1300,14 +1485,44 @@ pub fn is_u64(value: &[u8]) -> bool {
 
 /// If the `JSONB` is a Number, represent it as u64 if possible. Returns None otherwise.
 pub fn as_u64(value: &[u8]) -> Option<u64> {
+    let value = strip_version_header(value);
     match as_number(value) {
         Some(num) => num.as_u64(),
         None => None,
     }
 }
 
+/// Returns true if the `JSONB` is a i128 Number. Returns false otherwise.
+pub fn is_i128(value: &[u8]) -> bool {
+    as_i128(value).is_some()
+}
+
+/// If the `JSONB` is a Number, represent it as i128 if possible. Returns None otherwise.
+pub fn as_i128(value: &[u8]) -> Option<i128> {
+    let value = strip_version_header(value);
+    match as_number(value) {
+        Some(num) => num.as_i128(),
+        None => None,
+    }
+}
+
+/// Returns true if the `JSONB` is a u128 Number. Returns false otherwise.
+pub fn is_u128(value: &[u8]) -> bool {
+    as_u128(value).is_some()
+}
+
+/// If the `JSONB` is a Number, represent it as u128 if possible. Returns None otherwise.
+pub fn as_u128(value: &[u8]) -> Option<u128> {
+    let value = strip_version_header(value);
+    match as_number(value) {
+        Some(num) => num.as_u128(),
+        None => None,
+    }
+}
+
 /// Cast `JSONB` value to u64
 pub fn to_u64(value: &[u8]) -> Result<u64, Error> {
+    let value = strip_version_header(value);
     if let Some(v) = as_u64(value) {
         return Ok(v);
     } else if let Some(v) = as_bool(value) {
@@ -1331,6 +1546,7 @@ pub fn is_f64(value: &[u8]) -> bool {
 
 /// If the `JSONB` is a Number, represent it as f64 if possible. Returns None otherwise.
 pub fn as_f64(value: &[u8]) -> Option<f64> {
+    let value = strip_version_header(value);
     match as_number(value) {
         Some(num) => num.as_f64(),
         None => None,
@@ -1339,6 +1555,7 @@ pub fn as_f64(value: &[u8]) -> Option<f64> {
 
 /// Cast `JSONB` value to f64
 pub fn to_f64(value: &[u8]) -> Result<f64, Error> {
+    let value = strip_version_header(value);
     if let Some(v) = as_f64(value) {
         return Ok(v);
     } else if let Some(v) = as_bool(value) {
@@ -1362,6 +1579,7 @@ pub fn is_string(value: &[u8]) -> bool {
 
 /// If the `JSONB` is a String, returns the String. Returns None otherwise.
 pub fn as_str(value: &[u8]) -> Option<Cow<'_, str>> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => match val {
@@ -1379,7 +1597,7 @@ pub fn as_str(value: &[u8]) -> Option<Cow<'_, str>> {
             match jentry.type_code {
                 STRING_TAG => {
                     let length = jentry.length as usize;
-                    let s = unsafe { std::str::from_utf8_unchecked(&value[8..8 + length]) };
+                    let s = from_utf8(value.get(8..8 + length)?).ok()?;
                     Some(Cow::Borrowed(s))
                 }
                 _ => None,
@@ -1391,6 +1609,7 @@ pub fn as_str(value: &[u8]) -> Option<Cow<'_, str>> {
 
 /// Cast `JSONB` value to String
 pub fn to_str(value: &[u8]) -> Result<String, Error> {
+    let value = strip_version_header(value);
     if let Some(v) = as_str(value) {
         return Ok(v.to_string());
     } else if let Some(v) = as_bool(value) {
@@ -1407,6 +1626,7 @@ pub fn to_str(value: &[u8]) -> Result<String, Error> {
 
 /// Returns true if the `JSONB` is An Array. Returns false otherwise.
 pub fn is_array(value: &[u8]) -> bool {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => val.is_array(),
@@ -1419,6 +1639,7 @@ pub fn is_array(value: &[u8]) -> bool {
 
 /// Returns true if the `JSONB` is An Object. Returns false otherwise.
 pub fn is_object(value: &[u8]) -> bool {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match parse_value(value) {
             Ok(val) => val.is_object(),
@@ -1429,8 +1650,10 @@ pub fn is_object(value: &[u8]) -> bool {
     matches!(header & CONTAINER_HEADER_TYPE_MASK, OBJECT_CONTAINER_TAG)
 }
 
-/// Convert `JSONB` value to `serde_json` Value
+/// Convert `JSONB` value to `serde_json` Value, values nested deeper than 128 levels
+/// fail with `Error::RecursionLimitExceeded`.
 pub fn to_serde_json(value: &[u8]) -> Result<serde_json::Value, Error> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         let json_str = std::str::from_utf8(value)?;
         return match serde_json::Value::from_str(json_str) {
@@ -1439,13 +1662,14 @@ pub fn to_serde_json(value: &[u8]) -> Result<serde_json::Value, Error> {
         };
     }
 
-    containter_to_serde_json(value)
+    containter_to_serde_json(value, 1)
 }
 
 /// Convert `JSONB` value to `serde_json` Object Value
 pub fn to_serde_json_object(
     value: &[u8],
 ) -> Result<Option<serde_json::Map<String, serde_json::Value>>, Error> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         let json_str = std::str::from_utf8(value)?;
         return match serde_json::Value::from_str(json_str) {
@@ -1468,9 +1692,9 @@ fn containter_to_serde_json_object(
 
     let obj_value = match header & CONTAINER_HEADER_TYPE_MASK {
         OBJECT_CONTAINER_TAG => {
-            let mut obj = serde_json::Map::with_capacity(length);
+            let mut obj = serde_json::Map::with_capacity(capacity_of(length, value));
             for (key, jentry, val) in iterate_object_entries(value, header) {
-                let item = scalar_to_serde_json(jentry, val)?;
+                let item = scalar_to_serde_json(jentry, val, 1)?;
                 obj.insert(key.to_string(), item);
             }
             Some(obj)
@@ -1483,23 +1707,25 @@ fn containter_to_serde_json_object(
     Ok(obj_value)
 }
 
-fn containter_to_serde_json(value: &[u8]) -> Result<serde_json::Value, Error> {
+// `depth` is the nesting depth of the container.
+fn containter_to_serde_json(value: &[u8], depth: usize) -> Result<serde_json::Value, Error> {
+    check_depth(depth, DEFAULT_MAX_DEPTH)?;
     let header = read_u32(value, 0).unwrap_or_default();
     let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
 
     let json_value = match header & CONTAINER_HEADER_TYPE_MASK {
         OBJECT_CONTAINER_TAG => {
-            let mut obj = serde_json::Map::with_capacity(length);
+            let mut obj = serde_json::Map::with_capacity(capacity_of(length, value));
             for (key, jentry, val) in iterate_object_entries(value, header) {
-                let item = scalar_to_serde_json(jentry, val)?;
+                let item = scalar_to_serde_json(jentry, val, depth)?;
                 obj.insert(key.to_string(), item);
             }
             serde_json::Value::Object(obj)
         }
         ARRAY_CONTAINER_TAG => {
-            let mut arr = Vec::with_capacity(length);
+            let mut arr = Vec::with_capacity(capacity_of(length, value));
             for (jentry, val) in iterate_array(value, header) {
-                let item = scalar_to_serde_json(jentry, val)?;
+                let item = scalar_to_serde_json(jentry, val, depth)?;
                 arr.push(item);
             }
             serde_json::Value::Array(arr)
@@ -1512,7 +1738,7 @@ fn containter_to_serde_json(value: &[u8]) -> Result<serde_json::Value, Error> {
                 }
             };
             let jentry = JEntry::decode_jentry(encoded);
-            scalar_to_serde_json(jentry, &value[8..])?
+            scalar_to_serde_json(jentry, &value[8..], depth)?
         }
         _ => {
             return Err(Error::InvalidJsonb);
@@ -1521,14 +1747,21 @@ fn containter_to_serde_json(value: &[u8]) -> Result<serde_json::Value, Error> {
     Ok(json_value)
 }
 
-fn scalar_to_serde_json(jentry: JEntry, value: &[u8]) -> Result<serde_json::Value, Error> {
+// `depth` is the nesting depth of the container holding the value.
+fn scalar_to_serde_json(
+    jentry: JEntry,
+    value: &[u8],
+    depth: usize,
+) -> Result<serde_json::Value, Error> {
     let json_value = match jentry.type_code {
         NULL_TAG => serde_json::Value::Null,
         TRUE_TAG => serde_json::Value::Bool(true),
         FALSE_TAG => serde_json::Value::Bool(false),
         NUMBER_TAG => {
             let len = jentry.length as usize;
-            let n = Number::decode(&value[..len])?;
+            let n = Number::decode(read_bytes(value, 0, len)?)?;
+            // convert the raw number text with its numeric value
+            let n = n.numeric().clone();
             match n {
                 Number::Int64(v) => serde_json::Value::Number(serde_json::Number::from(v)),
                 Number::UInt64(v) => serde_json::Value::Number(serde_json::Number::from(v)),
@@ -1538,14 +1771,54 @@ fn scalar_to_serde_json(jentry: JEntry, value: &[u8]) -> Result<serde_json::Valu
                         return Err(Error::InvalidJson);
                     }
                 },
+                Number::Decimal(v) => match v.as_i128() {
+                    Some(n) if i64::try_from(n).is_ok() => {
+                        serde_json::Value::Number(serde_json::Number::from(n as i64))
+                    }
+                    Some(n) if u64::try_from(n).is_ok() => {
+                        serde_json::Value::Number(serde_json::Number::from(n as u64))
+                    }
+                    _ => match serde_json::Number::from_f64(v.to_f64()) {
+                        Some(v) => serde_json::Value::Number(v),
+                        None => {
+                            return Err(Error::InvalidJson);
+                        }
+                    },
+                },
+                Number::Int128(v) => match i64::try_from(v) {
+                    Ok(v) => serde_json::Value::Number(serde_json::Number::from(v)),
+                    Err(_) => match serde_json::Number::from_f64(v as f64) {
+                        Some(v) => serde_json::Value::Number(v),
+                        None => {
+                            return Err(Error::InvalidJson);
+                        }
+                    },
+                },
+                Number::UInt128(v) => match u64::try_from(v) {
+                    Ok(v) => serde_json::Value::Number(serde_json::Number::from(v)),
+                    Err(_) => match serde_json::Number::from_f64(v as f64) {
+                        Some(v) => serde_json::Value::Number(v),
+                        None => {
+                            return Err(Error::InvalidJson);
+                        }
+                    },
+                },
+                Number::Raw(_) => {
+                    return Err(Error::InvalidJson);
+                }
             }
         }
         STRING_TAG => {
             let len = jentry.length as usize;
-            let s = unsafe { String::from_utf8_unchecked(value[..len].to_vec()) };
+            let s = from_utf8(read_bytes(value, 0, len)?)?.to_string();
             serde_json::Value::String(s)
         }
-        CONTAINER_TAG => containter_to_serde_json(value)?,
+        EXTENSION_TAG => {
+            let len = jentry.length as usize;
+            let v = ExtensionValue::decode(read_bytes(value, 0, len)?)?;
+            serde_json::Value::String(v.to_string())
+        }
+        CONTAINER_TAG => containter_to_serde_json(value, depth + 1)?,
         _ => {
             return Err(Error::InvalidJsonb);
         }
@@ -1553,8 +1826,22 @@ fn scalar_to_serde_json(jentry: JEntry, value: &[u8]) -> Result<serde_json::Valu
     Ok(json_value)
 }
 
-/// Convert `JSONB` value to String
+/// Convert `JSONB` value to String, corrupted values are converted to `null`.
+/// The nesting depth is not limited, use [`to_string_with_options`] to limit it.
 pub fn to_string(value: &[u8]) -> String {
+    to_string_or_null(value, &JsonFormatOptions::new().with_max_depth(usize::MAX))
+}
+
+/// Convert `JSONB` value to pretty String, the same as [`to_string`].
+pub fn to_pretty_string(value: &[u8]) -> String {
+    to_string_or_null(
+        value,
+        &JsonFormatOptions::pretty().with_max_depth(usize::MAX),
+    )
+}
+
+fn to_string_or_null(value: &[u8], opts: &JsonFormatOptions) -> String {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         // empty value as default null
         if value.is_empty() {
@@ -1565,59 +1852,85 @@ pub fn to_string(value: &[u8]) -> String {
     }
 
     let mut json = String::new();
-    if container_to_string(value, &mut 0, &mut json, &PrettyOpts::new(false)).is_err() {
+    if container_to_string(value, &mut 0, &mut json, opts, 0).is_err() {
         json.clear();
         json.push_str("null");
     }
     json
 }
 
-/// Convert `JSONB` value to pretty String
-pub fn to_pretty_string(value: &[u8]) -> String {
+/// Convert `JSONB` value to String with the format options.
+/// `JSON` text is parsed first, so that the options are applied as well.
+pub fn to_string_with_options(value: &[u8], opts: &JsonFormatOptions) -> Result<String, Error> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         // empty value as default null
         if value.is_empty() {
-            return "null".to_string();
-        } else {
-            return String::from_utf8_lossy(value).to_string();
+            return Ok("null".to_string());
         }
+        let buf = parse_value(value)?.try_to_vec()?;
+        return to_string_with_options(&buf, opts);
     }
 
     let mut json = String::new();
-    if container_to_string(value, &mut 0, &mut json, &PrettyOpts::new(true)).is_err() {
-        json.clear();
-        json.push_str("null");
-    }
-    json
-}
-
-struct PrettyOpts {
-    enabled: bool,
-    indent: usize,
-}
-
-impl PrettyOpts {
-    fn new(enabled: bool) -> Self {
-        Self { enabled, indent: 0 }
-    }
-
-    fn inc_indent(&self) -> Self {
-        Self {
-            enabled: self.enabled,
-            indent: self.indent + 2,
-        }
-    }
-
-    fn generate_indent(&self) -> String {
-        String::from_utf8(vec![0x20; self.indent]).unwrap()
+    container_to_string(value, &mut 0, &mut json, opts, 0)?;
+    Ok(json)
+}
+
+/// Convert `JSONB` value to the canonical `JSON` text defined by RFC 8785,
+/// semantically equal values are always converted to the same text,
+/// which can be hashed or signed.
+///
+/// The object keys are sorted by their UTF-16 code units, the numbers are
+/// written as IEEE 754 doubles the same as ECMAScript, and there is no whitespace.
+/// `NaN` and infinite numbers fail with `Error::NonFiniteNumber`.
+/// Timestamps are written in UTC, as the offset is not compared.
+/// `JSON` text is parsed first.
+pub fn to_canonical_string(value: &[u8]) -> Result<String, Error> {
+    to_string_with_options(value, &JsonFormatOptions::canonical())
+}
+
+/// Re-encode the `JSONB` value in the canonical form, so that equal values
+/// have identical bytes and can be compared or hashed by their bytes.
+///
+/// In the canonical form, object keys are sorted and unique, the last value is kept
+/// for duplicate keys, and each number has a single representation:
+/// integers, including integral floats and decimals, are written with the smallest width,
+/// decimals equal to a float are written as the float, other decimals without
+/// trailing zeros, and raw numbers are converted to their numeric value.
+/// Timestamps are converted to UTC, as the offset is not compared.
+/// The output has no version header, the input can also be `JSON` text.
+///
+/// Only this function guarantees the canonical form. `Value::to_vec`, `build_object`
+/// and the functions that modify values, like `object_insert` and `delete_by_keypath`,
+/// write the object keys sorted but keep the numbers as they are,
+/// so their output is canonical only if the input values are canonical.
+pub fn canonicalize(value: &[u8], buf: &mut Vec<u8>) -> Result<(), Error> {
+    let mut val = from_slice_checked(value)?;
+    canonicalize_value(&mut val);
+    val.try_write_to_vec(buf)
+}
+
+// The nesting depth is already limited by the decoder.
+fn canonicalize_value(val: &mut Value<'_>) {
+    match val {
+        Value::Number(num) => *num = num.canonical(),
+        // timestamps of the same instant are equal whatever the offset
+        Value::TimestampTz(ts) => ts.offset = 0,
+        Value::Array(arr) => arr.iter_mut().for_each(canonicalize_value),
+        Value::Object(obj) => obj.values_mut().for_each(canonicalize_value),
+        _ => {}
     }
 }
 
+// Write the container at `offset`, `level` is the nesting level used for the indentation,
+// the arrays and objects nested deeper than the limit fail.
 fn container_to_string(
     value: &[u8],
     offset: &mut usize,
     json: &mut String,
-    pretty_opts: &PrettyOpts,
+    opts: &JsonFormatOptions,
+    level: usize,
 ) -> Result<(), Error> {
     let header = read_u32(value, *offset)?;
     match header & CONTAINER_HEADER_TYPE_MASK {
@@ -1629,79 +1942,82 @@ fn container_to_string(
                 &mut jentry_offset,
                 &mut value_offset,
                 json,
-                pretty_opts,
+                opts,
+                level,
             )?;
         }
         ARRAY_CONTAINER_TAG => {
-            if pretty_opts.enabled {
-                json.push_str("[\n");
-            } else {
-                json.push('[');
-            }
+            check_depth(level + 1, opts.max_depth)?;
+            json.push('[');
             let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
             let mut jentry_offset = 4 + *offset;
             let mut value_offset = 4 + *offset + 4 * length;
-            let inner_pretty_ops = pretty_opts.inc_indent();
             for i in 0..length {
                 if i > 0 {
-                    if pretty_opts.enabled {
-                        json.push_str(",\n");
-                    } else {
-                        json.push(',');
-                    }
-                }
-                if pretty_opts.enabled {
-                    json.push_str(&inner_pretty_ops.generate_indent());
+                    json.push(',');
                 }
+                write_newline(json, opts, level + 1);
                 scalar_to_string(
                     value,
                     &mut jentry_offset,
                     &mut value_offset,
                     json,
-                    &inner_pretty_ops,
+                    opts,
+                    level + 1,
                 )?;
             }
-            if pretty_opts.enabled {
-                json.push('\n');
-                json.push_str(&pretty_opts.generate_indent());
+            if opts.is_pretty() {
+                // empty containers are written with a blank line, the same as before
+                if length == 0 {
+                    json.push('\n');
+                }
+                write_newline(json, opts, level);
             }
             json.push(']');
         }
         OBJECT_CONTAINER_TAG => {
-            if pretty_opts.enabled {
-                json.push_str("{\n");
-            } else {
-                json.push('{');
-            }
+            check_depth(level + 1, opts.max_depth)?;
+            json.push('{');
             let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
             let mut jentry_offset = 4 + *offset;
             let mut key_offset = 4 + *offset + 8 * length;
-            let mut keys = VecDeque::with_capacity(length);
+            let mut keys = Vec::with_capacity(capacity_of(length, value));
             for _ in 0..length {
                 let jentry_encoded = read_u32(value, jentry_offset)?;
-                let jentry = JEntry::decode_jentry(jentry_encoded);
+                let jentry = JEntry::decode_jentry_at(jentry_encoded, jentry_offset, key_offset)?;
                 let key_length = jentry.length as usize;
-                keys.push_back((key_offset, key_offset + key_length));
+                keys.push(read_bytes(value, key_offset, key_length)?);
                 jentry_offset += 4;
                 key_offset += key_length;
             }
+            // the offsets of the value jentries and the values
             let mut value_offset = key_offset;
-            let inner_pretty_ops = pretty_opts.inc_indent();
-            for i in 0..length {
+            let mut entries = Vec::with_capacity(keys.len());
+            for key in keys {
+                entries.push((key, jentry_offset, value_offset));
+                let jentry_encoded = read_u32(value, jentry_offset)?;
+                let jentry = JEntry::decode_jentry_at(jentry_encoded, jentry_offset, value_offset)?;
+                jentry_offset += 4;
+                value_offset += jentry.length as usize;
+            }
+            if opts.canonical {
+                entries.sort_by(|(k1, _, _), (k2, _, _)| {
+                    let k1 = String::from_utf8_lossy(k1);
+                    let k2 = String::from_utf8_lossy(k2);
+                    k1.encode_utf16().cmp(k2.encode_utf16())
+                });
+            } else if opts.sort_keys {
+                entries.sort_by_key(|(key, _, _)| *key);
+            }
+            for (i, (key, mut jentry_offset, mut value_offset)) in entries.into_iter().enumerate() {
                 if i > 0 {
-                    if pretty_opts.enabled {
-                        json.push_str(",\n");
-                    } else {
-                        json.push(',');
-                    }
+                    json.push(',');
                 }
-                let (key_start, key_end) = keys.pop_front().unwrap();
-                if pretty_opts.enabled {
-                    json.push_str(&inner_pretty_ops.generate_indent());
-                    escape_scalar_string(value, key_start, key_end, json);
+                write_newline(json, opts, level + 1);
+                opts.write_string(&String::from_utf8_lossy(key), json);
+                if opts.is_pretty() {
                     json.push_str(": ");
                 } else {
-                    escape_scalar_string(value, key_start, key_end, json);
                     json.push(':');
                 }
                 scalar_to_string(
@@ -1709,12 +2025,16 @@ fn container_to_string(
                     &mut jentry_offset,
                     &mut value_offset,
                     json,
-                    &inner_pretty_ops,
+                    opts,
+                    level + 1,
                 )?;
             }
-            if pretty_opts.enabled {
-                json.push('\n');
-                json.push_str(&pretty_opts.generate_indent());
+            if opts.is_pretty() {
+                // empty containers are written with a blank line, the same as before
+                if length == 0 {
+                    json.push('\n');
+                }
+                write_newline(json, opts, level);
             }
             json.push('}');
         }
@@ -1723,29 +2043,48 @@ fn container_to_string(
     Ok(())
 }
 
+// Start a new line with the indentation of the level in pretty text.
+fn write_newline(json: &mut String, opts: &JsonFormatOptions, level: usize) {
+    if opts.is_pretty() {
+        json.push('\n');
+        opts.write_indent(level, json);
+    }
+}
+
 fn scalar_to_string(
     value: &[u8],
     jentry_offset: &mut usize,
     value_offset: &mut usize,
     json: &mut String,
-    pretty_opts: &PrettyOpts,
+    opts: &JsonFormatOptions,
+    level: usize,
 ) -> Result<(), Error> {
     let jentry_encoded = read_u32(value, *jentry_offset)?;
-    let jentry = JEntry::decode_jentry(jentry_encoded);
+    let jentry = JEntry::decode_jentry_at(jentry_encoded, *jentry_offset, *value_offset)?;
     let length = jentry.length as usize;
     match jentry.type_code {
         NULL_TAG => json.push_str("null"),
         TRUE_TAG => json.push_str("true"),
         FALSE_TAG => json.push_str("false"),
         NUMBER_TAG => {
-            let num = Number::decode(&value[*value_offset..*value_offset + length])?;
-            json.push_str(&num.to_string());
+            let num = Number::decode(read_bytes(value, *value_offset, length)?)?;
+            opts.write_number(&num, json)?;
         }
         STRING_TAG => {
-            escape_scalar_string(value, *value_offset, *value_offset + length, json);
+            let s = String::from_utf8_lossy(read_bytes(value, *value_offset, length)?);
+            opts.write_string(&s, json);
+        }
+        EXTENSION_TAG => {
+            let mut v = ExtensionValue::decode(read_bytes(value, *value_offset, length)?)?;
+            if let (true, ExtensionValue::TimestampTz(ts)) = (opts.canonical, &mut v) {
+                ts.offset = 0;
+            }
+            json.push('\"');
+            json.push_str(&v.to_string());
+            json.push('\"');
         }
         CONTAINER_TAG => {
-            container_to_string(value, value_offset, json, pretty_opts)?;
+            container_to_string(value, value_offset, json, opts, level)?;
         }
         _ => {}
     }
@@ -1754,49 +2093,39 @@ fn scalar_to_string(
     Ok(())
 }
 
-fn escape_scalar_string(value: &[u8], start: usize, end: usize, json: &mut String) {
-    json.push('\"');
-    let mut last_start = start;
-    for i in start..end {
-        // add backslash for escaped characters.
-        let c = match value[i] {
-            0x5C => "\\\\",
-            0x22 => "\\\"",
-            0x08 => "\\b",
-            0x0C => "\\f",
-            0x0A => "\\n",
-            0x0D => "\\r",
-            0x09 => "\\t",
-            _ => {
-                continue;
-            }
-        };
-        if i > last_start {
-            let val = String::from_utf8_lossy(&value[last_start..i]);
-            json.push_str(&val);
-        }
-        json.push_str(c);
-        last_start = i + 1;
-    }
-    if last_start < end {
-        let val = String::from_utf8_lossy(&value[last_start..end]);
-        json.push_str(&val);
-    }
-    json.push('\"');
-}
-
 /// Convert `JSONB` value to comparable vector.
 /// The compare rules are the same as the `compare` function.
 /// Scalar Null > Array > Object > Other Scalars(String > Number > Boolean).
+///
+/// The vector ends with the types of the numbers and other details that are not compared,
+/// so that [`convert_from_comparable`] can restore the value. Values that are equal but
+/// have different number types, like `1` and `1.0`, only differ in the end,
+/// which keeps them next to each other when sorted.
+///
+/// Numbers are encoded with their exact decimal digits instead of the bits of the float,
+/// so decimals and large integers keep their order.
+///
+/// The vector starts with the format version `1`. The vectors of the older versions,
+/// whose type levels and number encoding differ, start with `0` and sort before
+/// all the new ones, [`convert_from_comparable`] rejects them with
+/// `Error::InvalidJsonbVersion`. They must be rebuilt.
 pub fn convert_to_comparable(value: &[u8], buf: &mut Vec<u8>) {
+    convert_to_comparable_with_options(value, buf, &CompareOptions::default())
+}
+
+/// Convert `JSONB` value to comparable vector, the bytes compare in the same order
+/// as `compare_with_options` with the same options.
+pub fn convert_to_comparable_with_options(value: &[u8], buf: &mut Vec<u8>, opts: &CompareOptions) {
+    let value = strip_version_header(value);
     let depth = 0;
     if !is_jsonb(value) {
         match parse_value(value) {
             Ok(val) => {
                 let val_buf = val.to_vec();
-                convert_to_comparable(&val_buf, buf);
+                convert_to_comparable_with_options(&val_buf, buf, opts);
             }
             Err(_) => {
+                buf.push(COMPARABLE_FORMAT_VERSION);
                 buf.push(depth);
                 buf.push(INVALID_LEVEL);
                 buf.extend_from_slice(value);
@@ -1804,6 +2133,8 @@ pub fn convert_to_comparable(value: &[u8], buf: &mut Vec<u8>) {
         }
         return;
     }
+    buf.push(COMPARABLE_FORMAT_VERSION);
+    let mut types = Vec::new();
     let header = read_u32(value, 0).unwrap_or_default();
     match header & CONTAINER_HEADER_TYPE_MASK {
         SCALAR_CONTAINER_TAG => {
@@ -1814,27 +2145,137 @@ pub fn convert_to_comparable(value: &[u8], buf: &mut Vec<u8>) {
                 }
             };
             let jentry = JEntry::decode_jentry(encoded);
-            scalar_convert_to_comparable(depth, &jentry, &value[8..], buf);
+            scalar_convert_to_comparable(depth, &jentry, &value[8..], buf, &mut types, opts);
         }
         ARRAY_CONTAINER_TAG => {
             buf.push(depth);
-            buf.push(ARRAY_LEVEL);
+            buf.push(opts.level(ARRAY_LEVEL));
             let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
-            array_convert_to_comparable(depth + 1, length, &value[4..], buf);
+            if let Some(depth) = next_comparable_depth(depth, opts) {
+                array_convert_to_comparable(depth, length, &value[4..], buf, &mut types, opts);
+            }
         }
         OBJECT_CONTAINER_TAG => {
             buf.push(depth);
-            buf.push(OBJECT_LEVEL);
+            buf.push(opts.level(OBJECT_LEVEL));
             let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
-            object_convert_to_comparable(depth + 1, length, &value[4..], buf);
+            if let Some(depth) = next_comparable_depth(depth, opts) {
+                object_convert_to_comparable(depth, length, &value[4..], buf, &mut types, opts);
+            }
         }
         _ => {}
     }
+    // The zero byte is less than the depth of any nested value,
+    // so that the types don't change the order of values with different lengths.
+    buf.push(0x00);
+    buf.extend_from_slice(&types);
+    buf.extend_from_slice(&(types.len() as u32).to_be_bytes());
+}
+
+/// Restore the `JSONB` value from the comparable vector of [`convert_to_comparable`].
+/// The numbers are restored with the same types, and the value is encoded
+/// the same as `Value::to_vec`.
+pub fn convert_from_comparable(key: &[u8], buf: &mut Vec<u8>) -> Result<(), Error> {
+    convert_from_comparable_with_options(key, buf, &CompareOptions::default())
+}
+
+/// Restore the `JSONB` value from the comparable vector of
+/// [`convert_to_comparable_with_options`], the options must be the same.
+pub fn convert_from_comparable_with_options(
+    key: &[u8],
+    buf: &mut Vec<u8>,
+    opts: &CompareOptions,
+) -> Result<(), Error> {
+    let Some((&version, key)) = key.split_first() else {
+        return Err(Error::InvalidJsonb);
+    };
+    if version != COMPARABLE_FORMAT_VERSION {
+        return Err(Error::InvalidJsonbVersion);
+    }
+    // invalid `JSON` text is kept as it is
+    if let [0, INVALID_LEVEL, text @ ..] = key {
+        buf.extend_from_slice(text);
+        return Ok(());
+    }
+    // the value is followed by a zero byte, the types and the length of the types
+    let len_offset = key.len().checked_sub(4).ok_or(Error::InvalidJsonb)?;
+    let len = read_u32(key, len_offset)? as usize;
+    let types_offset = len_offset.checked_sub(len).ok_or(Error::InvalidJsonb)?;
+    let mut types = &key[types_offset..len_offset];
+    let Some((0x00, mut key)) = key[..types_offset].split_last() else {
+        return Err(Error::InvalidJsonb);
+    };
+
+    let value = value_from_comparable(&mut key, &mut types, 0, opts)?;
+    if !key.is_empty() || !types.is_empty() {
+        return Err(Error::InvalidJsonb);
+    }
+    value.try_write_to_vec(buf)
+}
+
+// Decode the value of the `depth` written by `scalar_convert_to_comparable`.
+fn value_from_comparable(
+    key: &mut &[u8],
+    types: &mut &[u8],
+    depth: u8,
+    opts: &CompareOptions,
+) -> Result<Value<'static>, Error> {
+    let header = read_exact(key, 2)?;
+    if header[0] != depth {
+        return Err(Error::InvalidJsonb);
+    }
+    let level = opts.default_level(header[1]).ok_or(Error::InvalidJsonb)?;
+    let value = match level {
+        NULL_LEVEL => Value::Null,
+        TRUE_LEVEL => Value::Bool(true),
+        FALSE_LEVEL => Value::Bool(false),
+        STRING_LEVEL => Value::String(Cow::Owned(opts.comparable_decode_str(key, types)?)),
+        NUMBER_LEVEL => Value::Number(Number::comparable_decode(key, types)?),
+        EXTENSION_LEVEL => ExtensionValue::comparable_decode(key, types)?,
+        ARRAY_LEVEL => {
+            let depth = next_comparable_depth(depth, opts).ok_or(Error::RecursionLimitExceeded)?;
+            let mut values = Vec::new();
+            while key.first() == Some(&depth) {
+                values.push(value_from_comparable(key, types, depth, opts)?);
+            }
+            Value::Array(values)
+        }
+        _ => {
+            let depth = next_comparable_depth(depth, opts).ok_or(Error::RecursionLimitExceeded)?;
+            let mut obj = Object::new();
+            while key.first() == Some(&depth) {
+                let Value::String(k) = value_from_comparable(key, types, depth, opts)? else {
+                    return Err(Error::InvalidJsonb);
+                };
+                let v = value_from_comparable(key, types, depth, opts)?;
+                obj.insert(k.into_owned(), v);
+            }
+            Value::Object(obj)
+        }
+    };
+    Ok(value)
+}
+
+// The depth of the elements in the container at `depth`, which is also the nesting depth
+// of the container, returns `None` if it exceeds the limit or doesn't fit in the byte.
+fn next_comparable_depth(depth: u8, opts: &CompareOptions) -> Option<u8> {
+    depth
+        .checked_add(1)
+        .filter(|depth| *depth as usize <= opts.max_depth)
 }
 
-fn scalar_convert_to_comparable(depth: u8, jentry: &JEntry, value: &[u8], buf: &mut Vec<u8>) {
+// Write the value with its depth and level, the details that are not compared,
+// like the types of numbers, are written into `types`.
+fn scalar_convert_to_comparable(
+    depth: u8,
+    jentry: &JEntry,
+    value: &[u8],
+    buf: &mut Vec<u8>,
+    types: &mut Vec<u8>,
+    opts: &CompareOptions,
+) {
     buf.push(depth);
-    let level = jentry_compare_level(jentry);
+    let level = opts.level(jentry_compare_level(jentry, value));
     match jentry.type_code {
         CONTAINER_TAG => {
             let header = match read_u32(value, 0) {
@@ -1844,14 +2285,19 @@ fn scalar_convert_to_comparable(depth: u8, jentry: &JEntry, value: &[u8], buf: &
                 }
             };
             let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
+            let depth = next_comparable_depth(depth, opts);
             match header & CONTAINER_HEADER_TYPE_MASK {
                 ARRAY_CONTAINER_TAG => {
-                    buf.push(ARRAY_LEVEL);
-                    array_convert_to_comparable(depth + 1, length, &value[4..], buf);
+                    buf.push(level);
+                    if let Some(depth) = depth {
+                        array_convert_to_comparable(depth, length, &value[4..], buf, types, opts);
+                    }
                 }
                 OBJECT_CONTAINER_TAG => {
-                    buf.push(OBJECT_LEVEL);
-                    object_convert_to_comparable(depth + 1, length, &value[4..], buf);
+                    buf.push(level);
+                    if let Some(depth) = depth {
+                        object_convert_to_comparable(depth, length, &value[4..], buf, types, opts);
+                    }
                 }
                 _ => {}
             }
@@ -1861,19 +2307,21 @@ fn scalar_convert_to_comparable(depth: u8, jentry: &JEntry, value: &[u8], buf: &
             match jentry.type_code {
                 STRING_TAG => {
                     let length = jentry.length as usize;
-                    buf.extend_from_slice(&value[..length]);
+                    if let Ok(s) = read_bytes(value, 0, length) {
+                        opts.comparable_encode_str(s, buf, types);
+                    }
                 }
                 NUMBER_TAG => {
                     let length = jentry.length as usize;
-                    if let Ok(num) = Number::decode(&value[..length]) {
-                        let n = num.as_f64().unwrap();
-                        // https://github.com/rust-lang/rust/blob/9c20b2a8cc7588decb6de25ac6a7912dcef24d65/library/core/src/num/f32.rs#L1176-L1260
-                        let s = n.to_bits() as i64;
-                        let v = s ^ (((s >> 63) as u64) >> 1) as i64;
-                        let mut b = v.to_be_bytes();
-                        // Toggle top "sign" bit to ensure consistent sort order
-                        b[0] ^= 0x80;
-                        buf.extend_from_slice(&b);
+                    if let Ok(num) = read_bytes(value, 0, length).and_then(Number::decode) {
+                        num.comparable_encode(buf);
+                        num.comparable_encode_type(types);
+                    }
+                }
+                EXTENSION_TAG => {
+                    let length = jentry.length as usize;
+                    if let Ok(v) = read_bytes(value, 0, length).and_then(ExtensionValue::decode) {
+                        v.comparable_encode(buf, types);
                     }
                 }
                 _ => {}
@@ -1882,7 +2330,14 @@ fn scalar_convert_to_comparable(depth: u8, jentry: &JEntry, value: &[u8], buf: &
     }
 }
 
-fn array_convert_to_comparable(depth: u8, length: usize, value: &[u8], buf: &mut Vec<u8>) {
+fn array_convert_to_comparable(
+    depth: u8,
+    length: usize,
+    value: &[u8],
+    buf: &mut Vec<u8>,
+    types: &mut Vec<u8>,
+    opts: &CompareOptions,
+) {
     let mut jentry_offset = 0;
     let mut val_offset = 4 * length;
     for _ in 0..length {
@@ -1892,19 +2347,31 @@ fn array_convert_to_comparable(depth: u8, length: usize, value: &[u8], buf: &mut
                 return;
             }
         };
-        let jentry = JEntry::decode_jentry(encoded);
-        scalar_convert_to_comparable(depth, &jentry, &value[val_offset..], buf);
+        let Ok(jentry) = JEntry::decode_jentry_at(encoded, jentry_offset, val_offset) else {
+            return;
+        };
+        let Some(val) = value.get(val_offset..) else {
+            return;
+        };
+        scalar_convert_to_comparable(depth, &jentry, val, buf, types, opts);
         jentry_offset += 4;
         val_offset += jentry.length as usize;
     }
 }
 
-fn object_convert_to_comparable(depth: u8, length: usize, value: &[u8], buf: &mut Vec<u8>) {
+fn object_convert_to_comparable(
+    depth: u8,
+    length: usize,
+    value: &[u8],
+    buf: &mut Vec<u8>,
+    types: &mut Vec<u8>,
+    opts: &CompareOptions,
+) {
     let mut jentry_offset = 0;
     let mut val_offset = 8 * length;
 
     // read all key jentries first
-    let mut key_jentries: VecDeque<JEntry> = VecDeque::with_capacity(length);
+    let mut key_jentries: VecDeque<JEntry> = VecDeque::with_capacity(capacity_of(length, value));
     for _ in 0..length {
         let encoded = match read_u32(value, jentry_offset) {
             Ok(encoded) => encoded,
@@ -1912,7 +2379,9 @@ fn object_convert_to_comparable(depth: u8, length: usize, value: &[u8], buf: &mu
                 return;
             }
         };
-        let key_jentry = JEntry::decode_jentry(encoded);
+        let Ok(key_jentry) = JEntry::decode_jentry_at(encoded, jentry_offset, val_offset) else {
+            return;
+        };
 
         jentry_offset += 4;
         val_offset += key_jentry.length as usize;
@@ -1922,7 +2391,10 @@ fn object_convert_to_comparable(depth: u8, length: usize, value: &[u8], buf: &mu
     let mut key_offset = 8 * length;
     for _ in 0..length {
         let key_jentry = key_jentries.pop_front().unwrap();
-        scalar_convert_to_comparable(depth, &key_jentry, &value[key_offset..], buf);
+        let Some(key) = value.get(key_offset..) else {
+            return;
+        };
+        scalar_convert_to_comparable(depth, &key_jentry, key, buf, types, opts);
 
         let encoded = match read_u32(value, jentry_offset) {
             Ok(encoded) => encoded,
@@ -1930,8 +2402,13 @@ fn object_convert_to_comparable(depth: u8, length: usize, value: &[u8], buf: &mu
                 return;
             }
         };
-        let val_jentry = JEntry::decode_jentry(encoded);
-        scalar_convert_to_comparable(depth, &val_jentry, &value[val_offset..], buf);
+        let Ok(val_jentry) = JEntry::decode_jentry_at(encoded, jentry_offset, val_offset) else {
+            return;
+        };
+        let Some(val) = value.get(val_offset..) else {
+            return;
+        };
+        scalar_convert_to_comparable(depth, &val_jentry, val, buf, types, opts);
 
         jentry_offset += 4;
         key_offset += key_jentry.length as usize;
@@ -1998,6 +2475,7 @@ fn rand_scalar_value() -> Value<'static> {
 
 /// Traverse all the string fields in a jsonb value and check whether the conditions are met.
 pub fn traverse_check_string(value: &[u8], func: impl Fn(&[u8]) -> bool) -> bool {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         match parse_value(value) {
             Ok(val) => {
@@ -2026,7 +2504,9 @@ pub fn traverse_check_string(value: &[u8], func: impl Fn(&[u8]) -> bool) -> bool
             SCALAR_CONTAINER_TAG => 1,
             ARRAY_CONTAINER_TAG => length,
             OBJECT_CONTAINER_TAG => length * 2,
-            _ => unreachable!("invalid jsonb value"),
+            _ => {
+                return false;
+            }
         };
 
         let mut jentry_offset = offset + 4;
@@ -2038,15 +2518,23 @@ pub fn traverse_check_string(value: &[u8], func: impl Fn(&[u8]) -> bool) -> bool
                     return false;
                 }
             };
-            let jentry = JEntry::decode_jentry(encoded);
+            let Ok(jentry) = JEntry::decode_jentry_at(encoded, jentry_offset, val_offset) else {
+                return false;
+            };
             match jentry.type_code {
                 CONTAINER_TAG => {
                     offsets.push_back(val_offset);
                 }
                 STRING_TAG => {
                     let val_length = jentry.length as usize;
-                    if func(&value[val_offset..val_offset + val_length]) {
-                        return true;
+                    match value.get(val_offset..val_offset + val_length) {
+                        Some(val) if func(val) => {
+                            return true;
+                        }
+                        Some(_) => {}
+                        None => {
+                            return false;
+                        }
                     }
                 }
                 _ => {}
@@ -2063,11 +2551,13 @@ pub fn traverse_check_string(value: &[u8], func: impl Fn(&[u8]) -> bool) -> bool
 /// Concatenating two objects generates an object containing the union of their keys, taking the second object's value when there are duplicate keys.
 /// All other cases are treated by converting a non-array input into a single-element array, and then proceeding as for two arrays.
 pub fn concat(left: &[u8], right: &[u8], buf: &mut Vec<u8>) -> Result<(), Error> {
+    let left = strip_version_header(left);
+    let right = strip_version_header(right);
     if !is_jsonb(left) || !is_jsonb(right) {
         let left_val = from_slice(left)?;
         let right_val = from_slice(right)?;
         let result = concat_values(left_val, right_val);
-        result.write_to_vec(buf);
+        result.try_write_to_vec(buf)?;
         return Ok(());
     }
     concat_jsonb(left, right, buf)
@@ -2119,7 +2609,7 @@ fn concat_jsonb(left: &[u8], right: &[u8], buf: &mut Vec<u8>) -> Result<(), Erro
             for (key, jentry, item) in iterate_object_entries(right, right_header) {
                 builder.push_raw(key, jentry, item);
             }
-            builder.build_into(buf);
+            builder.build_into(buf)?;
         }
         (ARRAY_CONTAINER_TAG, ARRAY_CONTAINER_TAG) => {
             let mut builder = ArrayBuilder::new(left_len + right_len);
@@ -2129,13 +2619,13 @@ fn concat_jsonb(left: &[u8], right: &[u8], buf: &mut Vec<u8>) -> Result<(), Erro
             for (jentry, item) in iterate_array(right, right_header) {
                 builder.push_raw(jentry, item);
             }
-            builder.build_into(buf);
+            builder.build_into(buf)?;
         }
         (_, ARRAY_CONTAINER_TAG) => {
             let mut builder = ArrayBuilder::new(right_len + 1);
             match left_type {
                 OBJECT_CONTAINER_TAG => {
-                    let jentry = JEntry::make_container_jentry(left.len());
+                    let jentry = JEntry::make_container_jentry(left.len())?;
                     builder.push_raw(jentry, left);
                 }
                 _ => {
@@ -2146,7 +2636,7 @@ fn concat_jsonb(left: &[u8], right: &[u8], buf: &mut Vec<u8>) -> Result<(), Erro
             for (jentry, item) in iterate_array(right, right_header) {
                 builder.push_raw(jentry, item);
             }
-            builder.build_into(buf);
+            builder.build_into(buf)?;
         }
         (ARRAY_CONTAINER_TAG, _) => {
             let mut builder = ArrayBuilder::new(left_len + 1);
@@ -2155,7 +2645,7 @@ fn concat_jsonb(left: &[u8], right: &[u8], buf: &mut Vec<u8>) -> Result<(), Erro
             }
             match right_type {
                 OBJECT_CONTAINER_TAG => {
-                    let jentry = JEntry::make_container_jentry(right.len());
+                    let jentry = JEntry::make_container_jentry(right.len())?;
                     builder.push_raw(jentry, right);
                 }
                 _ => {
@@ -2163,13 +2653,13 @@ fn concat_jsonb(left: &[u8], right: &[u8], buf: &mut Vec<u8>) -> Result<(), Erro
                     builder.push_raw(jentry, &right[8..]);
                 }
             };
-            builder.build_into(buf);
+            builder.build_into(buf)?;
         }
         (_, _) => {
             let mut builder = ArrayBuilder::new(2);
             match left_type {
                 OBJECT_CONTAINER_TAG => {
-                    let jentry = JEntry::make_container_jentry(left.len());
+                    let jentry = JEntry::make_container_jentry(left.len())?;
                     builder.push_raw(jentry, left);
                 }
                 _ => {
@@ -2179,7 +2669,7 @@ fn concat_jsonb(left: &[u8], right: &[u8], buf: &mut Vec<u8>) -> Result<(), Erro
             };
             match right_type {
                 OBJECT_CONTAINER_TAG => {
-                    let jentry = JEntry::make_container_jentry(right.len());
+                    let jentry = JEntry::make_container_jentry(right.len())?;
                     builder.push_raw(jentry, right);
                 }
                 _ => {
@@ -2187,7 +2677,7 @@ fn concat_jsonb(left: &[u8], right: &[u8], buf: &mut Vec<u8>) -> Result<(), Erro
                     builder.push_raw(jentry, &right[8..]);
                 }
             };
-            builder.build_into(buf);
+            builder.build_into(buf)?;
         }
     }
     Ok(())
@@ -2200,6 +2690,7 @@ pub fn delete_by_keypath<'a, I: Iterator<Item = &'a KeyPath<'a>>>(
     keypath: I,
     buf: &mut Vec<u8>,
 ) -> Result<(), Error> {
+    let value = strip_version_header(value);
     let mut keypath: VecDeque<_> = keypath.collect();
     if !is_jsonb(value) {
         let mut value = parse_value(value)?;
@@ -2208,7 +2699,7 @@ pub fn delete_by_keypath<'a, I: Iterator<Item = &'a KeyPath<'a>>>(
             Value::Object(ref mut obj) => delete_value_object_by_keypath(obj, &mut keypath),
             _ => return Err(Error::InvalidJsonType),
         };
-        value.write_to_vec(buf);
+        value.try_write_to_vec(buf)?;
         return Ok(());
     }
     delete_by_keypath_jsonb(value, keypath, buf)
@@ -2263,20 +2754,20 @@ fn delete_by_keypath_jsonb<'a>(
         ARRAY_CONTAINER_TAG => {
             match delete_jsonb_array_by_keypath(value, header, &mut keypath)? {
                 Some(builder) => {
-                    builder.build_into(buf);
+                    builder.build_into(buf)?;
                 }
                 None => {
-                    buf.extend_from_slice(value);
+                    extend_without_offsets(buf, value);
                 }
             };
         }
         OBJECT_CONTAINER_TAG => {
             match delete_jsonb_object_by_keypath(value, header, &mut keypath)? {
                 Some(builder) => {
-                    builder.build_into(buf);
+                    builder.build_into(buf)?;
                 }
                 None => {
-                    buf.extend_from_slice(value);
+                    extend_without_offsets(buf, value);
                 }
             }
         }
@@ -2328,7 +2819,7 @@ fn delete_jsonb_array_by_keypath<'a, 'b>(
                                         None => return Ok(None),
                                     }
                                 }
-                                _ => unreachable!(),
+                                _ => return Err(Error::InvalidJsonb),
                             }
                         }
                         _ => return Ok(None),
@@ -2376,7 +2867,7 @@ fn delete_jsonb_object_by_keypath<'a, 'b>(
                                         None => return Ok(None),
                                     }
                                 }
-                                _ => unreachable!(),
+                                _ => return Err(Error::InvalidJsonb),
                             }
                         }
                         _ => return Ok(None),
@@ -2391,6 +2882,7 @@ fn delete_jsonb_object_by_keypath<'a, 'b>(
 
 /// Deletes a key (and its value) from a JSON object, or matching string value(s) from a JSON array.
 pub fn delete_by_name(value: &[u8], name: &str, buf: &mut Vec<u8>) -> Result<(), Error> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         let mut val = parse_value(value)?;
         match &mut val {
@@ -2402,7 +2894,7 @@ pub fn delete_by_name(value: &[u8], name: &str, buf: &mut Vec<u8>) -> Result<(),
             }
             _ => return Err(Error::InvalidJsonType),
         };
-        val.write_to_vec(buf);
+        val.try_write_to_vec(buf)?;
         return Ok(());
     }
     delete_jsonb_by_name(value, name, buf)
@@ -2419,10 +2911,13 @@ fn delete_jsonb_by_name(value: &[u8], name: &str, buf: &mut Vec<u8>) -> Result<(
                     builder.push_raw(key, jentry, item);
                 }
             }
-            builder.build_into(buf);
+            builder.build_into(buf)?;
         }
         ARRAY_CONTAINER_TAG => {
-            let mut builder = ArrayBuilder::new((header & CONTAINER_HEADER_LEN_MASK) as usize);
+            let mut builder = ArrayBuilder::new(capacity_of(
+                (header & CONTAINER_HEADER_LEN_MASK) as usize,
+                value,
+            ));
             for (jentry, item) in iterate_array(value, header) {
                 let matches = match jentry.type_code {
                     STRING_TAG => {
@@ -2435,7 +2930,7 @@ fn delete_jsonb_by_name(value: &[u8], name: &str, buf: &mut Vec<u8>) -> Result<(
                     builder.push_raw(jentry, item);
                 }
             }
-            builder.build_into(buf);
+            builder.build_into(buf)?;
         }
         _ => return Err(Error::InvalidJsonType),
     }
@@ -2444,6 +2939,7 @@ fn delete_jsonb_by_name(value: &[u8], name: &str, buf: &mut Vec<u8>) -> Result<(
 
 /// Deletes the array element with specified index (negative integers count from the end).
 pub fn delete_by_index(value: &[u8], index: i32, buf: &mut Vec<u8>) -> Result<(), Error> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         let mut val = parse_value(value)?;
         match &mut val {
@@ -2456,7 +2952,7 @@ pub fn delete_by_index(value: &[u8], index: i32, buf: &mut Vec<u8>) -> Result<()
             }
             _ => return Err(Error::InvalidJsonType),
         };
-        val.write_to_vec(buf);
+        val.try_write_to_vec(buf)?;
         return Ok(());
     }
     delete_jsonb_by_index(value, index, buf)
@@ -2470,16 +2966,19 @@ fn delete_jsonb_by_index(value: &[u8], index: i32, buf: &mut Vec<u8>) -> Result<
             let len = (header & CONTAINER_HEADER_LEN_MASK) as i32;
             let index = if index < 0 { len - index.abs() } else { index };
             if index < 0 || index >= len {
-                buf.extend_from_slice(value);
+                extend_without_offsets(buf, value);
             } else {
-                let mut builder = ArrayBuilder::new((header & CONTAINER_HEADER_LEN_MASK) as usize);
+                let mut builder = ArrayBuilder::new(capacity_of(
+                    (header & CONTAINER_HEADER_LEN_MASK) as usize,
+                    value,
+                ));
                 let index = index as usize;
                 for (i, entry) in iterate_array(value, header).enumerate() {
                     if i != index {
                         builder.push_raw(entry.0, entry.1);
                     }
                 }
-                builder.build_into(buf);
+                builder.build_into(buf)?;
             }
         }
         _ => return Err(Error::InvalidJsonType),
@@ -2494,14 +2993,16 @@ pub fn array_insert(
     new_value: &[u8],
     buf: &mut Vec<u8>,
 ) -> Result<(), Error> {
+    let value = strip_version_header(value);
+    let new_value = strip_version_header(new_value);
     if !is_jsonb(value) {
         let value = parse_value(value)?;
         let mut val_buf = Vec::new();
-        value.write_to_vec(&mut val_buf);
+        value.try_write_to_vec(&mut val_buf)?;
         if !is_jsonb(new_value) {
             let new_value = parse_value(new_value)?;
             let mut new_val_buf = Vec::new();
-            new_value.write_to_vec(&mut new_val_buf);
+            new_value.try_write_to_vec(&mut new_val_buf)?;
             return array_insert_jsonb(&val_buf, pos, &new_val_buf, buf);
         }
         return array_insert_jsonb(&val_buf, pos, new_value, buf);
@@ -2532,7 +3033,7 @@ fn array_insert_jsonb(
     } as usize;
     let len = len as usize;
 
-    let mut items = VecDeque::with_capacity(len);
+    let mut items = VecDeque::with_capacity(capacity_of(len, value));
     match header & CONTAINER_HEADER_TYPE_MASK {
         ARRAY_CONTAINER_TAG => {
             for (jentry, item) in iterate_array(value, header) {
@@ -2540,7 +3041,7 @@ fn array_insert_jsonb(
             }
         }
         OBJECT_CONTAINER_TAG => {
-            let jentry = JEntry::make_container_jentry(value.len());
+            let jentry = JEntry::make_container_jentry(value.len())?;
             items.push_back((jentry, value));
         }
         _ => {
@@ -2565,7 +3066,7 @@ fn array_insert_jsonb(
     let new_header = read_u32(new_value, 0)?;
     match new_header & CONTAINER_HEADER_TYPE_MASK {
         ARRAY_CONTAINER_TAG | OBJECT_CONTAINER_TAG => {
-            let new_jentry = JEntry::make_container_jentry(new_value.len());
+            let new_jentry = JEntry::make_container_jentry(new_value.len())?;
             builder.push_raw(new_jentry, new_value);
         }
         _ => {
@@ -2578,17 +3079,18 @@ fn array_insert_jsonb(
     while let Some((jentry, item)) = items.pop_front() {
         builder.push_raw(jentry, item);
     }
-    builder.build_into(buf);
+    builder.build_into(buf)?;
 
     Ok(())
 }
 
 /// Return a JSONB Array that contains only the distinct elements from the input JSONB Array.
 pub fn array_distinct(value: &[u8], buf: &mut Vec<u8>) -> Result<(), Error> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         let value = parse_value(value)?;
         let mut val_buf = Vec::new();
-        value.write_to_vec(&mut val_buf);
+        value.try_write_to_vec(&mut val_buf)?;
         return array_distinct_jsonb(&val_buf, buf);
     }
     array_distinct_jsonb(value, buf)
@@ -2608,7 +3110,7 @@ fn array_distinct_jsonb(value: &[u8], buf: &mut Vec<u8>) -> Result<(), Error> {
             }
         }
         OBJECT_CONTAINER_TAG => {
-            let jentry = JEntry::make_container_jentry(value.len());
+            let jentry = JEntry::make_container_jentry(value.len())?;
             builder.push_raw(jentry, value);
         }
         _ => {
@@ -2617,21 +3119,23 @@ fn array_distinct_jsonb(value: &[u8], buf: &mut Vec<u8>) -> Result<(), Error> {
             builder.push_raw(jentry, &value[8..]);
         }
     }
-    builder.build_into(buf);
+    builder.build_into(buf)?;
 
     Ok(())
 }
 
 /// Return a JSONB Array that contains the matching elements in the two input JSONB Arrays.
 pub fn array_intersection(value1: &[u8], value2: &[u8], buf: &mut Vec<u8>) -> Result<(), Error> {
+    let value1 = strip_version_header(value1);
+    let value2 = strip_version_header(value2);
     if !is_jsonb(value1) {
         let value1 = parse_value(value1)?;
         let mut val_buf1 = Vec::new();
-        value1.write_to_vec(&mut val_buf1);
+        value1.try_write_to_vec(&mut val_buf1)?;
         if !is_jsonb(value2) {
             let value2 = parse_value(value2)?;
             let mut val_buf2 = Vec::new();
-            value2.write_to_vec(&mut val_buf2);
+            value2.try_write_to_vec(&mut val_buf2)?;
             return array_intersection_jsonb(&val_buf1, &val_buf2, buf);
         }
         return array_intersection_jsonb(&val_buf1, value2, buf);
@@ -2655,7 +3159,7 @@ fn array_intersection_jsonb(value1: &[u8], value2: &[u8], buf: &mut Vec<u8>) ->
             }
         }
         OBJECT_CONTAINER_TAG => {
-            let jentry2 = JEntry::make_container_jentry(value2.len());
+            let jentry2 = JEntry::make_container_jentry(value2.len())?;
             item_map.insert((jentry2, value2), 1);
         }
         _ => {
@@ -2678,7 +3182,7 @@ fn array_intersection_jsonb(value1: &[u8], value2: &[u8], buf: &mut Vec<u8>) ->
             }
         }
         OBJECT_CONTAINER_TAG => {
-            let jentry1 = JEntry::make_container_jentry(value1.len());
+            let jentry1 = JEntry::make_container_jentry(value1.len())?;
             if item_map.contains_key(&(jentry1.clone(), value1)) {
                 builder.push_raw(jentry1, value1);
             }
@@ -2691,7 +3195,7 @@ fn array_intersection_jsonb(value1: &[u8], value2: &[u8], buf: &mut Vec<u8>) ->
             }
         }
     }
-    builder.build_into(buf);
+    builder.build_into(buf)?;
 
     Ok(())
 }
@@ -2699,14 +3203,16 @@ fn array_intersection_jsonb(value1: &[u8], value2: &[u8], buf: &mut Vec<u8>) ->
 /// Return a JSONB Array that contains the elements from one input JSONB Array
 /// that are not in another input JSONB Array.
 pub fn array_except(value1: &[u8], value2: &[u8], buf: &mut Vec<u8>) -> Result<(), Error> {
+    let value1 = strip_version_header(value1);
+    let value2 = strip_version_header(value2);
     if !is_jsonb(value1) {
         let value1 = parse_value(value1)?;
         let mut val_buf1 = Vec::new();
-        value1.write_to_vec(&mut val_buf1);
+        value1.try_write_to_vec(&mut val_buf1)?;
         if !is_jsonb(value2) {
             let value2 = parse_value(value2)?;
             let mut val_buf2 = Vec::new();
-            value2.write_to_vec(&mut val_buf2);
+            value2.try_write_to_vec(&mut val_buf2)?;
             return array_except_jsonb(&val_buf1, &val_buf2, buf);
         }
         return array_except_jsonb(&val_buf1, value2, buf);
@@ -2730,7 +3236,7 @@ fn array_except_jsonb(value1: &[u8], value2: &[u8], buf: &mut Vec<u8>) -> Result
             }
         }
         OBJECT_CONTAINER_TAG => {
-            let jentry2 = JEntry::make_container_jentry(value2.len());
+            let jentry2 = JEntry::make_container_jentry(value2.len())?;
             item_map.insert((jentry2, value2), 1);
         }
         _ => {
@@ -2754,7 +3260,7 @@ fn array_except_jsonb(value1: &[u8], value2: &[u8], buf: &mut Vec<u8>) -> Result
             }
         }
         OBJECT_CONTAINER_TAG => {
-            let jentry1 = JEntry::make_container_jentry(value1.len());
+            let jentry1 = JEntry::make_container_jentry(value1.len())?;
             if !item_map.contains_key(&(jentry1.clone(), value1)) {
                 builder.push_raw(jentry1, value1);
             }
@@ -2767,7 +3273,7 @@ fn array_except_jsonb(value1: &[u8], value2: &[u8], buf: &mut Vec<u8>) -> Result
             }
         }
     }
-    builder.build_into(buf);
+    builder.build_into(buf)?;
 
     Ok(())
 }
@@ -2775,14 +3281,16 @@ fn array_except_jsonb(value1: &[u8], value2: &[u8], buf: &mut Vec<u8>) -> Result
 /// Compares whether two JSONB Arrays have at least one element in common.
 /// Return TRUE if there is at least one element in common; otherwise return FALSE.
 pub fn array_overlap(value1: &[u8], value2: &[u8]) -> Result<bool, Error> {
+    let value1 = strip_version_header(value1);
+    let value2 = strip_version_header(value2);
     if !is_jsonb(value1) {
         let value1 = parse_value(value1)?;
         let mut val_buf1 = Vec::new();
-        value1.write_to_vec(&mut val_buf1);
+        value1.try_write_to_vec(&mut val_buf1)?;
         if !is_jsonb(value2) {
             let value2 = parse_value(value2)?;
             let mut val_buf2 = Vec::new();
-            value2.write_to_vec(&mut val_buf2);
+            value2.try_write_to_vec(&mut val_buf2)?;
             return array_overlap_jsonb(&val_buf1, &val_buf2);
         }
         return array_overlap_jsonb(&val_buf1, value2);
@@ -2804,7 +3312,7 @@ fn array_overlap_jsonb(value1: &[u8], value2: &[u8]) -> Result<bool, Error> {
             }
         }
         OBJECT_CONTAINER_TAG => {
-            let jentry2 = JEntry::make_container_jentry(value2.len());
+            let jentry2 = JEntry::make_container_jentry(value2.len())?;
             item_set.insert((jentry2, value2));
         }
         _ => {
@@ -2823,7 +3331,7 @@ fn array_overlap_jsonb(value1: &[u8], value2: &[u8]) -> Result<bool, Error> {
             }
         }
         OBJECT_CONTAINER_TAG => {
-            let jentry1 = JEntry::make_container_jentry(value1.len());
+            let jentry1 = JEntry::make_container_jentry(value1.len())?;
             if item_set.contains(&(jentry1, value1)) {
                 return Ok(true);
             }
@@ -2848,14 +3356,16 @@ pub fn object_insert(
     update_flag: bool,
     buf: &mut Vec<u8>,
 ) -> Result<(), Error> {
+    let value = strip_version_header(value);
+    let new_value = strip_version_header(new_value);
     if !is_jsonb(value) {
         let value = parse_value(value)?;
         let mut val_buf = Vec::new();
-        value.write_to_vec(&mut val_buf);
+        value.try_write_to_vec(&mut val_buf)?;
         if !is_jsonb(new_value) {
             let new_value = parse_value(new_value)?;
             let mut new_val_buf = Vec::new();
-            new_value.write_to_vec(&mut new_val_buf);
+            new_value.try_write_to_vec(&mut new_val_buf)?;
             return object_insert_jsonb(&val_buf, new_key, &new_val_buf, update_flag, buf);
         }
         return object_insert_jsonb(&val_buf, new_key, new_value, update_flag, buf);
@@ -2903,7 +3413,7 @@ fn object_insert_jsonb(
     let new_header = read_u32(new_value, 0)?;
     match new_header & CONTAINER_HEADER_TYPE_MASK {
         ARRAY_CONTAINER_TAG | OBJECT_CONTAINER_TAG => {
-            let new_jentry = JEntry::make_container_jentry(new_value.len());
+            let new_jentry = JEntry::make_container_jentry(new_value.len())?;
             builder.push_raw(new_key, new_jentry, new_value);
         }
         _ => {
@@ -2919,17 +3429,18 @@ fn object_insert_jsonb(
     for (key, jentry, item) in obj_iter {
         builder.push_raw(key, jentry, item);
     }
-    builder.build_into(buf);
+    builder.build_into(buf)?;
 
     Ok(())
 }
 
 /// Delete keys and values from a JSONB object value by keys.
 pub fn object_delete(value: &[u8], keys: &BTreeSet<&str>, buf: &mut Vec<u8>) -> Result<(), Error> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         let value = parse_value(value)?;
         let mut val_buf = Vec::new();
-        value.write_to_vec(&mut val_buf);
+        value.try_write_to_vec(&mut val_buf)?;
         return object_delete_jsonb(&val_buf, keys, buf);
     }
     object_delete_jsonb(value, keys, buf)
@@ -2952,17 +3463,18 @@ fn object_delete_jsonb(
         }
         builder.push_raw(key, jentry, item);
     }
-    builder.build_into(buf);
+    builder.build_into(buf)?;
 
     Ok(())
 }
 
 /// Pick keys and values from a JSONB object value by keys.
 pub fn object_pick(value: &[u8], keys: &BTreeSet<&str>, buf: &mut Vec<u8>) -> Result<(), Error> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         let value = parse_value(value)?;
         let mut val_buf = Vec::new();
-        value.write_to_vec(&mut val_buf);
+        value.try_write_to_vec(&mut val_buf)?;
         return object_pick_jsonb(&val_buf, keys, buf);
     }
     object_pick_jsonb(value, keys, buf)
@@ -2981,38 +3493,43 @@ fn object_pick_jsonb(value: &[u8], keys: &BTreeSet<&str>, buf: &mut Vec<u8>) ->
         }
         builder.push_raw(key, jentry, item);
     }
-    builder.build_into(buf);
+    builder.build_into(buf)?;
 
     Ok(())
 }
 
 /// Deletes all object fields that have null values from the given JSON value, recursively.
 /// Null values that are not object fields are untouched.
+/// Values nested deeper than 128 levels fail with `Error::RecursionLimitExceeded`.
 pub fn strip_nulls(value: &[u8], buf: &mut Vec<u8>) -> Result<(), Error> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         let mut json = parse_value(value)?;
-        strip_value_nulls(&mut json);
-        json.write_to_vec(buf);
+        strip_value_nulls(&mut json, 0)?;
+        json.try_write_to_vec(buf)?;
         return Ok(());
     }
     strip_nulls_jsonb(value, buf)
 }
 
-fn strip_value_nulls(val: &mut Value<'_>) {
+fn strip_value_nulls(val: &mut Value<'_>, depth: usize) -> Result<(), Error> {
     match val {
         Value::Array(arr) => {
+            check_depth(depth + 1, DEFAULT_MAX_DEPTH)?;
             for v in arr {
-                strip_value_nulls(v);
+                strip_value_nulls(v, depth + 1)?;
             }
         }
         Value::Object(ref mut obj) => {
+            check_depth(depth + 1, DEFAULT_MAX_DEPTH)?;
             for (_, v) in obj.iter_mut() {
-                strip_value_nulls(v);
+                strip_value_nulls(v, depth + 1)?;
             }
             obj.retain(|_, v| !matches!(v, Value::Null));
         }
         _ => {}
     }
+    Ok(())
 }
 
 fn strip_nulls_jsonb(value: &[u8], buf: &mut Vec<u8>) -> Result<(), Error> {
@@ -3020,21 +3537,22 @@ fn strip_nulls_jsonb(value: &[u8], buf: &mut Vec<u8>) -> Result<(), Error> {
 
     match header & CONTAINER_HEADER_TYPE_MASK {
         OBJECT_CONTAINER_TAG => {
-            let builder = strip_nulls_object(header, value)?;
-            builder.build_into(buf);
+            let builder = strip_nulls_object(header, value, 1)?;
+            builder.build_into(buf)?;
         }
         ARRAY_CONTAINER_TAG => {
-            let builder = strip_nulls_array(header, value)?;
-            builder.build_into(buf);
+            let builder = strip_nulls_array(header, value, 1)?;
+            builder.build_into(buf)?;
         }
         _ => buf.extend_from_slice(value),
     }
     Ok(())
 }
 
-fn strip_nulls_array(header: u32, value: &[u8]) -> Result<ArrayBuilder<'_>, Error> {
+fn strip_nulls_array(header: u32, value: &[u8], depth: usize) -> Result<ArrayBuilder<'_>, Error> {
+    check_depth(depth, DEFAULT_MAX_DEPTH)?;
     let len = (header & CONTAINER_HEADER_LEN_MASK) as usize;
-    let mut builder = ArrayBuilder::new(len);
+    let mut builder = ArrayBuilder::new(capacity_of(len, value));
 
     for (jentry, item) in iterate_array(value, header) {
         match jentry.type_code {
@@ -3042,12 +3560,12 @@ fn strip_nulls_array(header: u32, value: &[u8]) -> Result<ArrayBuilder<'_>, Erro
                 let item_header = read_u32(item, 0)?;
                 match item_header & CONTAINER_HEADER_TYPE_MASK {
                     OBJECT_CONTAINER_TAG => {
-                        builder.push_object(strip_nulls_object(item_header, item)?);
+                        builder.push_object(strip_nulls_object(item_header, item, depth + 1)?);
                     }
                     ARRAY_CONTAINER_TAG => {
-                        builder.push_array(strip_nulls_array(item_header, item)?);
+                        builder.push_array(strip_nulls_array(item_header, item, depth + 1)?);
                     }
-                    _ => unreachable!(),
+                    _ => return Err(Error::InvalidJsonb),
                 }
             }
             _ => builder.push_raw(jentry, item),
@@ -3056,7 +3574,8 @@ fn strip_nulls_array(header: u32, value: &[u8]) -> Result<ArrayBuilder<'_>, Erro
     Ok(builder)
 }
 
-fn strip_nulls_object(header: u32, value: &[u8]) -> Result<ObjectBuilder<'_>, Error> {
+fn strip_nulls_object(header: u32, value: &[u8], depth: usize) -> Result<ObjectBuilder<'_>, Error> {
+    check_depth(depth, DEFAULT_MAX_DEPTH)?;
     let mut builder = ObjectBuilder::new();
     for (key, jentry, item) in iterate_object_entries(value, header) {
         match jentry.type_code {
@@ -3064,12 +3583,12 @@ fn strip_nulls_object(header: u32, value: &[u8]) -> Result<ObjectBuilder<'_>, Er
                 let item_header = read_u32(item, 0)?;
                 match item_header & CONTAINER_HEADER_TYPE_MASK {
                     OBJECT_CONTAINER_TAG => {
-                        builder.push_object(key, strip_nulls_object(item_header, item)?);
+                        builder.push_object(key, strip_nulls_object(item_header, item, depth + 1)?);
                     }
                     ARRAY_CONTAINER_TAG => {
-                        builder.push_array(key, strip_nulls_array(item_header, item)?);
+                        builder.push_array(key, strip_nulls_array(item_header, item, depth + 1)?);
                     }
-                    _ => unreachable!(),
+                    _ => return Err(Error::InvalidJsonb),
                 }
             }
             NULL_TAG => continue,
@@ -3082,6 +3601,7 @@ fn strip_nulls_object(header: u32, value: &[u8]) -> Result<ObjectBuilder<'_>, Er
 /// Returns the type of the top-level JSON value as a text string.
 /// Possible types are object, array, string, number, boolean, and null.
 pub fn type_of(value: &[u8]) -> Result<&'static str, Error> {
+    let value = strip_version_header(value);
     if !is_jsonb(value) {
         return match value.first() {
             Some(v) => match v {
@@ -3108,6 +3628,11 @@ pub fn type_of(value: &[u8]) -> Result<&'static str, Error> {
                 TRUE_TAG | FALSE_TAG => Ok(TYPE_BOOLEAN),
                 NUMBER_TAG => Ok(TYPE_NUMBER),
                 STRING_TAG => Ok(TYPE_STRING),
+                EXTENSION_TAG => {
+                    let length = jentry.length as usize;
+                    let data = value.get(8..8 + length).ok_or(Error::InvalidJsonb)?;
+                    Ok(ExtensionValue::decode(data)?.type_name())
+                }
                 _ => Err(Error::InvalidJsonbJEntry),
             }
         }
@@ -3120,12 +3645,31 @@ pub fn type_of(value: &[u8]) -> Result<&'static str, Error> {
 // Check whether the value is `JSONB` format,
 // for compatibility with previous `JSON` string.
 pub(crate) fn is_jsonb(value: &[u8]) -> bool {
-    if let Some(v) = value.first() {
-        if matches!(*v, ARRAY_PREFIX | OBJECT_PREFIX | SCALAR_PREFIX) {
-            return true;
-        }
+    match detect_format(value) {
+        Format::Jsonb => true,
+        Format::VersionedJsonb(version) => (1..=JSONB_VERSION).contains(&version),
+        Format::Json => false,
     }
-    false
+}
+
+// Capacity for the elements of a container, a corrupted header may claim more
+// elements than the value can hold, each element has at least a 4-byte JEntry.
+fn capacity_of(length: usize, value: &[u8]) -> usize {
+    length.min(value.len() / 4)
+}
+
+// Read `length` bytes of the encoded value at `offset`.
+fn read_bytes(buf: &[u8], offset: usize, length: usize) -> Result<&[u8], Error> {
+    buf.get(offset..offset + length).ok_or(Error::InvalidJsonb)
+}
+
+// Fails if the nesting depth of arrays and objects exceeds the limit,
+// the recursive functions check it to avoid overflowing the stack.
+fn check_depth(depth: usize, max_depth: usize) -> Result<(), Error> {
+    if depth > max_depth {
+        return Err(Error::RecursionLimitExceeded);
+    }
+    Ok(())
 }
 
 fn read_u32(buf: &[u8], idx: usize) -> Result<u32, Error> {
@@ -12,9 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::VecDeque, str::from_utf8_unchecked};
+use std::{cmp::Ordering, collections::VecDeque, str::from_utf8_unchecked};
 
-use crate::{constants::CONTAINER_HEADER_LEN_MASK, jentry::JEntry, Error};
+use crate::{
+    constants::{
+        CONTAINER_HEADER_LEN_MASK, JENTRY_IS_OFF_FLAG, JENTRY_OFFSET_STRIDE, JENTRY_OFF_LEN_MASK,
+        OBJECT_KEY_SEARCH_THRESHOLD,
+    },
+    jentry::JEntry,
+    Error,
+};
 
 pub(crate) fn iterate_array(value: &[u8], header: u32) -> ArrayIterator<'_> {
     let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
@@ -66,12 +73,13 @@ impl<'a> Iterator for ArrayIterator<'a> {
             return None;
         }
         let encoded = read_u32(self.value, self.jentry_offset).ok()?;
-        let jentry = JEntry::decode_jentry(encoded);
+        let jentry = JEntry::decode_jentry_at(encoded, self.jentry_offset, self.val_offset).ok()?;
         let val_length = jentry.length as usize;
 
         let item = (
             jentry,
-            &self.value[self.val_offset..self.val_offset + val_length],
+            self.value
+                .get(self.val_offset..self.val_offset + val_length)?,
         );
 
         self.idx += 1;
@@ -99,11 +107,14 @@ impl<'a> Iterator for ObjectKeyIterator<'a> {
         }
 
         let encoded = read_u32(self.value, self.jentry_offset).ok()?;
-        let jentry = JEntry::decode_jentry(encoded);
+        let jentry = JEntry::decode_jentry_at(encoded, self.jentry_offset, self.key_offset).ok()?;
         let key_length = jentry.length as usize;
 
         let key = unsafe {
-            from_utf8_unchecked(&self.value[self.key_offset..self.key_offset + key_length])
+            from_utf8_unchecked(
+                self.value
+                    .get(self.key_offset..self.key_offset + key_length)?,
+            )
         };
 
         self.idx += 1;
@@ -136,15 +147,18 @@ impl<'a> Iterator for ObjectEntryIterator<'a> {
                 self.key_offset += key_jentry.length as usize;
 
                 let key = unsafe {
-                    std::str::from_utf8_unchecked(&self.value[prev_key_offset..self.key_offset])
+                    std::str::from_utf8_unchecked(self.value.get(prev_key_offset..self.key_offset)?)
                 };
 
                 let val_encoded = read_u32(self.value, self.jentry_offset).ok()?;
-                let val_jentry = JEntry::decode_jentry(val_encoded);
+                let val_jentry =
+                    JEntry::decode_jentry_at(val_encoded, self.jentry_offset, self.val_offset)
+                        .ok()?;
                 let val_length = val_jentry.length as usize;
 
-                let val =
-                    &self.value[self.val_offset..self.val_offset + val_jentry.length as usize];
+                let val = self
+                    .value
+                    .get(self.val_offset..self.val_offset + val_length)?;
                 let result = (key, val_jentry, val);
 
                 self.jentry_offset += 4;
@@ -159,16 +173,23 @@ impl<'a> Iterator for ObjectEntryIterator<'a> {
 
 impl<'a> ObjectEntryIterator<'a> {
     fn fill_keys(&mut self) {
-        let mut keys: VecDeque<JEntry> = VecDeque::with_capacity(self.length);
+        // a corrupted header may claim more keys than the value can hold
+        let capacity = self.length.min(self.value.len() / 4);
+        let mut keys: VecDeque<JEntry> = VecDeque::with_capacity(capacity);
         for _ in 0..self.length {
             let encoded = match read_u32(self.value, self.jentry_offset) {
                 Ok(encoded) => encoded,
                 Err(_) => {
-                    self.keys = None;
+                    self.keys = Some(VecDeque::new());
                     return;
                 }
             };
-            let key_jentry = JEntry::decode_jentry(encoded);
+            let Ok(key_jentry) =
+                JEntry::decode_jentry_at(encoded, self.jentry_offset, self.val_offset)
+            else {
+                self.keys = Some(VecDeque::new());
+                return;
+            };
 
             self.jentry_offset += 4;
             self.val_offset += key_jentry.length as usize;
@@ -178,6 +199,79 @@ impl<'a> ObjectEntryIterator<'a> {
     }
 }
 
+// Get the `JEntry` and the value position of the `index`th value of a container,
+// `jentry_offset` is the position of the first `JEntry` and `val_offset` is the position
+// of the first value. Only the `JEntries` back to the nearest one that stores an offset
+// are read, so the cost is bounded by `JENTRY_OFFSET_STRIDE` rather than the `index`.
+pub(crate) fn get_jentry_at(
+    value: &[u8],
+    jentry_offset: usize,
+    val_offset: usize,
+    index: usize,
+) -> Option<(JEntry, usize)> {
+    let mut start = val_offset;
+    let mut length = 0;
+    for i in (0..index).rev() {
+        let offset = jentry_offset + i * 4;
+        let encoded = read_u32(value, offset).ok()?;
+        let len = (encoded & JENTRY_OFF_LEN_MASK) as usize;
+        if encoded & JENTRY_IS_OFF_FLAG != 0 {
+            start = offset + len;
+            break;
+        }
+        length += len;
+    }
+    let start = start + length;
+    let offset = jentry_offset + index * 4;
+    let encoded = read_u32(value, offset).ok()?;
+    let jentry = JEntry::decode_jentry_at(encoded, offset, start).ok()?;
+    Some((jentry, start))
+}
+
+// Checks whether the keys of the large object at `offset` with `length` keys can be
+// searched by binary search. Only the encoder with the version header writes offset JEntries,
+// and it always writes the keys sorted without duplicates, so an offset in the last JEntry
+// of the first stride marks the sorted keys. The keys of other objects are in any order,
+// such as those built by `build_object` or by the releases before the version header.
+pub(crate) fn is_sorted_object(value: &[u8], offset: usize, length: usize) -> bool {
+    // the threshold is not less than the stride, so the JEntry always exists
+    if length < OBJECT_KEY_SEARCH_THRESHOLD {
+        return false;
+    }
+    let jentry_offset = offset + 4 + (JENTRY_OFFSET_STRIDE - 1) * 4;
+    matches!(read_u32(value, jentry_offset), Ok(encoded) if encoded & JENTRY_IS_OFF_FLAG != 0)
+}
+
+// Binary search the `name` in the sorted keys of the object at `offset` with `length` keys,
+// returns the `JEntry` and the position of the value of the first key equal to the `name`.
+pub(crate) fn search_object_value(
+    value: &[u8],
+    offset: usize,
+    length: usize,
+    name: &str,
+) -> Option<(JEntry, usize)> {
+    let jentry_offset = offset + 4;
+    let val_offset = offset + 4 + length * 8;
+    let read_key = |index: usize| {
+        let (jentry, key_offset) = get_jentry_at(value, jentry_offset, val_offset, index)?;
+        value.get(key_offset..key_offset + jentry.length as usize)
+    };
+    // find the first key that is not less than the `name`
+    let mut low = 0;
+    let mut high = length;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match read_key(mid)?.cmp(name.as_bytes()) {
+            Ordering::Less => low = mid + 1,
+            Ordering::Equal | Ordering::Greater => high = mid,
+        }
+    }
+    if low < length && read_key(low)? == name.as_bytes() {
+        return get_jentry_at(value, jentry_offset, val_offset, length + low);
+    }
+    None
+}
+
 fn read_u32(buf: &[u8], idx: usize) -> Result<u32, Error> {
     let bytes: [u8; 4] = buf
         .get(idx..idx + 4)
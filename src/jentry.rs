@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use super::constants::*;
+use super::error::Error;
 
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Ord)]
 pub(crate) struct JEntry {
@@ -27,6 +28,43 @@ impl JEntry {
         JEntry { type_code, length }
     }
 
+    // Decode the JEntry of a container value, which may store an offset instead of a length.
+    // The offset is the distance from the JEntry to the end of the value,
+    // `jentry_offset` is the position of the JEntry and `val_offset` is the position
+    // where the value starts, both relative to the same base.
+    // An offset pointing before the start of the value is corrupted.
+    pub(crate) fn decode_jentry_at(
+        encoded: u32,
+        jentry_offset: usize,
+        val_offset: usize,
+    ) -> Result<JEntry, Error> {
+        let mut jentry = Self::decode_jentry(encoded);
+        if encoded & JENTRY_IS_OFF_FLAG != 0 {
+            let val_end = jentry_offset + jentry.length as usize;
+            let length = val_end.checked_sub(val_offset).ok_or(Error::InvalidJsonb)?;
+            jentry.length = length as u32;
+        }
+        Ok(jentry)
+    }
+
+    // Encode the JEntry of the value at `index` of a container,
+    // every `JENTRY_OFFSET_STRIDE`th JEntry stores the distance
+    // from the `jentry_offset` to the `val_end` instead of the length.
+    // The offset must fit in the 28 bits of the JEntry like the length.
+    pub(crate) fn encoded_at(
+        &self,
+        index: usize,
+        jentry_offset: usize,
+        val_end: usize,
+    ) -> Result<u32, Error> {
+        if (index + 1) % JENTRY_OFFSET_STRIDE == 0 {
+            let offset = check_length(val_end - jentry_offset)?;
+            Ok(JENTRY_IS_OFF_FLAG | self.type_code | offset)
+        } else {
+            Ok(self.encoded())
+        }
+    }
+
     pub(crate) fn make_null_jentry() -> JEntry {
         JEntry {
             type_code: NULL_TAG,
@@ -48,28 +86,37 @@ impl JEntry {
         }
     }
 
-    pub(crate) fn make_string_jentry(length: usize) -> JEntry {
-        JEntry {
-            type_code: STRING_TAG,
-            length: length as u32,
-        }
+    pub(crate) fn make_jentry(type_code: u32, length: usize) -> Result<JEntry, Error> {
+        Ok(JEntry {
+            type_code,
+            length: check_length(length)?,
+        })
     }
 
-    pub(crate) fn make_number_jentry(length: usize) -> JEntry {
-        JEntry {
-            type_code: NUMBER_TAG,
-            length: length as u32,
-        }
+    pub(crate) fn make_string_jentry(length: usize) -> Result<JEntry, Error> {
+        Ok(JEntry {
+            type_code: STRING_TAG,
+            length: check_length(length)?,
+        })
     }
 
-    pub(crate) fn make_container_jentry(length: usize) -> JEntry {
-        JEntry {
+    pub(crate) fn make_container_jentry(length: usize) -> Result<JEntry, Error> {
+        Ok(JEntry {
             type_code: CONTAINER_TAG,
-            length: length as u32,
-        }
+            length: check_length(length)?,
+        })
     }
 
     pub(crate) fn encoded(&self) -> u32 {
         self.type_code | self.length
     }
 }
+
+// The length and offset are stored in the lower 28 bits of the JEntry,
+// larger values can't be encoded and would corrupt the type bits.
+fn check_length(length: usize) -> Result<u32, Error> {
+    if length > JENTRY_OFF_LEN_MASK as usize {
+        return Err(Error::ValueTooLarge);
+    }
+    Ok(length as u32)
+}
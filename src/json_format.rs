@@ -0,0 +1,252 @@
+// Copyright 2024 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Write;
+
+use super::constants::DEFAULT_MAX_DEPTH;
+use super::error::Error;
+use super::number::Number;
+use super::util::escape_string_with;
+
+/// The indentation of nested values in the `JSON` text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Indent {
+    /// Compact text without any whitespace.
+    #[default]
+    None,
+    /// Each value on its own line, indented by the number of spaces for each level.
+    Spaces(usize),
+    /// Each value on its own line, indented by a tab for each level.
+    Tab,
+}
+
+/// How `NaN`, `Infinity` and `-Infinity` numbers are written,
+/// none of them is valid `JSON`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonFinitePolicy {
+    /// Write the numbers as `NaN`, `inf` and `-inf`, the same as `to_string`.
+    #[default]
+    Display,
+    /// Write the numbers as `null`.
+    Null,
+    /// Write the numbers as the strings `"NaN"`, `"Infinity"` and `"-Infinity"`.
+    String,
+    /// Fail with `Error::NonFiniteNumber`.
+    Error,
+}
+
+/// How floating point numbers are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatFormat {
+    /// The shortest text that round-trips, with an exponent for very large
+    /// or very small numbers, like `0.1`, `1.0` and `1e20`.
+    #[default]
+    Shortest,
+    /// The shortest text that round-trips, without exponent,
+    /// like `0.1`, `1` and `100000000000000000000`.
+    Plain,
+    /// The shortest text that round-trips, always with an exponent,
+    /// like `1e-1`, `1e0` and `1e20`.
+    Scientific,
+}
+
+/// Options to control how `JSONB` values are written as `JSON` text.
+/// The default options produce the same text as `to_string`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonFormatOptions {
+    pub indent: Indent,
+    /// Write object keys sorted by their bytes instead of the stored order.
+    pub sort_keys: bool,
+    /// Escape all non-ASCII characters as `\uXXXX`, with surrogate pairs when needed.
+    pub ascii_only: bool,
+    /// Escape `<`, `>` and `&` as `\u003c`, `\u003e` and `\u0026`,
+    /// so that the text can be embedded in HTML safely.
+    pub html_safe: bool,
+    pub non_finite: NonFinitePolicy,
+    pub float_format: FloatFormat,
+    /// The maximum nesting depth of arrays and objects, deeper values fail
+    /// with `Error::RecursionLimitExceeded`. Defaults to 128.
+    pub max_depth: usize,
+    // Write the RFC 8785 canonical text, ignoring the other options.
+    pub(crate) canonical: bool,
+}
+
+impl Default for JsonFormatOptions {
+    fn default() -> Self {
+        Self {
+            indent: Indent::default(),
+            sort_keys: false,
+            ascii_only: false,
+            html_safe: false,
+            non_finite: NonFinitePolicy::default(),
+            float_format: FloatFormat::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            canonical: false,
+        }
+    }
+}
+
+impl JsonFormatOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options that produce the same text as `to_pretty_string`.
+    pub fn pretty() -> Self {
+        Self::default().with_indent(Indent::Spaces(2))
+    }
+
+    pub fn with_indent(mut self, indent: Indent) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    pub fn with_sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    pub fn with_ascii_only(mut self, ascii_only: bool) -> Self {
+        self.ascii_only = ascii_only;
+        self
+    }
+
+    pub fn with_html_safe(mut self, html_safe: bool) -> Self {
+        self.html_safe = html_safe;
+        self
+    }
+
+    pub fn with_non_finite(mut self, policy: NonFinitePolicy) -> Self {
+        self.non_finite = policy;
+        self
+    }
+
+    pub fn with_float_format(mut self, format: FloatFormat) -> Self {
+        self.float_format = format;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    // Options for the RFC 8785 `JSON` Canonicalization Scheme: no whitespace,
+    // keys sorted by UTF-16 code units, numbers written the same as ECMAScript
+    // and only the characters that must be escaped are escaped.
+    pub(crate) fn canonical() -> Self {
+        Self {
+            canonical: true,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn is_pretty(&self) -> bool {
+        !self.canonical && self.indent != Indent::None
+    }
+
+    // Write the indentation of the nesting level.
+    pub(crate) fn write_indent(&self, level: usize, json: &mut String) {
+        match self.indent {
+            Indent::None => {}
+            Indent::Spaces(n) => json.extend(std::iter::repeat(' ').take(n * level)),
+            Indent::Tab => json.extend(std::iter::repeat('\t').take(level)),
+        }
+    }
+
+    pub(crate) fn write_string(&self, value: &str, json: &mut String) {
+        // writing to a `String` never fails
+        let _ = escape_string_with(value, json, self.ascii_only, self.html_safe);
+    }
+
+    pub(crate) fn write_number(&self, num: &Number, json: &mut String) -> Result<(), Error> {
+        if self.canonical {
+            // all the numbers are IEEE 754 doubles in ECMAScript
+            let v = num.as_f64().ok_or(Error::InvalidJsonbNumber)?;
+            return write_ecmascript_number(v, json);
+        }
+        let Number::Float64(v) = num else {
+            let _ = write!(json, "{}", num);
+            return Ok(());
+        };
+        if !v.is_finite() {
+            match self.non_finite {
+                NonFinitePolicy::Display => {
+                    let _ = write!(json, "{}", num);
+                }
+                NonFinitePolicy::Null => json.push_str("null"),
+                NonFinitePolicy::String => {
+                    let s = if v.is_nan() {
+                        "\"NaN\""
+                    } else if v.is_sign_positive() {
+                        "\"Infinity\""
+                    } else {
+                        "\"-Infinity\""
+                    };
+                    json.push_str(s);
+                }
+                NonFinitePolicy::Error => return Err(Error::NonFiniteNumber),
+            }
+            return Ok(());
+        }
+        let _ = match self.float_format {
+            FloatFormat::Shortest => write!(json, "{}", num),
+            FloatFormat::Plain => write!(json, "{}", v),
+            FloatFormat::Scientific => write!(json, "{:e}", v),
+        };
+        Ok(())
+    }
+}
+
+// Write the number the same as `Number.prototype.toString` of ECMAScript,
+// with the shortest digits that round-trip, as required by RFC 8785.
+fn write_ecmascript_number(v: f64, json: &mut String) -> Result<(), Error> {
+    if !v.is_finite() {
+        return Err(Error::NonFiniteNumber);
+    }
+    // both 0 and -0 are written as 0
+    if v == 0.0 {
+        json.push('0');
+        return Ok(());
+    }
+    if v < 0.0 {
+        json.push('-');
+    }
+    // the shortest digits `d1.d2..dk` and the exponent `e`, the value is `0.d1d2..dk * 10^n`
+    let s = format!("{:e}", v.abs());
+    let (mantissa, exp) = s.split_once('e').unwrap_or((&s, "0"));
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let k = digits.len() as i32;
+    let n = exp.parse::<i32>().unwrap_or_default() + 1;
+    if k <= n && n <= 21 {
+        json.push_str(&digits);
+        json.extend(std::iter::repeat('0').take((n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        json.push_str(&digits[..n as usize]);
+        json.push('.');
+        json.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        json.push_str("0.");
+        json.extend(std::iter::repeat('0').take(-n as usize));
+        json.push_str(&digits);
+    } else {
+        json.push_str(&digits[..1]);
+        if k > 1 {
+            json.push('.');
+            json.push_str(&digits[1..]);
+        }
+        let _ = write!(json, "e{}{}", if n > 0 { '+' } else { '-' }, (n - 1).abs());
+    }
+    Ok(())
+}
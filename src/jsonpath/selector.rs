@@ -20,6 +20,12 @@ use std::cmp::Ordering;
 use std::collections::VecDeque;
 
 use crate::constants::*;
+use crate::format::extend_without_offsets;
+use crate::format::strip_version_header;
+use crate::iterator::get_jentry_at;
+use crate::iterator::is_sorted_object;
+use crate::iterator::search_object_value;
+use crate::jentry::JEntry;
 use crate::jsonpath::ArrayIndex;
 use crate::jsonpath::BinaryOperator;
 use crate::jsonpath::Expr;
@@ -29,6 +35,7 @@ use crate::jsonpath::JsonPath;
 use crate::jsonpath::Path;
 use crate::jsonpath::PathValue;
 use crate::number::Number;
+use crate::ser::make_container_header;
 use crate::Error;
 
 use nom::{
@@ -80,6 +87,7 @@ impl<'a> Selector<'a> {
         data: &mut Vec<u8>,
         offsets: &mut Vec<u64>,
     ) -> Result<(), Error> {
+        let root = strip_version_header(root);
         let mut poses = self.find_positions(root, None, &self.json_path.paths)?;
 
         if self.json_path.is_predicate() {
@@ -106,6 +114,7 @@ impl<'a> Selector<'a> {
     }
 
     pub fn exists(&'a self, root: &'a [u8]) -> Result<bool, Error> {
+        let root = strip_version_header(root);
         if self.json_path.is_predicate() {
             return Ok(true);
         }
@@ -114,6 +123,7 @@ impl<'a> Selector<'a> {
     }
 
     pub fn predicate_match(&'a self, root: &'a [u8]) -> Result<bool, Error> {
+        let root = strip_version_header(root);
         if !self.json_path.is_predicate() {
             return Err(Error::InvalidJsonPathPredicate);
         }
@@ -206,12 +216,13 @@ impl<'a> Selector<'a> {
         root_offset: usize,
         poses: &mut VecDeque<Position>,
     ) -> Result<(), Error> {
-        let (rest, (ty, length)) = decode_header(&root[root_offset..])?;
+        let (rest, (ty, length)) =
+            decode_header(root.get(root_offset..).ok_or(Error::InvalidJsonb)?)?;
         if ty != OBJECT_CONTAINER_TAG || length == 0 {
             return Ok(());
         }
-        let (rest, key_jentries) = decode_jentries(rest, length)?;
-        let (_, val_jentries) = decode_jentries(rest, length)?;
+        let (_, jentries) = decode_jentries(rest, length * 2)?;
+        let (key_jentries, val_jentries) = jentries.split_at(length);
         let mut offset = root_offset + 4 + length * 8;
         for (_, length) in key_jentries.iter() {
             offset += length;
@@ -236,7 +247,8 @@ impl<'a> Selector<'a> {
         root_length: usize,
         poses: &mut VecDeque<Position>,
     ) -> Result<(), Error> {
-        let (rest, (ty, length)) = decode_header(&root[root_offset..])?;
+        let (rest, (ty, length)) =
+            decode_header(root.get(root_offset..).ok_or(Error::InvalidJsonb)?)?;
         if ty != ARRAY_CONTAINER_TAG {
             // In lax mode, bracket wildcard allow Scalar value.
             poses.push_back(Position::Container((root_offset, root_length)));
@@ -264,12 +276,25 @@ impl<'a> Selector<'a> {
         name: &str,
         poses: &mut VecDeque<Position>,
     ) -> Result<(), Error> {
-        let (rest, (ty, length)) = decode_header(&root[root_offset..])?;
+        let (rest, (ty, length)) =
+            decode_header(root.get(root_offset..).ok_or(Error::InvalidJsonb)?)?;
         if ty != OBJECT_CONTAINER_TAG || length == 0 {
             return Ok(());
         }
-        let (rest, key_jentries) = decode_jentries(rest, length)?;
-        let (_, val_jentries) = decode_jentries(rest, length)?;
+        if is_sorted_object(root, root_offset, length) {
+            if let Some((jentry, offset)) = search_object_value(root, root_offset, length, name) {
+                let (jty, jlength) = (jentry.type_code, jentry.length as usize);
+                let pos = if jty == CONTAINER_TAG {
+                    Position::Container((offset, jlength))
+                } else {
+                    Position::Scalar((jty, offset, jlength))
+                };
+                poses.push_back(pos);
+            }
+            return Ok(());
+        }
+        let (_, jentries) = decode_jentries(rest, length * 2)?;
+        let (key_jentries, val_jentries) = jentries.split_at(length);
         let mut idx = 0;
         let mut offset = root_offset + 4 + length * 8;
         let mut found = false;
@@ -278,7 +303,7 @@ impl<'a> Selector<'a> {
                 offset += jlength;
                 continue;
             }
-            let (_, key) = decode_string(&root[offset..], *jlength)?;
+            let (_, key) = decode_string(root.get(offset..).ok_or(Error::InvalidJsonb)?, *jlength)?;
             if name == unsafe { std::str::from_utf8_unchecked(key) } {
                 found = true;
                 idx = i;
@@ -312,7 +337,7 @@ impl<'a> Selector<'a> {
         indices: &Vec<ArrayIndex>,
         poses: &mut VecDeque<Position>,
     ) -> Result<(), Error> {
-        let (rest, (ty, length)) = decode_header(&root[root_offset..])?;
+        let (_, (ty, length)) = decode_header(root.get(root_offset..).ok_or(Error::InvalidJsonb)?)?;
         if ty != ARRAY_CONTAINER_TAG || length == 0 {
             return Ok(());
         }
@@ -334,16 +359,12 @@ impl<'a> Selector<'a> {
         if val_indices.is_empty() {
             return Ok(());
         }
-        let (_, jentries) = decode_jentries(rest, length)?;
-        let mut offset = root_offset + 4 + length * 4;
-        let mut offsets = Vec::with_capacity(jentries.len());
-        for (_, jlength) in jentries.iter() {
-            offsets.push(offset);
-            offset += jlength;
-        }
+        let jentry_offset = root_offset + 4;
+        let val_offset = root_offset + 4 + length * 4;
         for i in val_indices {
-            let offset = offsets[i];
-            let (jty, jlength) = jentries[i];
+            let (jentry, offset) =
+                get_jentry_at(root, jentry_offset, val_offset, i).ok_or(Error::InvalidJsonb)?;
+            let (jty, jlength) = (jentry.type_code, jentry.length as usize);
             let pos = if jty == CONTAINER_TAG {
                 Position::Container((offset, jlength))
             } else {
@@ -376,14 +397,21 @@ impl<'a> Selector<'a> {
         while let Some(pos) = poses.pop_front() {
             match pos {
                 Position::Container((offset, length)) => {
-                    data.extend_from_slice(&root[offset..offset + length]);
+                    extend_without_offsets(
+                        data,
+                        root.get(offset..offset + length)
+                            .ok_or(Error::InvalidJsonb)?,
+                    );
                 }
                 Position::Scalar((ty, offset, length)) => {
                     data.write_u32::<BigEndian>(SCALAR_CONTAINER_TAG)?;
                     let jentry = ty | length as u32;
                     data.write_u32::<BigEndian>(jentry)?;
                     if length > 0 {
-                        data.extend_from_slice(&root[offset..offset + length]);
+                        data.extend_from_slice(
+                            root.get(offset..offset + length)
+                                .ok_or(Error::InvalidJsonb)?,
+                        );
                     }
                 }
             }
@@ -399,7 +427,7 @@ impl<'a> Selector<'a> {
         offsets: &mut Vec<u64>,
     ) -> Result<(), Error> {
         let len = poses.len();
-        let header = ARRAY_CONTAINER_TAG | len as u32;
+        let header = make_container_header(ARRAY_CONTAINER_TAG, len)?;
         // write header.
         data.write_u32::<BigEndian>(header)?;
         let mut jentry_offset = data.len();
@@ -408,17 +436,27 @@ impl<'a> Selector<'a> {
         while let Some(pos) = poses.pop_front() {
             let jentry = match pos {
                 Position::Container((offset, length)) => {
-                    data.extend_from_slice(&root[offset..offset + length]);
-                    CONTAINER_TAG | length as u32
+                    extend_without_offsets(
+                        data,
+                        root.get(offset..offset + length)
+                            .ok_or(Error::InvalidJsonb)?,
+                    );
+                    JEntry::make_container_jentry(length)?
                 }
                 Position::Scalar((ty, offset, length)) => {
                     if length > 0 {
-                        data.extend_from_slice(&root[offset..offset + length]);
+                        data.extend_from_slice(
+                            root.get(offset..offset + length)
+                                .ok_or(Error::InvalidJsonb)?,
+                        );
+                    }
+                    JEntry {
+                        type_code: ty,
+                        length: length as u32,
                     }
-                    ty | length as u32
                 }
             };
-            for (i, b) in jentry.to_be_bytes().iter().enumerate() {
+            for (i, b) in jentry.encoded().to_be_bytes().iter().enumerate() {
                 data[jentry_offset + i] = *b;
             }
             jentry_offset += 4;
@@ -556,16 +594,19 @@ impl<'a> Selector<'a> {
                             TRUE_TAG => PathValue::Boolean(true),
                             FALSE_TAG => PathValue::Boolean(false),
                             NUMBER_TAG => {
-                                let n = Number::decode(&root[offset..offset + length])?;
+                                let data = root.get(offset..offset + length);
+                                let n = Number::decode(data.ok_or(Error::InvalidJsonb)?)?;
                                 PathValue::Number(n)
                             }
                             STRING_TAG => {
-                                let v = &root[offset..offset + length];
+                                let v = root.get(offset..offset + length);
+                                let v = v.ok_or(Error::InvalidJsonb)?;
                                 PathValue::String(Cow::Owned(unsafe {
                                     String::from_utf8_unchecked(v.to_vec())
                                 }))
                             }
-                            _ => unreachable!(),
+                            // extension values can't be compared with the literals
+                            _ => continue,
                         };
                         values.push(value);
                     }
@@ -642,17 +683,20 @@ fn decode_header(input: &[u8]) -> IResult<&[u8], (u32, usize)> {
     })(input)
 }
 
-fn decode_jentry(input: &[u8]) -> IResult<&[u8], (u32, usize)> {
-    map(be_u32, |jentry| {
-        (
-            jentry & JENTRY_TYPE_MASK,
-            (jentry & JENTRY_OFF_LEN_MASK) as usize,
-        )
-    })(input)
-}
-
+// Decode all the `JEntries` of a container into the types and lengths of the values.
 fn decode_jentries(input: &[u8], length: usize) -> IResult<&[u8], Vec<(u32, usize)>> {
-    count(decode_jentry, length)(input)
+    let (rest, encoded) = count(be_u32, length)(input)?;
+    // positions relative to the first `JEntry`
+    let mut val_offset = 4 * length;
+    let mut jentries = Vec::with_capacity(length);
+    for (i, encoded) in encoded.into_iter().enumerate() {
+        let jentry = JEntry::decode_jentry_at(encoded, 4 * i, val_offset).map_err(|_| {
+            nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Verify))
+        })?;
+        val_offset += jentry.length as usize;
+        jentries.push((jentry.type_code, jentry.length as usize));
+    }
+    Ok((rest, jentries))
 }
 
 fn decode_string(input: &[u8], length: usize) -> IResult<&[u8], &[u8]> {
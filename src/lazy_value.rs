@@ -16,7 +16,7 @@ use std::borrow::Cow;
 use std::fmt::Debug;
 
 use crate::array_length;
-use crate::ser::Encoder;
+use crate::Error;
 use crate::Value;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -30,10 +30,7 @@ impl<'a> LazyValue<'a> {
     /// Serialize the JSONB Value into a byte stream.
     pub fn write_to_vec(&self, buf: &mut Vec<u8>) {
         match self {
-            LazyValue::Value(v) => {
-                let mut encoder = Encoder::new(buf);
-                encoder.encode(v)
-            }
+            LazyValue::Value(v) => v.write_to_vec(buf),
             LazyValue::Raw(v) => buf.extend_from_slice(v),
         };
     }
@@ -59,10 +56,12 @@ impl<'a> LazyValue<'a> {
         }
     }
 
-    pub fn to_value(&'a self) -> Cow<Value<'a>> {
+    /// Decode the raw bytes into a `Value`, returns an error if they are not valid,
+    /// including the strings that are not valid UTF-8.
+    pub fn to_value(&'a self) -> Result<Cow<'a, Value<'a>>, Error> {
         match self {
-            LazyValue::Value(v) => Cow::Borrowed(v),
-            LazyValue::Raw(v) => Cow::Owned(crate::from_slice(v.as_ref()).unwrap()),
+            LazyValue::Value(v) => Ok(Cow::Borrowed(v)),
+            LazyValue::Raw(v) => Ok(Cow::Owned(crate::from_slice_checked(v.as_ref())?)),
         }
     }
 }
@@ -25,11 +25,12 @@
 //!
 //! The `jsonb` encoding format is a tree-like structure. Each node contains a container header, a number of JEntry headers, and nested encoding values.
 //!
+//! - Optional 32-bit version header `0xFF 'J' 'B' <version>` before the root node. Use [`detect_format`] to tell `JSON` text, `JSONB` with and without the version header apart. All functions accept each of them.
 //! - 32-bit container header. 3 bits identify the type of value, including `scalar`, `object` and `array`, and 29 bits identify the number of JEntries in the `array` or `object`. The root node of the `jsonb` value is always a container header.
 //!   - `scalar` container header: `0x20000000`
 //!   - `object` container header: `0x40000000`
 //!   - `array` container header: `0x80000000`
-//! - 32-bit JEntry header. 1 bit identifies whether the JEntry stores a length or an offset, 3 bits identify the type of value, including `null`, `string`, `number`, `false`, `true` and `container`, and the remaining 28 bits identify the length or offset of the encoding value.
+//! - 32-bit JEntry header. 1 bit identifies whether the JEntry stores a length or an offset, 3 bits identify the type of value, including `null`, `string`, `number`, `false`, `true` and `container`, and the remaining 28 bits identify the length or offset of the encoding value, so an encoding value can be at most 256 MiB. In `JSONB` written with the version header, every 32nd JEntry of a container stores the offset from the JEntry to the end of its encoding value, so that a value can be located by reading at most 32 JEntries. `JSONB` without the header only stores lengths and can be read by all the releases.
 //!   - `null` JEntry header: `0x00000000`
 //!   - `string` JEntry header: `0x10000000`
 //!   - `number` JEntry header: `0x20000000`
@@ -64,29 +65,63 @@
 #![allow(clippy::uninlined_format_args)]
 
 mod builder;
+mod compare_options;
 mod constants;
 mod de;
 mod error;
+mod extension;
+mod format;
 mod from;
 mod functions;
 mod iterator;
 mod jentry;
+mod json_format;
 pub mod jsonpath;
 pub mod keypath;
 mod lazy_value;
 mod number;
 mod parser;
+mod push_parser;
 mod ser;
+mod stream;
 mod util;
+mod validate;
 mod value;
 
-pub use de::{from_slice, parse_jsonb};
+pub use compare_options::Collation;
+pub use compare_options::CompareOptions;
+pub use compare_options::NullOrder;
+pub use compare_options::TypeOrder;
+pub use de::{from_slice, from_slice_checked, from_slice_with_max_depth, parse_jsonb};
 pub use error::Error;
+pub use error::ParseErrorCode;
+pub use error::ValidationError;
+pub use error::ValidationErrorCode;
+pub use extension::Date;
+pub use extension::TimestampTz;
+pub use extension::Uuid;
+pub use format::detect_format;
+pub use format::Format;
+pub use format::JSONB_VERSION;
 #[allow(unused_imports)]
 pub use from::*;
 pub use functions::*;
+pub use json_format::FloatFormat;
+pub use json_format::Indent;
+pub use json_format::JsonFormatOptions;
+pub use json_format::NonFinitePolicy;
 pub use lazy_value::*;
+pub use number::Decimal;
 pub use number::Number;
+pub use number::RawNumber;
 pub use parser::parse_lazy_value;
 pub use parser::parse_value;
+pub use parser::parse_value_with_options;
+pub use parser::DuplicateKeyPolicy;
+pub use parser::ParseOptions;
+pub use push_parser::*;
+pub use ser::Encoder;
+pub use stream::*;
+pub use validate::validate;
+pub use validate::validate_with_max_depth;
 pub use value::*;
@@ -16,18 +16,46 @@ use std::cmp::Ordering;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::fmt::Write as _;
+use std::hash::Hash;
+use std::hash::Hasher;
 use std::io::Write;
 
 use ordered_float::OrderedFloat;
 
 use super::constants::*;
 use super::error::Error;
+use super::parser::parse_value_with_options;
+use super::parser::ParseOptions;
+use super::util::comparable_decode_bytes;
+use super::util::comparable_encode_bytes;
+use super::util::read_exact;
+use super::value::Value;
 
 #[derive(Debug, Clone)]
 pub enum Number {
     Int64(i64),
     UInt64(u64),
     Float64(f64),
+    Decimal(Decimal),
+    Int128(i128),
+    UInt128(u128),
+    /// The original text of the number, compared with its numeric value.
+    Raw(RawNumber),
+}
+
+/// The original text of a JSON number and its parsed value.
+#[derive(Debug, Clone)]
+pub struct RawNumber {
+    text: String,
+    value: Box<Number>,
+}
+
+/// An exact decimal number, the value is `value * 10^-scale`.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    pub value: i128,
+    pub scale: u8,
 }
 
 impl Number {
@@ -90,17 +118,51 @@ impl Number {
                 writer.write_all(&v.to_be_bytes())?;
                 Ok(9)
             }
+            Self::Int128(v) => {
+                if *v >= i64::MIN.into() && *v <= i64::MAX.into() {
+                    return Self::Int64(*v as i64).compact_encode(writer);
+                }
+                writer.write_all(&[NUMBER_INT])?;
+                writer.write_all(&v.to_be_bytes())?;
+                Ok(17)
+            }
+            Self::UInt128(v) => {
+                if *v <= u64::MAX.into() {
+                    return Self::UInt64(*v as u64).compact_encode(writer);
+                }
+                writer.write_all(&[NUMBER_UINT])?;
+                writer.write_all(&v.to_be_bytes())?;
+                Ok(17)
+            }
+            Self::Decimal(v) => {
+                writer.write_all(&[NUMBER_DECIMAL, v.scale])?;
+                if v.value >= i64::MIN.into() && v.value <= i64::MAX.into() {
+                    writer.write_all(&(v.value as i64).to_be_bytes())?;
+                    Ok(10)
+                } else {
+                    writer.write_all(&v.value.to_be_bytes())?;
+                    Ok(18)
+                }
+            }
+            Self::Raw(v) => {
+                writer.write_all(&[NUMBER_RAW])?;
+                writer.write_all(v.as_str().as_bytes())?;
+                Ok(1 + v.as_str().len())
+            }
         }
     }
 
     #[inline]
     pub fn decode(bytes: &[u8]) -> Result<Number, Error> {
-        let mut len = bytes.len();
-        assert!(len > 0);
-        len -= 1;
+        let Some(len) = bytes.len().checked_sub(1) else {
+            return Err(Error::InvalidJsonbNumber);
+        };
 
         let ty = bytes[0];
         let num = match ty {
+            NUMBER_ZERO | NUMBER_NAN | NUMBER_INF | NUMBER_NEG_INF if len != 0 => {
+                return Err(Error::InvalidJsonbNumber);
+            }
             NUMBER_ZERO => Number::UInt64(0),
             NUMBER_NAN => Number::Float64(f64::NAN),
             NUMBER_INF => Number::Float64(f64::INFINITY),
@@ -110,6 +172,7 @@ impl Number {
                 2 => Number::Int64(i16::from_be_bytes(bytes[1..].try_into().unwrap()) as i64),
                 4 => Number::Int64(i32::from_be_bytes(bytes[1..].try_into().unwrap()) as i64),
                 8 => Number::Int64(i64::from_be_bytes(bytes[1..].try_into().unwrap())),
+                16 => Number::Int128(i128::from_be_bytes(bytes[1..].try_into().unwrap())),
                 _ => {
                     return Err(Error::InvalidJsonbNumber);
                 }
@@ -119,11 +182,43 @@ impl Number {
                 2 => Number::UInt64(u16::from_be_bytes(bytes[1..].try_into().unwrap()) as u64),
                 4 => Number::UInt64(u32::from_be_bytes(bytes[1..].try_into().unwrap()) as u64),
                 8 => Number::UInt64(u64::from_be_bytes(bytes[1..].try_into().unwrap())),
+                16 => Number::UInt128(u128::from_be_bytes(bytes[1..].try_into().unwrap())),
+                _ => {
+                    return Err(Error::InvalidJsonbNumber);
+                }
+            },
+            NUMBER_FLOAT => match len {
+                8 => Number::Float64(f64::from_be_bytes(bytes[1..].try_into().unwrap())),
                 _ => {
                     return Err(Error::InvalidJsonbNumber);
                 }
             },
-            NUMBER_FLOAT => Number::Float64(f64::from_be_bytes(bytes[1..].try_into().unwrap())),
+            NUMBER_RAW => {
+                let Ok(text) = std::str::from_utf8(&bytes[1..]) else {
+                    return Err(Error::InvalidJsonbNumber);
+                };
+                match RawNumber::new(text) {
+                    Some(v) => Number::Raw(v),
+                    None => {
+                        return Err(Error::InvalidJsonbNumber);
+                    }
+                }
+            }
+            NUMBER_DECIMAL => {
+                let value = match len {
+                    9 => i64::from_be_bytes(bytes[2..].try_into().unwrap()) as i128,
+                    17 => i128::from_be_bytes(bytes[2..].try_into().unwrap()),
+                    _ => {
+                        return Err(Error::InvalidJsonbNumber);
+                    }
+                };
+                match Decimal::new(value, bytes[1]) {
+                    Some(v) => Number::Decimal(v),
+                    None => {
+                        return Err(Error::InvalidJsonbNumber);
+                    }
+                }
+            }
             _ => {
                 return Err(Error::InvalidJsonbNumber);
             }
@@ -133,6 +228,7 @@ impl Number {
 
     pub fn as_i64(&self) -> Option<i64> {
         match self {
+            Number::Raw(v) => v.value().as_i64(),
             Number::Int64(v) => Some(*v),
             Number::UInt64(v) => {
                 if *v <= i64::MAX.try_into().unwrap() {
@@ -142,11 +238,15 @@ impl Number {
                 }
             }
             Number::Float64(_) => None,
+            Number::Decimal(v) => v.as_i128().and_then(|v| v.try_into().ok()),
+            Number::Int128(v) => (*v).try_into().ok(),
+            Number::UInt128(v) => (*v).try_into().ok(),
         }
     }
 
     pub fn as_u64(&self) -> Option<u64> {
         match self {
+            Number::Raw(v) => v.value().as_u64(),
             Number::Int64(v) => {
                 if *v >= 0 {
                     Some(*v as u64)
@@ -156,14 +256,532 @@ impl Number {
             }
             Number::UInt64(v) => Some(*v),
             Number::Float64(_) => None,
+            Number::Decimal(v) => v.as_i128().and_then(|v| v.try_into().ok()),
+            Number::Int128(v) => (*v).try_into().ok(),
+            Number::UInt128(v) => (*v).try_into().ok(),
+        }
+    }
+
+    pub fn as_i128(&self) -> Option<i128> {
+        match self {
+            Number::Raw(v) => v.value().as_i128(),
+            Number::Int64(v) => Some(*v as i128),
+            Number::UInt64(v) => Some(*v as i128),
+            Number::Float64(_) => None,
+            Number::Decimal(v) => v.as_i128(),
+            Number::Int128(v) => Some(*v),
+            Number::UInt128(v) => (*v).try_into().ok(),
+        }
+    }
+
+    pub fn as_u128(&self) -> Option<u128> {
+        match self {
+            Number::Raw(v) => v.value().as_u128(),
+            Number::Int64(v) => (*v).try_into().ok(),
+            Number::UInt64(v) => Some(*v as u128),
+            Number::Float64(_) => None,
+            Number::Decimal(v) => v.as_i128().and_then(|v| v.try_into().ok()),
+            Number::Int128(v) => (*v).try_into().ok(),
+            Number::UInt128(v) => Some(*v),
         }
     }
 
     pub fn as_f64(&self) -> Option<f64> {
         match self {
+            Number::Raw(v) => v.value().as_f64(),
             Number::Int64(v) => Some(*v as f64),
             Number::UInt64(v) => Some(*v as f64),
             Number::Float64(v) => Some(*v),
+            Number::Decimal(v) => Some(v.to_f64()),
+            Number::Int128(v) => Some(*v as f64),
+            Number::UInt128(v) => Some(*v as f64),
+        }
+    }
+
+    // The numeric value used to compare and convert the number.
+    pub(crate) fn numeric(&self) -> &Number {
+        match self {
+            Number::Raw(v) => v.value(),
+            _ => self,
+        }
+    }
+
+    // The canonical representation of the number, equal numbers have the same representation.
+    // Integers, including the floats and decimals with integer values, are converted to
+    // `UInt64` or `Int64` if they fit, otherwise to `UInt128` or `Int128`.
+    // Other decimals are converted to `Float64` if they are equal to the float,
+    // otherwise the trailing zeros of the fraction digits are removed.
+    pub(crate) fn canonical(&self) -> Number {
+        let num = match self.numeric().clone() {
+            Number::Decimal(mut d) => {
+                while d.scale > 0 && d.value % 10 == 0 {
+                    d.value /= 10;
+                    d.scale -= 1;
+                }
+                let float = Number::Float64(d.to_f64());
+                if d.scale == 0 {
+                    Number::Int128(d.value)
+                } else if float == Number::Decimal(d) {
+                    float
+                } else {
+                    Number::Decimal(d)
+                }
+            }
+            num => num,
+        };
+        match num.as_int() {
+            Some((false, v)) => u64::try_from(v)
+                .map(Number::UInt64)
+                .unwrap_or(Number::UInt128(v)),
+            // `2^127` is converted to `i128::MIN` by the wrapping negation
+            Some((true, v)) if v <= i128::MIN.unsigned_abs() => {
+                let v = (v as i128).wrapping_neg();
+                i64::try_from(v)
+                    .map(Number::Int64)
+                    .unwrap_or(Number::Int128(v))
+            }
+            _ => num,
+        }
+    }
+
+    // The sign and the absolute value of integers, including the floats with integer values.
+    fn as_int(&self) -> Option<(bool, u128)> {
+        match self {
+            Number::Raw(v) => v.value().as_int(),
+            Number::Int64(v) => Some((*v < 0, v.unsigned_abs() as u128)),
+            Number::UInt64(v) => Some((false, *v as u128)),
+            Number::Int128(v) => Some((*v < 0, v.unsigned_abs())),
+            Number::UInt128(v) => Some((false, *v)),
+            // `u128::MAX as f64` is rounded up to 2^128
+            Number::Float64(v) if v.fract() == 0.0 && v.abs() < u128::MAX as f64 => {
+                Some((*v < 0.0, v.abs() as u128))
+            }
+            _ => None,
+        }
+    }
+
+    // Convert to exact decimal, floats are converted with the shortest representation.
+    fn as_decimal(&self) -> Option<Decimal> {
+        match self {
+            Number::Raw(v) => v.value().as_decimal(),
+            Number::Int64(v) => Some(Decimal::from(*v)),
+            Number::UInt64(v) => Some(Decimal::from(*v)),
+            Number::Float64(v) => Decimal::from_f64(*v),
+            Number::Decimal(v) => Some(*v),
+            Number::Int128(v) => Decimal::new(*v, 0),
+            Number::UInt128(v) => Decimal::new((*v).try_into().ok()?, 0),
+        }
+    }
+
+    // The sign, digits and exponent of the number as `0.d1d2..dk * 10^exp`,
+    // the digits are the shortest representation for floats, without trailing zeros,
+    // and empty for zero. Returns `None` if the number is NaN or infinite.
+    fn decimal_digits(&self) -> Option<DecimalDigits> {
+        if let Number::Raw(v) = self {
+            return v.value().decimal_digits();
+        }
+        let mut digits = DecimalDigits::new();
+        match self {
+            Number::Float64(v) if !v.is_finite() => return None,
+            _ if self.as_int().is_some() => {
+                let (negative, v) = self.as_int().unwrap();
+                let mut buffer = itoa::Buffer::new();
+                let s = buffer.format(v);
+                digits.extend(s.bytes());
+                digits.negative = negative;
+                digits.exp = s.len() as i32;
+            }
+            Number::Float64(v) => {
+                // the shortest representation, such as `-1.5e-7`
+                let mut buffer = DecimalDigits::new();
+                let _ = write!(buffer, "{:e}", v.abs());
+                let s = buffer.as_str();
+                let (mantissa, exp) = s.split_once('e').unwrap();
+                digits.extend(mantissa.bytes().filter(u8::is_ascii_digit));
+                digits.negative = *v < 0.0;
+                digits.exp = exp.parse::<i32>().unwrap() + 1;
+            }
+            _ => {
+                let v = self.as_decimal().unwrap();
+                let mut buffer = itoa::Buffer::new();
+                let s = buffer.format(v.value.unsigned_abs());
+                digits.extend(s.bytes());
+                digits.negative = v.value < 0;
+                digits.exp = s.len() as i32 - v.scale as i32;
+            }
+        }
+        while digits.len > 0 && digits.buf[digits.len - 1] == b'0' {
+            digits.len -= 1;
+        }
+        if digits.len == 0 {
+            digits.negative = false;
+            digits.exp = 0;
+        }
+        Some(digits)
+    }
+
+    // The comparable byte of the numbers that have no decimal digits.
+    fn non_finite_key(&self) -> u8 {
+        match self.as_f64() {
+            Some(v) if v.is_infinite() && v.is_sign_negative() => NUMBER_KEY_NEG_INF,
+            Some(v) if v.is_infinite() => NUMBER_KEY_INF,
+            _ => NUMBER_KEY_NAN,
+        }
+    }
+
+    // Encode the number to comparable bytes with the same order as the `Ord`.
+    // The number is encoded as a sign marker followed by the decimal exponent and digits,
+    // the bytes are inverted for negative numbers to reverse the order.
+    pub(crate) fn comparable_encode(&self, buf: &mut Vec<u8>) {
+        let Some(digits) = self.decimal_digits() else {
+            buf.push(self.non_finite_key());
+            return;
+        };
+        if digits.len == 0 {
+            buf.push(NUMBER_KEY_ZERO);
+            return;
+        }
+        let (negative, exp, digits) = (digits.negative, digits.exp, digits.as_bytes());
+        let exp = (exp as i16 as u16 ^ 0x8000).to_be_bytes();
+        if negative {
+            buf.push(NUMBER_KEY_NEG);
+            buf.extend(exp.iter().map(|b| !b));
+            buf.extend(digits.iter().map(|b| !b));
+            buf.push(0xFF);
+        } else {
+            buf.push(NUMBER_KEY_POS);
+            buf.extend_from_slice(&exp);
+            buf.extend_from_slice(digits);
+            buf.push(0x00);
+        }
+    }
+
+    // Append the type of the number to restore it from the comparable bytes,
+    // which only keep the numeric value. The type is the tag of the `JSONB` encoding,
+    // followed by the sign of floats, the scale of decimals or the text of raw numbers.
+    pub(crate) fn comparable_encode_type(&self, types: &mut Vec<u8>) {
+        match self {
+            Number::Float64(v) if v.is_finite() => {
+                types.extend_from_slice(&[NUMBER_FLOAT, v.is_sign_negative() as u8]);
+            }
+            Number::Decimal(v) => types.extend_from_slice(&[NUMBER_DECIMAL, v.scale]),
+            Number::Raw(v) => {
+                types.push(NUMBER_RAW);
+                comparable_encode_bytes(v.as_str().as_bytes(), types);
+            }
+            _ => {
+                let mut data = Vec::with_capacity(17);
+                // writing to a `Vec` never fails
+                let _ = self.compact_encode(&mut data);
+                types.push(data[0]);
+            }
+        }
+    }
+
+    // Decode the number written by `comparable_encode` and `comparable_encode_type`,
+    // and advance the inputs.
+    pub(crate) fn comparable_decode(key: &mut &[u8], types: &mut &[u8]) -> Result<Number, Error> {
+        // the value is `0.d1d2..dk * 10^exp`
+        let (negative, exp, digits) = match read_exact(key, 1)?[0] {
+            NUMBER_KEY_NAN | NUMBER_KEY_INF | NUMBER_KEY_NEG_INF | NUMBER_KEY_ZERO => {
+                (false, 0, Vec::new())
+            }
+            NUMBER_KEY_POS => {
+                let exp = u16::from_be_bytes(read_exact(key, 2)?.try_into().unwrap());
+                let len = key
+                    .iter()
+                    .position(|b| *b == 0x00)
+                    .ok_or(Error::InvalidJsonb)?;
+                let digits = read_exact(key, len + 1)?[..len].to_vec();
+                (false, exp, digits)
+            }
+            NUMBER_KEY_NEG => {
+                let exp = !u16::from_be_bytes(read_exact(key, 2)?.try_into().unwrap());
+                let len = key
+                    .iter()
+                    .position(|b| *b == 0xFF)
+                    .ok_or(Error::InvalidJsonb)?;
+                let digits = read_exact(key, len + 1)?[..len]
+                    .iter()
+                    .map(|b| !b)
+                    .collect();
+                (true, exp, digits)
+            }
+            _ => return Err(Error::InvalidJsonb),
+        };
+        if !digits.iter().all(u8::is_ascii_digit) {
+            return Err(Error::InvalidJsonb);
+        }
+        let exp = (exp ^ 0x8000) as i16 as i32;
+        // the integer value of the digits multiplied by `10^shift`
+        let integer = |shift: i32| -> Result<u128, Error> {
+            let shift = (exp - digits.len() as i32 + shift)
+                .try_into()
+                .map_err(|_| Error::InvalidJsonbNumber)?;
+            let mut value: u128 = 0;
+            for d in digits.iter() {
+                value = value
+                    .checked_mul(10)
+                    .and_then(|v| v.checked_add((d - b'0') as u128))
+                    .ok_or(Error::InvalidJsonbNumber)?;
+            }
+            10u128
+                .checked_pow(shift)
+                .and_then(|p| value.checked_mul(p))
+                .ok_or(Error::InvalidJsonbNumber)
+        };
+        let signed = |value: u128| -> Result<i128, Error> {
+            let value = if negative {
+                0i128.checked_sub_unsigned(value)
+            } else {
+                value.try_into().ok()
+            };
+            value.ok_or(Error::InvalidJsonbNumber)
+        };
+
+        let num = match read_exact(types, 1)?[0] {
+            NUMBER_ZERO => Number::UInt64(0),
+            NUMBER_NAN => Number::Float64(f64::NAN),
+            NUMBER_INF => Number::Float64(f64::INFINITY),
+            NUMBER_NEG_INF => Number::Float64(f64::NEG_INFINITY),
+            NUMBER_FLOAT => {
+                let negative_zero = read_exact(types, 1)?[0] == 1;
+                if digits.is_empty() {
+                    Number::Float64(if negative_zero { -0.0 } else { 0.0 })
+                } else {
+                    // the digits are the shortest representation of the float
+                    let sign = if negative { "-" } else { "" };
+                    let digits = std::str::from_utf8(&digits).unwrap();
+                    let v = format!("{}0.{}e{}", sign, digits, exp)
+                        .parse::<f64>()
+                        .map_err(|_| Error::InvalidJsonbNumber)?;
+                    Number::Float64(v)
+                }
+            }
+            NUMBER_INT => {
+                let v = signed(integer(0)?)?;
+                i64::try_from(v)
+                    .map(Number::Int64)
+                    .unwrap_or(Number::Int128(v))
+            }
+            NUMBER_UINT if !negative => {
+                let v = integer(0)?;
+                u64::try_from(v)
+                    .map(Number::UInt64)
+                    .unwrap_or(Number::UInt128(v))
+            }
+            NUMBER_DECIMAL => {
+                let scale = read_exact(types, 1)?[0];
+                let value = if digits.is_empty() {
+                    0
+                } else {
+                    signed(integer(scale as i32)?)?
+                };
+                Number::Decimal(Decimal::new(value, scale).ok_or(Error::InvalidJsonbNumber)?)
+            }
+            NUMBER_RAW => {
+                let text = comparable_decode_bytes(types)?;
+                let text = String::from_utf8(text).map_err(|_| Error::InvalidJsonbNumber)?;
+                Number::Raw(RawNumber::new(text).ok_or(Error::InvalidJsonbNumber)?)
+            }
+            _ => return Err(Error::InvalidJsonbNumber),
+        };
+        Ok(num)
+    }
+}
+
+impl RawNumber {
+    /// Parse the text of a JSON number and keep it with the parsed value.
+    /// Returns `None` if the text is not a valid JSON number,
+    /// including the text with leading or trailing whitespace.
+    pub fn new(text: impl Into<String>) -> Option<RawNumber> {
+        let text = text.into();
+        // a JSON number starts with a minus sign or a digit and ends with a digit
+        if !text.starts_with(|c: char| c == '-' || c.is_ascii_digit())
+            || !text.ends_with(|c: char| c.is_ascii_digit())
+        {
+            return None;
+        }
+        let opts = ParseOptions::new().with_decimal(true);
+        match parse_value_with_options(text.as_bytes(), &opts) {
+            Ok(Value::Number(value)) => Some(RawNumber::from_parts(text, value)),
+            _ => None,
+        }
+    }
+
+    // The text must be the valid JSON number of the value.
+    pub(crate) fn from_parts(text: String, value: Number) -> RawNumber {
+        RawNumber {
+            text,
+            value: Box::new(value),
+        }
+    }
+
+    /// The original text of the number.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// The parsed value of the number, `Decimal` is used for the fractions that fit.
+    pub fn value(&self) -> &Number {
+        &self.value
+    }
+}
+
+impl Decimal {
+    /// The max number of digits after the decimal point.
+    pub const MAX_SCALE: u8 = 38;
+
+    pub fn new(value: i128, scale: u8) -> Option<Decimal> {
+        if scale > Self::MAX_SCALE {
+            return None;
+        }
+        Some(Decimal { value, scale })
+    }
+
+    /// Parse a JSON number text to decimal, the digits after the decimal point are kept.
+    /// Returns `None` if the number is invalid or can't be represented exactly.
+    pub fn parse(s: &str) -> Option<Decimal> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let (mantissa, exp) = match s.find(['e', 'E']) {
+            Some(pos) => (&s[..pos], s[pos + 1..].parse::<i32>().ok()?),
+            None => (s, 0),
+        };
+        let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if int_part.is_empty() && frac_part.is_empty() {
+            return None;
+        }
+        let mut value: i128 = 0;
+        for c in int_part.bytes().chain(frac_part.bytes()) {
+            if !c.is_ascii_digit() {
+                return None;
+            }
+            value = value.checked_mul(10)?.checked_add((c - b'0') as i128)?;
+        }
+        let mut scale = frac_part.len() as i64 - exp as i64;
+        if scale < 0 {
+            value = value.checked_mul(10_i128.checked_pow(u32::try_from(-scale).ok()?)?)?;
+            scale = 0;
+        }
+        if value == 0 {
+            scale = scale.min(Self::MAX_SCALE as i64);
+        }
+        while scale > Self::MAX_SCALE as i64 && value % 10 == 0 {
+            value /= 10;
+            scale -= 1;
+        }
+        if negative {
+            value = -value;
+        }
+        Decimal::new(value, u8::try_from(scale).ok()?)
+    }
+
+    /// Convert the float to decimal, integers are converted exactly and fractions are
+    /// converted with the shortest representation that round trips, such as `0.1`.
+    /// Returns `None` if the float is not finite or out of the decimal range.
+    pub fn from_f64(v: f64) -> Option<Decimal> {
+        if !v.is_finite() {
+            return None;
+        }
+        if v.fract() == 0.0 && v.abs() < i128::MAX as f64 {
+            return Decimal::new(v as i128, 0);
+        }
+        Decimal::parse(&format!("{:e}", v))
+    }
+
+    /// Returns the integer value if the decimal has no fractional part.
+    pub fn as_i128(&self) -> Option<i128> {
+        let pow = 10_i128.pow(self.scale as u32);
+        if self.value % pow == 0 {
+            Some(self.value / pow)
+        } else {
+            None
+        }
+    }
+
+    /// Convert to the nearest float.
+    pub fn to_f64(&self) -> f64 {
+        fast_float2::parse(self.to_string()).unwrap_or(f64::NAN)
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(v: i64) -> Self {
+        Decimal {
+            value: v as i128,
+            scale: 0,
+        }
+    }
+}
+
+impl From<u64> for Decimal {
+    fn from(v: u64) -> Self {
+        Decimal {
+            value: v as i128,
+            scale: 0,
+        }
+    }
+}
+
+impl PartialEq for Decimal {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.scale == other.scale {
+            return self.value.cmp(&other.value);
+        }
+        // rescale to the same scale, if the value overflows,
+        // its absolute value is larger than any other values.
+        let (l, r, reverse) = if self.scale < other.scale {
+            (self, other, false)
+        } else {
+            (other, self, true)
+        };
+        let pow = 10_i128.pow((r.scale - l.scale) as u32);
+        let ord = match l.value.checked_mul(pow) {
+            Some(l_value) => l_value.cmp(&r.value),
+            None => l.value.cmp(&0),
+        };
+        if reverse {
+            ord.reverse()
+        } else {
+            ord
+        }
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let digits = self.value.unsigned_abs().to_string();
+        if self.value < 0 {
+            write!(f, "-")?;
+        }
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}", digits);
+        }
+        if digits.len() > scale {
+            let (int_part, frac_part) = digits.split_at(digits.len() - scale);
+            write!(f, "{}.{}", int_part, frac_part)
+        } else {
+            write!(f, "0.{:0>width$}", digits, width = scale)
         }
     }
 }
@@ -223,6 +841,7 @@ impl Ord for Number {
     #[inline]
     fn cmp(&self, other: &Self) -> Ordering {
         match (self, other) {
+            (Number::Raw(_), _) | (_, Number::Raw(_)) => self.numeric().cmp(other.numeric()),
             (Number::Int64(l), Number::Int64(r)) => l.cmp(r),
             (Number::UInt64(l), Number::UInt64(r)) => l.cmp(r),
             (Number::Int64(l), Number::UInt64(r)) => {
@@ -239,11 +858,108 @@ impl Ord for Number {
                     l.cmp(&(*r as u64))
                 }
             }
+            (Number::Float64(l), Number::Float64(r)) => OrderedFloat(*l).cmp(&OrderedFloat(*r)),
+            (Number::Decimal(l), Number::Decimal(r)) => l.cmp(r),
+            (Number::Decimal(l), _) if other.as_int().is_some() => match other.as_decimal() {
+                Some(r) => l.cmp(&r),
+                // integers out of the decimal range
+                None => Ordering::Less,
+            },
+            (_, Number::Decimal(r)) if self.as_int().is_some() => match self.as_decimal() {
+                Some(l) => l.cmp(r),
+                None => Ordering::Greater,
+            },
+            (_, _) if self.as_int().is_some() && other.as_int().is_some() => {
+                let (l_neg, l) = self.as_int().unwrap();
+                let (r_neg, r) = other.as_int().unwrap();
+                match (l_neg, r_neg) {
+                    (false, false) => l.cmp(&r),
+                    (true, true) => r.cmp(&l),
+                    (true, false) => Ordering::Less,
+                    (false, true) => Ordering::Greater,
+                }
+            }
             (_, _) => {
                 let l = OrderedFloat(self.as_f64().unwrap());
                 let r = OrderedFloat(other.as_f64().unwrap());
-                l.cmp(&r)
+                match l.cmp(&r) {
+                    // the floats may be rounded, compare the exact values
+                    Ordering::Equal => match (self.as_decimal(), other.as_decimal()) {
+                        (Some(l), Some(r)) => l.cmp(&r),
+                        // the float out of the decimal range has a larger absolute value
+                        (_, _) => match (self, other) {
+                            (_, Number::Float64(r)) if *r > 0.0 => Ordering::Less,
+                            (_, Number::Float64(_)) => Ordering::Greater,
+                            (Number::Float64(l), _) if *l > 0.0 => Ordering::Greater,
+                            (Number::Float64(_), _) => Ordering::Less,
+                            (_, _) => Ordering::Equal,
+                        },
+                    },
+                    ord => ord,
+                }
+            }
+        }
+    }
+}
+
+// The decimal digits of a number written on the stack, the buffer holds the 39 digits
+// of the 128-bit integers and the shortest representation of the floats.
+struct DecimalDigits {
+    negative: bool,
+    exp: i32,
+    buf: [u8; 40],
+    len: usize,
+}
+
+impl DecimalDigits {
+    fn new() -> Self {
+        DecimalDigits {
+            negative: false,
+            exp: 0,
+            buf: [0; 40],
+            len: 0,
+        }
+    }
+
+    fn extend(&mut self, digits: impl Iterator<Item = u8>) {
+        for d in digits {
+            self.buf[self.len] = d;
+            self.len += 1;
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    fn as_str(&self) -> &str {
+        std::str::from_utf8(self.as_bytes()).unwrap_or_default()
+    }
+}
+
+impl std::fmt::Write for DecimalDigits {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        let buf = self
+            .buf
+            .get_mut(self.len..self.len + s.len())
+            .ok_or(std::fmt::Error)?;
+        buf.copy_from_slice(s.as_bytes());
+        self.len += s.len();
+        Ok(())
+    }
+}
+
+// Equal numbers have the same decimal digits whatever the representation,
+// so the hash is consistent with the `Ord`.
+impl Hash for Number {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.decimal_digits() {
+            Some(digits) => {
+                digits.negative.hash(state);
+                digits.exp.hash(state);
+                digits.as_bytes().hash(state);
             }
+            None => self.non_finite_key().hash(state),
         }
     }
 }
@@ -266,6 +982,18 @@ impl Display for Number {
                 let s = buffer.format(*v);
                 write!(f, "{}", s)
             }
+            Number::Decimal(v) => write!(f, "{}", v),
+            Number::Int128(v) => {
+                let mut buffer = itoa::Buffer::new();
+                let s = buffer.format(*v);
+                write!(f, "{}", s)
+            }
+            Number::UInt128(v) => {
+                let mut buffer = itoa::Buffer::new();
+                let s = buffer.format(*v);
+                write!(f, "{}", s)
+            }
+            Number::Raw(v) => write!(f, "{}", v.as_str()),
         }
     }
 }
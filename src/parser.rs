@@ -20,19 +20,166 @@ use crate::lazy_value::LazyValue;
 use super::constants::*;
 use super::error::Error;
 use super::error::ParseErrorCode;
+use super::number::Decimal;
 use super::number::Number;
-use super::util::parse_string;
+use super::number::RawNumber;
+use super::util::parse_quoted_string;
 use super::value::Object;
 use super::value::Value;
 
+/// The policy for handling duplicate keys in a `JSON` object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeyPolicy {
+    /// Reject the document with `ParseErrorCode::ObjectDuplicateKey`.
+    Error,
+    /// Keep the value of the first occurrence of the key.
+    FirstWins,
+    /// Keep the value of the last occurrence of the key.
+    #[default]
+    LastWins,
+}
+
+/// Options to control how `JSON` text is parsed.
+/// By default, only standard `JSON` text is accepted,
+/// the extensions can be turned on individually.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub duplicate_key: DuplicateKeyPolicy,
+    /// Allow `// line` and `/* block */` comments.
+    pub allow_comments: bool,
+    /// Allow a trailing comma after the last element of arrays and objects.
+    pub allow_trailing_commas: bool,
+    /// Allow strings and keys enclosed in single quotes.
+    pub allow_single_quotes: bool,
+    /// Allow object keys that are identifiers without quotes.
+    pub allow_unquoted_keys: bool,
+    /// Allow `NaN`, `Infinity` and `-Infinity` number literals.
+    pub allow_nan_inf: bool,
+    /// Parse numbers with fraction or exponent, and integers out of the 128-bit range
+    /// as exact `Number::Decimal`, fall back to `Number::Float64` if out of the decimal range.
+    pub parse_decimal: bool,
+    /// Keep the original text of numbers as `Number::Raw`,
+    /// so that the numbers are converted back to the same text.
+    pub preserve_number_text: bool,
+    /// The maximum nesting depth of arrays and objects, deeper text is rejected
+    /// with `ParseErrorCode::RecursionLimitExceeded`. Defaults to 128.
+    pub max_depth: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            duplicate_key: DuplicateKeyPolicy::default(),
+            allow_comments: false,
+            allow_trailing_commas: false,
+            allow_single_quotes: false,
+            allow_unquoted_keys: false,
+            allow_nan_inf: false,
+            parse_decimal: false,
+            preserve_number_text: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options with all the `JSON5` like extensions turned on.
+    pub fn lenient() -> Self {
+        Self {
+            duplicate_key: DuplicateKeyPolicy::default(),
+            allow_comments: true,
+            allow_trailing_commas: true,
+            allow_single_quotes: true,
+            allow_unquoted_keys: true,
+            allow_nan_inf: true,
+            parse_decimal: false,
+            preserve_number_text: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    pub fn with_duplicate_key(mut self, policy: DuplicateKeyPolicy) -> Self {
+        self.duplicate_key = policy;
+        self
+    }
+
+    pub fn with_comments(mut self, allow: bool) -> Self {
+        self.allow_comments = allow;
+        self
+    }
+
+    pub fn with_trailing_commas(mut self, allow: bool) -> Self {
+        self.allow_trailing_commas = allow;
+        self
+    }
+
+    pub fn with_single_quotes(mut self, allow: bool) -> Self {
+        self.allow_single_quotes = allow;
+        self
+    }
+
+    pub fn with_unquoted_keys(mut self, allow: bool) -> Self {
+        self.allow_unquoted_keys = allow;
+        self
+    }
+
+    pub fn with_nan_inf(mut self, allow: bool) -> Self {
+        self.allow_nan_inf = allow;
+        self
+    }
+
+    pub fn with_decimal(mut self, parse_decimal: bool) -> Self {
+        self.parse_decimal = parse_decimal;
+        self
+    }
+
+    pub fn with_number_text(mut self, preserve: bool) -> Self {
+        self.preserve_number_text = preserve;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+}
+
 // Parse JSON text to JSONB Value.
 // Inspired by `https://github.com/jorgecarleitao/json-deserializer`
 // Thanks Jorge Leitao.
 pub fn parse_value(buf: &[u8]) -> Result<Value<'_>, Error> {
-    let mut parser = Parser::new(buf);
+    parse_value_with_options(buf, &ParseOptions::default())
+}
+
+/// Parse JSON text to JSONB Value with the specified options.
+pub fn parse_value_with_options<'a>(
+    buf: &'a [u8],
+    opts: &ParseOptions,
+) -> Result<Value<'a>, Error> {
+    let mut parser = Parser::new(buf, *opts);
     parser.parse()
 }
 
+// Parse the first JSON value at the beginning of the buffer, the remaining
+// text is left untouched. Returns the value and the number of consumed bytes,
+// or `None` if the buffer only contains whitespaces and comments.
+pub(crate) fn parse_first_value<'a>(
+    buf: &'a [u8],
+    opts: &ParseOptions,
+) -> Result<Option<(Value<'a>, usize)>, Error> {
+    let mut parser = Parser::new(buf, *opts);
+    parser.skip_unused()?;
+    if parser.idx >= buf.len() {
+        return Ok(None);
+    }
+    let val = parser.parse_json_value()?;
+    Ok(Some((val, parser.idx)))
+}
+
 pub fn parse_lazy_value(buf: &[u8]) -> Result<LazyValue<'_>, Error> {
     if !is_jsonb(buf) {
         parse_value(buf).map(LazyValue::Value)
@@ -44,16 +191,23 @@ pub fn parse_lazy_value(buf: &[u8]) -> Result<LazyValue<'_>, Error> {
 struct Parser<'a> {
     buf: &'a [u8],
     idx: usize,
+    depth: usize,
+    opts: ParseOptions,
 }
 
 impl<'a> Parser<'a> {
-    fn new(buf: &'a [u8]) -> Parser<'a> {
-        Self { buf, idx: 0 }
+    fn new(buf: &'a [u8], opts: ParseOptions) -> Parser<'a> {
+        Self {
+            buf,
+            idx: 0,
+            depth: 0,
+            opts,
+        }
     }
 
     fn parse(&mut self) -> Result<Value<'a>, Error> {
         let val = self.parse_json_value()?;
-        self.skip_unused();
+        self.skip_unused()?;
         if self.idx < self.buf.len() {
             self.step();
             return Err(self.error(ParseErrorCode::UnexpectedTrailingCharacters));
@@ -62,16 +216,19 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_json_value(&mut self) -> Result<Value<'a>, Error> {
-        self.skip_unused();
-        let c = self.next()?;
+        self.skip_unused()?;
+        let c = *self.next()?;
         match c {
             b'n' => self.parse_json_null(),
             b't' => self.parse_json_true(),
             b'f' => self.parse_json_false(),
             b'0'..=b'9' | b'-' => self.parse_json_number(),
-            b'"' => self.parse_json_string(),
-            b'[' => self.parse_json_array(),
-            b'{' => self.parse_json_object(),
+            b'"' => self.parse_json_string(b'"'),
+            b'\'' if self.opts.allow_single_quotes => self.parse_json_string(b'\''),
+            b'[' => self.parse_nested(Self::parse_json_array),
+            b'{' => self.parse_nested(Self::parse_json_object),
+            b'N' if self.opts.allow_nan_inf => self.parse_json_nan(),
+            b'I' if self.opts.allow_nan_inf => self.parse_json_infinity(false),
             _ => {
                 self.step();
                 Err(self.error(ParseErrorCode::ExpectedSomeValue))
@@ -79,6 +236,21 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // Parse an array or an object one level deeper, fails if the depth limit is exceeded.
+    fn parse_nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Value<'a>, Error>,
+    ) -> Result<Value<'a>, Error> {
+        if self.depth >= self.opts.max_depth {
+            self.step();
+            return Err(self.error(ParseErrorCode::RecursionLimitExceeded));
+        }
+        self.depth += 1;
+        let val = parse(self);
+        self.depth -= 1;
+        val
+    }
+
     fn next(&mut self) -> Result<&u8, Error> {
         match self.buf.get(self.idx) {
             Some(c) => Ok(c),
@@ -162,7 +334,7 @@ impl<'a> Parser<'a> {
     }
 
     #[inline]
-    fn skip_unused(&mut self) {
+    fn skip_unused(&mut self) -> Result<(), Error> {
         while self.idx < self.buf.len() {
             let c = self.buf.get(self.idx).unwrap();
             if c.is_ascii_whitespace() {
@@ -186,8 +358,47 @@ impl<'a> Parser<'a> {
                     continue;
                 }
             }
+            if *c == b'/' && self.opts.allow_comments && self.skip_comment()? {
+                continue;
+            }
             break;
         }
+        Ok(())
+    }
+
+    // Skip a `// line` or `/* block */` comment, returns false if not a comment.
+    // An unterminated block comment and a `/` at the end of the input fail with `InvalidEOF`,
+    // as they may continue in the following text of a stream.
+    fn skip_comment(&mut self) -> Result<bool, Error> {
+        match self.buf.get(self.idx + 1) {
+            None => {
+                self.step();
+                Err(self.error(ParseErrorCode::InvalidEOF))
+            }
+            Some(b'/') => {
+                self.step_by(2);
+                while self.idx < self.buf.len() && self.buf[self.idx] != b'\n' {
+                    self.step();
+                }
+                Ok(true)
+            }
+            Some(b'*') => {
+                self.step_by(2);
+                while self.idx < self.buf.len() {
+                    if self.buf[self.idx] == b'*' && self.check_next_at(self.idx + 1, b'/') {
+                        self.step_by(2);
+                        return Ok(true);
+                    }
+                    self.step();
+                }
+                Err(self.error(ParseErrorCode::InvalidEOF))
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn check_next_at(&self, idx: usize, c: u8) -> bool {
+        matches!(self.buf.get(idx), Some(v) if *v == c)
     }
 
     fn parse_json_null(&mut self) -> Result<Value<'a>, Error> {
@@ -214,6 +425,26 @@ impl<'a> Parser<'a> {
         Ok(Value::Bool(false))
     }
 
+    fn parse_json_nan(&mut self) -> Result<Value<'a>, Error> {
+        let data = [b'N', b'a', b'N'];
+        for v in data.into_iter() {
+            self.must_is(v)?;
+        }
+        Ok(Value::Number(Number::Float64(f64::NAN)))
+    }
+
+    fn parse_json_infinity(&mut self, negative: bool) -> Result<Value<'a>, Error> {
+        let data = [b'I', b'n', b'f', b'i', b'n', b'i', b't', b'y'];
+        for v in data.into_iter() {
+            self.must_is(v)?;
+        }
+        if negative {
+            Ok(Value::Number(Number::Float64(f64::NEG_INFINITY)))
+        } else {
+            Ok(Value::Number(Number::Float64(f64::INFINITY)))
+        }
+    }
+
     fn parse_json_number(&mut self) -> Result<Value<'a>, Error> {
         let start_idx = self.idx;
 
@@ -224,6 +455,9 @@ impl<'a> Parser<'a> {
         if self.check_next(b'-') {
             negative = true;
             self.step();
+            if self.opts.allow_nan_inf && self.check_next(b'I') {
+                return self.parse_json_infinity(true);
+            }
         }
         if self.check_next(b'0') {
             self.step();
@@ -260,25 +494,21 @@ impl<'a> Parser<'a> {
             }
         }
         let s = unsafe { std::str::from_utf8_unchecked(&self.buf[start_idx..self.idx]) };
-
-        if !has_fraction && !has_exponent {
-            if !negative {
-                if let Ok(v) = s.parse::<u64>() {
-                    return Ok(Value::Number(Number::UInt64(v)));
-                }
-            } else if let Ok(v) = s.parse::<i64>() {
-                return Ok(Value::Number(Number::Int64(v)));
-            }
-        }
-
-        match fast_float2::parse(s) {
-            Ok(v) => Ok(Value::Number(Number::Float64(v))),
-            Err(_) => Err(self.error(ParseErrorCode::InvalidNumberValue)),
+        // the raw number text is kept with the exact value
+        let parse_decimal = self.opts.parse_decimal || self.opts.preserve_number_text;
+        let Some(num) = parse_number(s, negative, !has_fraction && !has_exponent, parse_decimal)
+        else {
+            return Err(self.error(ParseErrorCode::InvalidNumberValue));
+        };
+        if self.opts.preserve_number_text {
+            let raw = RawNumber::from_parts(s.to_string(), num);
+            return Ok(Value::Number(Number::Raw(raw)));
         }
+        Ok(Value::Number(num))
     }
 
-    fn parse_json_string(&mut self) -> Result<Value<'a>, Error> {
-        self.must_is(b'"')?;
+    fn parse_json_string(&mut self, quote: u8) -> Result<Value<'a>, Error> {
+        self.must_is(quote)?;
 
         let start_idx = self.idx;
         let mut escapes = 0;
@@ -302,7 +532,7 @@ impl<'a> Parser<'a> {
                     }
                     continue;
                 }
-                b'"' => {
+                c if *c == quote => {
                     self.step();
                     break;
                 }
@@ -315,7 +545,7 @@ impl<'a> Parser<'a> {
         let val = if escapes > 0 {
             let len = self.idx - 1 - start_idx - escapes;
             let mut idx = start_idx + 1;
-            let s = parse_string(data, len, &mut idx)?;
+            let s = parse_quoted_string(data, len, &mut idx, quote)?;
             Cow::Owned(s)
         } else {
             std::str::from_utf8(data)
@@ -331,7 +561,7 @@ impl<'a> Parser<'a> {
         let mut first = true;
         let mut values = Vec::new();
         loop {
-            self.skip_unused();
+            self.skip_unused()?;
             let c = self.next()?;
             if *c == b']' {
                 self.step();
@@ -342,6 +572,13 @@ impl<'a> Parser<'a> {
                     return Err(self.error(ParseErrorCode::ExpectedArrayCommaOrEnd));
                 }
                 self.step();
+                if self.opts.allow_trailing_commas {
+                    self.skip_unused()?;
+                    if self.check_next(b']') {
+                        self.step();
+                        break;
+                    }
+                }
             }
             first = false;
             let value = self.parse_json_value()?;
@@ -356,7 +593,7 @@ impl<'a> Parser<'a> {
         let mut first = true;
         let mut obj = Object::new();
         loop {
-            self.skip_unused();
+            self.skip_unused()?;
             let c = self.next()?;
             if *c == b'}' {
                 self.step();
@@ -367,13 +604,25 @@ impl<'a> Parser<'a> {
                     return Err(self.error(ParseErrorCode::ExpectedObjectCommaOrEnd));
                 }
                 self.step();
+                if self.opts.allow_trailing_commas {
+                    self.skip_unused()?;
+                    if self.check_next(b'}') {
+                        self.step();
+                        break;
+                    }
+                }
             }
             first = false;
-            let key = self.parse_json_value()?;
+            let key = self.parse_json_key()?;
             if !key.is_string() {
                 return Err(self.error(ParseErrorCode::KeyMustBeAString));
             }
-            self.skip_unused();
+            let k = key.as_str().unwrap().to_string();
+            let duplicated = obj.contains_key(&k);
+            if duplicated && self.opts.duplicate_key == DuplicateKeyPolicy::Error {
+                return Err(self.error(ParseErrorCode::ObjectDuplicateKey(k)));
+            }
+            self.skip_unused()?;
             let c = self.next()?;
             if *c != b':' {
                 return Err(self.error(ParseErrorCode::ExpectedColon));
@@ -381,9 +630,60 @@ impl<'a> Parser<'a> {
             self.step();
             let value = self.parse_json_value()?;
 
-            let k = key.as_str().unwrap();
-            obj.insert(k.to_string(), value);
+            // keep the first value if the key is duplicated
+            if duplicated && self.opts.duplicate_key == DuplicateKeyPolicy::FirstWins {
+                continue;
+            }
+            obj.insert(k, value);
         }
         Ok(Value::Object(obj))
     }
+
+    fn parse_json_key(&mut self) -> Result<Value<'a>, Error> {
+        self.skip_unused()?;
+        let c = *self.next()?;
+        if self.opts.allow_unquoted_keys && (c.is_ascii_alphabetic() || matches!(c, b'_' | b'$')) {
+            return self.parse_json_ident();
+        }
+        self.parse_json_value()
+    }
+
+    // Parse the unquoted key, which is an identifier like `JavaScript`,
+    // consisting of letters, digits, `_` and `$`.
+    fn parse_json_ident(&mut self) -> Result<Value<'a>, Error> {
+        let start_idx = self.idx;
+        while self.idx < self.buf.len() {
+            let c = self.buf[self.idx];
+            if !c.is_ascii_alphanumeric() && !matches!(c, b'_' | b'$') {
+                break;
+            }
+            self.step();
+        }
+        let s = unsafe { std::str::from_utf8_unchecked(&self.buf[start_idx..self.idx]) };
+        Ok(Value::String(Cow::Borrowed(s)))
+    }
+}
+
+// Parse the text of a valid JSON number, integers are parsed to the integer types if they fit,
+// otherwise to decimals if `parse_decimal` is enabled, or floats.
+fn parse_number(s: &str, negative: bool, is_integer: bool, parse_decimal: bool) -> Option<Number> {
+    if is_integer {
+        if !negative {
+            if let Ok(v) = s.parse::<u64>() {
+                return Some(Number::UInt64(v));
+            } else if let Ok(v) = s.parse::<u128>() {
+                return Some(Number::UInt128(v));
+            }
+        } else if let Ok(v) = s.parse::<i64>() {
+            return Some(Number::Int64(v));
+        } else if let Ok(v) = s.parse::<i128>() {
+            return Some(Number::Int128(v));
+        }
+    }
+    if parse_decimal {
+        if let Some(v) = Decimal::parse(s) {
+            return Some(Number::Decimal(v));
+        }
+    }
+    fast_float2::parse(s).ok().map(Number::Float64)
 }
@@ -0,0 +1,443 @@
+// Copyright 2024 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::io::Read;
+
+use crate::constants::READ_CHUNK_SIZE;
+use crate::error::Error;
+use crate::error::ParseErrorCode;
+use crate::functions::build_array;
+use crate::functions::build_object;
+use crate::parser::parse_first_value;
+use crate::parser::DuplicateKeyPolicy;
+use crate::parser::ParseOptions;
+use crate::value::Value;
+
+/// Parse JSON text from a reader to `JSONB` bytes,
+/// the input is read and encoded in chunks without buffering the whole text.
+/// The encoded value is built in memory, see [`PushParser`].
+pub fn parse_value_from_reader<R: Read>(reader: R) -> Result<Vec<u8>, Error> {
+    parse_value_from_reader_with_options(reader, &ParseOptions::default())
+}
+
+/// Parse JSON text from a reader to `JSONB` bytes with the specified options.
+pub fn parse_value_from_reader_with_options<R: Read>(
+    mut reader: R,
+    opts: &ParseOptions,
+) -> Result<Vec<u8>, Error> {
+    let mut parser = PushParser::with_options(opts);
+    let mut chunk = vec![0; READ_CHUNK_SIZE];
+    loop {
+        match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => {
+                parser.feed(&chunk[..n])?;
+            }
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    parser.finish()
+}
+
+/// The status of the `PushParser` after feeding a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseStatus {
+    /// More input is required to complete the value.
+    Incomplete,
+    /// The value is complete, call `finish` to get the `JSONB` bytes.
+    Complete,
+}
+
+/// An incremental JSON parser that accepts the text in partial chunks.
+///
+/// Completed values are encoded to `JSONB` immediately and the consumed text is dropped,
+/// the unfinished token and the encoded values of the open containers are kept in memory.
+///
+/// The memory is not bounded: the elements of a container are kept until it is closed,
+/// so a document with a large top-level array or object needs about the size of its
+/// encoded `JSONB`, the same as the result. To bound the memory of large inputs,
+/// split them into records and parse them with [`parse_values_reader`](crate::parse_values_reader).
+///
+/// ```rust
+/// use jsonb::{ParseStatus, PushParser};
+///
+/// let mut parser = PushParser::new();
+/// assert_eq!(parser.feed(br#"{"a": [1, tr"#).unwrap(), ParseStatus::Incomplete);
+/// assert_eq!(parser.feed(br#"ue]}"#).unwrap(), ParseStatus::Complete);
+/// let jsonb = parser.finish().unwrap();
+/// assert_eq!(jsonb::to_string(&jsonb), r#"{"a":[1,true]}"#);
+/// ```
+pub struct PushParser {
+    opts: ParseOptions,
+    // the unconsumed input text
+    pending: Vec<u8>,
+    // the offset of `pending` in the whole input text
+    offset: usize,
+    // the scanned length and escape state of the unfinished token,
+    // avoid scanning long strings again when more input arrives.
+    scan_len: usize,
+    escaped: bool,
+    stack: Vec<Frame>,
+    state: State,
+    result: Option<Vec<u8>>,
+}
+
+// The open container with its encoded inner values
+enum Frame {
+    Array(Vec<Vec<u8>>),
+    Object(BTreeMap<String, Vec<u8>>, Option<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Value,
+    ValueOrEnd,
+    Key,
+    KeyOrEnd,
+    Colon,
+    CommaOrEnd,
+    Done,
+}
+
+impl Default for PushParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PushParser {
+    pub fn new() -> Self {
+        Self::with_options(&ParseOptions::default())
+    }
+
+    pub fn with_options(opts: &ParseOptions) -> Self {
+        Self {
+            opts: *opts,
+            pending: Vec::new(),
+            offset: 0,
+            scan_len: 0,
+            escaped: false,
+            stack: Vec::new(),
+            state: State::Value,
+            result: None,
+        }
+    }
+
+    /// Feed the next chunk of the JSON text.
+    /// The parser should not be used any more after an error is returned.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<ParseStatus, Error> {
+        self.pending.extend_from_slice(chunk);
+        self.process(false)?;
+        if self.state == State::Done {
+            Ok(ParseStatus::Complete)
+        } else {
+            Ok(ParseStatus::Incomplete)
+        }
+    }
+
+    /// Finish the input and returns the `JSONB` bytes of the value.
+    pub fn finish(mut self) -> Result<Vec<u8>, Error> {
+        self.process(true)?;
+        match self.result {
+            Some(result) if self.state == State::Done => Ok(result),
+            _ => Err(Error::Syntax(
+                ParseErrorCode::InvalidEOF,
+                self.offset + self.pending.len(),
+            )),
+        }
+    }
+
+    fn process(&mut self, eof: bool) -> Result<(), Error> {
+        let mut idx = 0;
+        let res = self.process_pending(&mut idx, eof);
+        // drop the consumed text
+        self.pending.drain(..idx);
+        self.offset += idx;
+        res
+    }
+
+    fn process_pending(&mut self, idx: &mut usize, eof: bool) -> Result<(), Error> {
+        while self.skip_unused(idx, eof)? {
+            let Some(c) = self.pending.get(*idx).copied() else {
+                break;
+            };
+            match self.state {
+                State::Done => {
+                    return Err(self.error(ParseErrorCode::UnexpectedTrailingCharacters, *idx + 1));
+                }
+                State::Value | State::ValueOrEnd => match c {
+                    b']' if self.state == State::ValueOrEnd => {
+                        *idx += 1;
+                        self.close_container()?;
+                    }
+                    b'[' => {
+                        self.check_depth(*idx)?;
+                        *idx += 1;
+                        self.stack.push(Frame::Array(Vec::new()));
+                        self.state = State::ValueOrEnd;
+                    }
+                    b'{' => {
+                        self.check_depth(*idx)?;
+                        *idx += 1;
+                        self.stack.push(Frame::Object(BTreeMap::new(), None));
+                        self.state = State::KeyOrEnd;
+                    }
+                    _ => {
+                        let Some(end) = self.scan_token(*idx, eof)? else {
+                            break;
+                        };
+                        let value = self.parse_token(*idx, end)?;
+                        *idx = end;
+                        self.push_value(value.to_vec())?;
+                    }
+                },
+                State::Key | State::KeyOrEnd => match c {
+                    b'}' if self.state == State::KeyOrEnd => {
+                        *idx += 1;
+                        self.close_container()?;
+                    }
+                    _ => {
+                        let Some(end) = self.scan_token(*idx, eof)? else {
+                            break;
+                        };
+                        let key = self.parse_key(*idx, end)?;
+                        *idx = end;
+                        self.push_key(key, end)?;
+                    }
+                },
+                State::Colon => {
+                    if c != b':' {
+                        return Err(self.error(ParseErrorCode::ExpectedColon, *idx));
+                    }
+                    *idx += 1;
+                    self.state = State::Value;
+                }
+                State::CommaOrEnd => match (self.stack.last(), c) {
+                    (Some(Frame::Array(_)), b',') => {
+                        *idx += 1;
+                        self.state = if self.opts.allow_trailing_commas {
+                            State::ValueOrEnd
+                        } else {
+                            State::Value
+                        };
+                    }
+                    (Some(Frame::Object(_, _)), b',') => {
+                        *idx += 1;
+                        self.state = if self.opts.allow_trailing_commas {
+                            State::KeyOrEnd
+                        } else {
+                            State::Key
+                        };
+                    }
+                    (Some(Frame::Array(_)), b']') | (Some(Frame::Object(_, _)), b'}') => {
+                        *idx += 1;
+                        self.close_container()?;
+                    }
+                    (Some(Frame::Array(_)), _) => {
+                        return Err(self.error(ParseErrorCode::ExpectedArrayCommaOrEnd, *idx));
+                    }
+                    _ => {
+                        return Err(self.error(ParseErrorCode::ExpectedObjectCommaOrEnd, *idx));
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // Skip whitespaces and comments, returns false if more input is required.
+    fn skip_unused(&self, idx: &mut usize, eof: bool) -> Result<bool, Error> {
+        while let Some(c) = self.pending.get(*idx) {
+            if c.is_ascii_whitespace() {
+                *idx += 1;
+                continue;
+            }
+            let rest = &self.pending[*idx..];
+            // Allow parse escaped white space
+            if *c == b'\\' {
+                if rest.len() < 4 && !eof && b"\\x0C".starts_with(rest) {
+                    return Ok(false);
+                }
+                if rest.len() >= 2 && matches!(rest[1], b'n' | b'r' | b't') {
+                    *idx += 2;
+                    continue;
+                }
+                if rest.starts_with(b"\\x0C") {
+                    *idx += 4;
+                    continue;
+                }
+            }
+            if *c == b'/' && self.opts.allow_comments {
+                let (end, terminator) = match rest.get(1) {
+                    Some(b'/') => (b"\n".as_slice(), 1),
+                    Some(b'*') => (b"*/".as_slice(), 2),
+                    Some(_) => break,
+                    None if eof => break,
+                    None => return Ok(false),
+                };
+                match rest[2..].windows(terminator).position(|w| w == end) {
+                    Some(pos) => *idx += 2 + pos + terminator,
+                    // a line comment may end at the end of the input
+                    None if eof && terminator == 1 => *idx = self.pending.len(),
+                    None if eof => {
+                        return Err(self.error(ParseErrorCode::InvalidEOF, self.pending.len()));
+                    }
+                    None => return Ok(false),
+                }
+                continue;
+            }
+            break;
+        }
+        Ok(true)
+    }
+
+    // Find the end of the scalar token starting at `idx`,
+    // returns `None` if the token may continue in the next chunk.
+    fn scan_token(&mut self, idx: usize, eof: bool) -> Result<Option<usize>, Error> {
+        let quote = self.pending[idx];
+        if quote == b'"' || (quote == b'\'' && self.opts.allow_single_quotes) {
+            let start = idx + self.scan_len.max(1);
+            for (i, c) in self.pending[start..].iter().enumerate() {
+                if self.escaped {
+                    self.escaped = false;
+                } else if *c == b'\\' {
+                    self.escaped = true;
+                } else if *c == quote {
+                    self.scan_len = 0;
+                    return Ok(Some(start + i + 1));
+                }
+            }
+            if eof {
+                return Err(self.error(ParseErrorCode::InvalidEOF, self.pending.len()));
+            }
+            self.scan_len = self.pending.len() - idx;
+            return Ok(None);
+        }
+        // numbers, literals and unquoted keys end with a delimiter
+        let start = idx + self.scan_len;
+        let pos = self.pending[start..].iter().position(|c| {
+            c.is_ascii_whitespace() || matches!(c, b',' | b':' | b'[' | b']' | b'{' | b'}' | b'/')
+        });
+        match pos {
+            Some(pos) => {
+                self.scan_len = 0;
+                Ok(Some(start + pos))
+            }
+            None if eof => {
+                self.scan_len = 0;
+                Ok(Some(self.pending.len()))
+            }
+            None => {
+                self.scan_len = self.pending.len() - idx;
+                Ok(None)
+            }
+        }
+    }
+
+    fn parse_token(&self, start: usize, end: usize) -> Result<Value<'_>, Error> {
+        let token = &self.pending[start..end];
+        match parse_first_value(token, &self.opts) {
+            Ok(Some((value, len))) if len == token.len() => Ok(value),
+            Ok(Some((_, len))) => {
+                let code = ParseErrorCode::UnexpectedTrailingCharacters;
+                Err(self.error(code, start + len + 1))
+            }
+            Ok(None) => Err(self.error(ParseErrorCode::ExpectedSomeValue, start + 1)),
+            Err(Error::Syntax(code, pos)) => Err(self.error(code, start + pos)),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn parse_key(&self, start: usize, end: usize) -> Result<String, Error> {
+        let token = &self.pending[start..end];
+        let is_ident = |c: &u8| c.is_ascii_alphanumeric() || matches!(c, b'_' | b'$');
+        if self.opts.allow_unquoted_keys
+            && token.first().is_some_and(|c| !c.is_ascii_digit())
+            && token.iter().all(is_ident)
+        {
+            // identifiers are always valid UTF-8
+            return Ok(String::from_utf8_lossy(token).into_owned());
+        }
+        match self.parse_token(start, end)? {
+            Value::String(s) => Ok(s.into_owned()),
+            _ => Err(self.error(ParseErrorCode::KeyMustBeAString, end)),
+        }
+    }
+
+    fn push_key(&mut self, key: String, end: usize) -> Result<(), Error> {
+        let policy = self.opts.duplicate_key;
+        if let Some(Frame::Object(entries, _)) = self.stack.last() {
+            if policy == DuplicateKeyPolicy::Error && entries.contains_key(&key) {
+                return Err(self.error(ParseErrorCode::ObjectDuplicateKey(key), end));
+            }
+        }
+        if let Some(Frame::Object(_, current_key)) = self.stack.last_mut() {
+            *current_key = Some(key);
+        }
+        self.state = State::Colon;
+        Ok(())
+    }
+
+    fn push_value(&mut self, value: Vec<u8>) -> Result<(), Error> {
+        let policy = self.opts.duplicate_key;
+        match self.stack.last_mut() {
+            None => {
+                self.result = Some(value);
+                self.state = State::Done;
+            }
+            Some(Frame::Array(items)) => {
+                items.push(value);
+                self.state = State::CommaOrEnd;
+            }
+            Some(Frame::Object(entries, key)) => {
+                let key = key.take().ok_or(Error::InvalidJson)?;
+                // keep the first value if the key is duplicated
+                if policy != DuplicateKeyPolicy::FirstWins || !entries.contains_key(&key) {
+                    entries.insert(key, value);
+                }
+                self.state = State::CommaOrEnd;
+            }
+        }
+        Ok(())
+    }
+
+    fn close_container(&mut self) -> Result<(), Error> {
+        let mut buf = Vec::new();
+        match self.stack.pop() {
+            Some(Frame::Array(items)) => {
+                build_array(items.iter().map(|v| v.as_slice()), &mut buf)?;
+            }
+            Some(Frame::Object(entries, _)) => {
+                build_object(entries.iter().map(|(k, v)| (k, v.as_slice())), &mut buf)?;
+            }
+            None => return Err(Error::InvalidJson),
+        }
+        self.push_value(buf)
+    }
+
+    // Fails if opening one more container exceeds the depth limit.
+    fn check_depth(&self, idx: usize) -> Result<(), Error> {
+        if self.stack.len() >= self.opts.max_depth {
+            return Err(self.error(ParseErrorCode::RecursionLimitExceeded, idx + 1));
+        }
+        Ok(())
+    }
+
+    fn error(&self, code: ParseErrorCode, idx: usize) -> Error {
+        Error::Syntax(code, self.offset + idx)
+    }
+}
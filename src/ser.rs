@@ -12,95 +12,194 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::io::Write;
+
 use byteorder::BigEndian;
 use byteorder::WriteBytesExt;
 
 use super::constants::*;
+use super::error::Error;
+use super::extension::ExtensionValue;
+use super::format::write_version_header;
 use super::jentry::JEntry;
 use super::value::Object;
 use super::value::Value;
 
-pub struct Encoder<'a> {
-    pub buf: &'a mut Vec<u8>,
+/// `Encoder` serializes `JSONB` Values into a writer, such as a file or a network buffer.
+///
+/// This is not a streaming encoder: the `JEntries` of a container are written before
+/// the values, but they can't be known until the values are encoded, so each `Value`
+/// is fully encoded into an internal buffer first and then written with one `write_all`.
+/// The memory used is the size of the largest encoded `Value`. The buffer is reused,
+/// encoding a stream of values doesn't allocate for each one.
+pub struct Encoder<W> {
+    writer: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(writer: W) -> Encoder<W> {
+        Self {
+            writer,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Encode `JSONB` Value and write it into the writer, returns the number of bytes written.
+    pub fn encode(&mut self, value: &Value<'_>) -> Result<usize, Error> {
+        self.buf.clear();
+        encode_to_vec(value, &mut self.buf)?;
+        self.flush_buf()
+    }
+
+    /// Encode `JSONB` Value starting with the version header and write it into the writer,
+    /// returns the number of bytes written.
+    pub fn encode_with_version(&mut self, value: &Value<'_>) -> Result<usize, Error> {
+        self.buf.clear();
+        encode_to_vec_with_version(value, &mut self.buf)?;
+        self.flush_buf()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn flush_buf(&mut self) -> Result<usize, Error> {
+        self.writer.write_all(&self.buf)?;
+        Ok(self.buf.len())
+    }
+}
+
+// Encode `JSONB` Value to the end of the buffer,
+// nothing is left in the buffer if the value can't be encoded.
+// All the JEntries store lengths, the same as the releases before the version header.
+pub(crate) fn encode_to_vec(value: &Value<'_>, buf: &mut Vec<u8>) -> Result<(), Error> {
+    encode_into(value, buf, false)
 }
 
-impl<'a> Encoder<'a> {
-    pub fn new(buf: &'a mut Vec<u8>) -> Encoder<'a> {
-        Self { buf }
+// Encode `JSONB` Value starting with the version header to the end of the buffer,
+// every `JENTRY_OFFSET_STRIDE`th JEntry of a container stores an offset,
+// which can only be read by the releases that understand the header.
+pub(crate) fn encode_to_vec_with_version(
+    value: &Value<'_>,
+    buf: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let old_len = buf.len();
+    write_version_header(buf);
+    let res = encode_into(value, buf, true);
+    if res.is_err() {
+        buf.truncate(old_len);
     }
+    res
+}
+
+fn encode_into(value: &Value<'_>, buf: &mut Vec<u8>, with_offsets: bool) -> Result<(), Error> {
+    let old_len = buf.len();
+    let mut encoder = VecEncoder { buf, with_offsets };
+    let res = encoder.encode(value);
+    if res.is_err() {
+        encoder.buf.truncate(old_len);
+    }
+    res
+}
 
+// Make the header of an `Array` or `Object` with `length` elements,
+// the number of elements is stored in the lower 29 bits.
+pub(crate) fn make_container_header(tag: u32, length: usize) -> Result<u32, Error> {
+    if length > CONTAINER_HEADER_LEN_MASK as usize {
+        return Err(Error::ValueTooLarge);
+    }
+    Ok(tag | length as u32)
+}
+
+// Encode into a `Vec` so the `JEntries` can be reserved and filled
+// after the values are encoded.
+struct VecEncoder<'a> {
+    buf: &'a mut Vec<u8>,
+    // write offsets into the JEntries, only allowed after the version header
+    with_offsets: bool,
+}
+
+impl VecEncoder<'_> {
     // Encode `JSONB` Value to a sequence of bytes
-    pub fn encode(&mut self, value: &Value<'a>) {
+    fn encode(&mut self, value: &Value<'_>) -> Result<(), Error> {
         match value {
-            Value::Array(array) => self.encode_array(array),
-            Value::Object(obj) => self.encode_object(obj),
-            _ => self.encode_scalar(value),
+            Value::Array(array) => self.encode_array(array)?,
+            Value::Object(obj) => self.encode_object(obj)?,
+            _ => self.encode_scalar(value)?,
         };
+        Ok(())
     }
 
     // Encoded `Scalar` consists of a `Header`, a `JEntry` and encoded data
-    fn encode_scalar(&mut self, value: &Value<'a>) -> usize {
-        self.buf
-            .write_u32::<BigEndian>(SCALAR_CONTAINER_TAG)
-            .unwrap();
+    fn encode_scalar(&mut self, value: &Value<'_>) -> Result<usize, Error> {
+        self.buf.write_u32::<BigEndian>(SCALAR_CONTAINER_TAG)?;
 
         // Scalar Value only has one JEntry
         let mut scalar_len = 4 + 4;
         let mut jentry_index = self.reserve_jentries(4);
 
-        let jentry = self.encode_value(value);
+        let jentry = self.encode_value(value)?;
         scalar_len += jentry.length as usize;
-        self.replace_jentry(jentry, &mut jentry_index);
+        self.replace_jentry(jentry, 0, &mut jentry_index)?;
 
-        scalar_len
+        Ok(scalar_len)
     }
 
     // Encoded `Array` consists of a `Header`, N `JEntries` and encoded data
     // N is the number of `Array` inner values
-    fn encode_array(&mut self, values: &[Value<'a>]) -> usize {
-        let header = ARRAY_CONTAINER_TAG | values.len() as u32;
-        self.buf.write_u32::<BigEndian>(header).unwrap();
+    fn encode_array(&mut self, values: &[Value<'_>]) -> Result<usize, Error> {
+        let header = make_container_header(ARRAY_CONTAINER_TAG, values.len())?;
+        self.buf.write_u32::<BigEndian>(header)?;
 
         // `Array` has N `JEntries`
         let mut array_len = 4 + values.len() * 4;
         let mut jentry_index = self.reserve_jentries(values.len() * 4);
 
         // encode all values
-        for value in values.iter() {
-            let jentry = self.encode_value(value);
+        for (i, value) in values.iter().enumerate() {
+            let jentry = self.encode_value(value)?;
             array_len += jentry.length as usize;
-            self.replace_jentry(jentry, &mut jentry_index);
+            self.replace_jentry(jentry, i, &mut jentry_index)?;
         }
 
-        array_len
+        Ok(array_len)
     }
 
     // Encoded `Object` consists of a `Header`, 2 * N `JEntries` and encoded data
     // N is the number of `Object` inner key value pair
-    fn encode_object(&mut self, obj: &Object<'a>) -> usize {
-        let header = OBJECT_CONTAINER_TAG | obj.len() as u32;
-        self.buf.write_u32::<BigEndian>(header).unwrap();
+    fn encode_object(&mut self, obj: &Object<'_>) -> Result<usize, Error> {
+        let header = make_container_header(OBJECT_CONTAINER_TAG, obj.len())?;
+        self.buf.write_u32::<BigEndian>(header)?;
 
         // `Object` has 2 * N `JEntries`
         let mut object_len = 4 + obj.len() * 8;
         let mut jentry_index = self.reserve_jentries(obj.len() * 8);
 
         // encode all keys first
-        for (key, _) in obj.iter() {
+        for (i, (key, _)) in obj.iter().enumerate() {
             let len = key.len();
             object_len += len;
             self.buf.extend_from_slice(key.as_bytes());
-            let jentry = JEntry::make_string_jentry(len);
-            self.replace_jentry(jentry, &mut jentry_index);
+            let jentry = JEntry::make_jentry(STRING_TAG, len)?;
+            self.replace_jentry(jentry, i, &mut jentry_index)?;
         }
         // encode all values
-        for (_, value) in obj.iter() {
-            let jentry = self.encode_value(value);
+        for (i, (_, value)) in obj.iter().enumerate() {
+            let jentry = self.encode_value(value)?;
             object_len += jentry.length as usize;
-            self.replace_jentry(jentry, &mut jentry_index);
+            self.replace_jentry(jentry, obj.len() + i, &mut jentry_index)?;
         }
 
-        object_len
+        Ok(object_len)
     }
 
     // Reserve space for `JEntries` and fill them later
@@ -112,19 +211,31 @@ impl<'a> Encoder<'a> {
         old_len
     }
 
-    // Write encoded `JEntry` to the corresponding index
-    fn replace_jentry(&mut self, jentry: JEntry, jentry_index: &mut usize) {
-        let jentry_bytes = jentry.encoded().to_be_bytes();
+    // Write encoded `JEntry` of the `index`th value to the corresponding position,
+    // the value has just been written, so it ends at the end of the buffer.
+    fn replace_jentry(
+        &mut self,
+        jentry: JEntry,
+        index: usize,
+        jentry_index: &mut usize,
+    ) -> Result<(), Error> {
+        let encoded = if self.with_offsets {
+            jentry.encoded_at(index, *jentry_index, self.buf.len())?
+        } else {
+            jentry.encoded()
+        };
+        let jentry_bytes = encoded.to_be_bytes();
         for (i, b) in jentry_bytes.iter().enumerate() {
             self.buf[*jentry_index + i] = *b;
         }
         *jentry_index += 4;
+        Ok(())
     }
 
     // `Null` and `Boolean` only has a `JEntry`
     // `Number` and `String` has a `JEntry` and an encoded data
     // `Array` and `Object` has a container `JEntry` and nested encoded data
-    fn encode_value(&mut self, value: &Value<'a>) -> JEntry {
+    fn encode_value(&mut self, value: &Value<'_>) -> Result<JEntry, Error> {
         let jentry = match value {
             Value::Null => JEntry::make_null_jentry(),
             Value::Bool(v) => {
@@ -136,25 +247,30 @@ impl<'a> Encoder<'a> {
             }
             Value::Number(v) => {
                 let old_off = self.buf.len();
-                let _ = v.compact_encode(&mut self.buf).unwrap();
+                v.compact_encode(&mut *self.buf)?;
                 let len = self.buf.len() - old_off;
-                JEntry::make_number_jentry(len)
+                JEntry::make_jentry(NUMBER_TAG, len)?
             }
             Value::String(s) => {
                 let len = s.len();
                 self.buf.extend_from_slice(s.as_ref().as_bytes());
-                JEntry::make_string_jentry(len)
+                JEntry::make_jentry(STRING_TAG, len)?
             }
             Value::Array(array) => {
-                let len = self.encode_array(array);
-                JEntry::make_container_jentry(len)
+                let len = self.encode_array(array)?;
+                JEntry::make_jentry(CONTAINER_TAG, len)?
             }
             Value::Object(obj) => {
-                let len = self.encode_object(obj);
-                JEntry::make_container_jentry(len)
+                let len = self.encode_object(obj)?;
+                JEntry::make_jentry(CONTAINER_TAG, len)?
+            }
+            Value::Binary(_) | Value::Date(_) | Value::TimestampTz(_) | Value::Uuid(_) => {
+                let v = ExtensionValue::from_value(value).unwrap();
+                let len = v.encode(self.buf);
+                JEntry::make_jentry(EXTENSION_TAG, len)?
             }
         };
 
-        jentry
+        Ok(jentry)
     }
 }
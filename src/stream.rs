@@ -0,0 +1,232 @@
+// Copyright 2024 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::ErrorKind;
+use std::io::Read;
+
+use crate::constants::READ_CHUNK_SIZE;
+use crate::error::Error;
+use crate::error::ParseErrorCode;
+use crate::parser::parse_first_value;
+use crate::parser::ParseOptions;
+use crate::value::Value;
+
+/// Parse newline-delimited `JSON` (NDJSON) or concatenated `JSON` text,
+/// returns an iterator over the values.
+///
+/// Values can be separated by any whitespaces or nothing at all.
+/// If a record is invalid, an `Error::Record` with the index and byte offset
+/// of the record is returned, and the parsing resumes from the next line.
+pub fn parse_values_iter(buf: &[u8]) -> ValuesIter<'_> {
+    parse_values_iter_with_options(buf, &ParseOptions::default())
+}
+
+/// Parse newline-delimited or concatenated `JSON` text with the specified options.
+pub fn parse_values_iter_with_options<'a>(buf: &'a [u8], opts: &ParseOptions) -> ValuesIter<'a> {
+    ValuesIter {
+        buf,
+        offset: 0,
+        index: 0,
+        opts: *opts,
+    }
+}
+
+/// Parse newline-delimited or concatenated `JSON` text from a reader,
+/// returns an iterator over the encoded `JSONB` values.
+///
+/// The reader is consumed in chunks, so values can cross the buffer boundaries,
+/// only the bytes of the current record are kept in memory.
+pub fn parse_values_reader<R: Read>(reader: R) -> ReaderValuesIter<R> {
+    parse_values_reader_with_options(reader, &ParseOptions::default())
+}
+
+/// Parse newline-delimited or concatenated `JSON` text from a reader with the specified options.
+pub fn parse_values_reader_with_options<R: Read>(
+    reader: R,
+    opts: &ParseOptions,
+) -> ReaderValuesIter<R> {
+    ReaderValuesIter {
+        reader,
+        buf: Vec::new(),
+        offset: 0,
+        index: 0,
+        opts: *opts,
+        eof: false,
+        finished: false,
+    }
+}
+
+pub struct ValuesIter<'a> {
+    buf: &'a [u8],
+    offset: usize,
+    index: usize,
+    opts: ParseOptions,
+}
+
+impl<'a> Iterator for ValuesIter<'a> {
+    type Item = Result<Value<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let buf = self.buf.get(self.offset..)?;
+        // skip the whitespaces, so that the error position is relative to the record
+        let start = start_offset(buf);
+        self.offset += start;
+        let buf = &buf[start..];
+        match parse_first_value(buf, &self.opts) {
+            Ok(Some((value, len))) => {
+                self.offset += len;
+                self.index += 1;
+                Some(Ok(value))
+            }
+            Ok(None) => {
+                self.offset = self.buf.len();
+                None
+            }
+            Err(err) => {
+                let err = Error::Record(self.index, self.offset, Box::new(err));
+                self.offset += skip_line(buf);
+                self.index += 1;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+pub struct ReaderValuesIter<R: Read> {
+    reader: R,
+    buf: Vec<u8>,
+    // the offset of `buf` in the stream
+    offset: usize,
+    index: usize,
+    opts: ParseOptions,
+    eof: bool,
+    finished: bool,
+}
+
+impl<R: Read> ReaderValuesIter<R> {
+    // Read the next chunk into the buffer, returns the number of bytes read.
+    fn fill_buf(&mut self) -> Result<usize, Error> {
+        let old_len = self.buf.len();
+        let chunk_size = READ_CHUNK_SIZE.max(old_len);
+        self.buf.resize(old_len + chunk_size, 0);
+        loop {
+            match self.reader.read(&mut self.buf[old_len..]) {
+                Ok(n) => {
+                    self.buf.truncate(old_len + n);
+                    if n == 0 {
+                        self.eof = true;
+                    }
+                    return Ok(n);
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.buf.truncate(old_len);
+                    return Err(err.into());
+                }
+            }
+        }
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.buf.drain(..len);
+        self.offset += len;
+    }
+
+    // Skip the invalid record to the next line, reading more data if needed.
+    fn skip_invalid_line(&mut self) -> Result<(), Error> {
+        loop {
+            if let Some(pos) = self.buf.iter().position(|c| *c == b'\n') {
+                self.consume(pos + 1);
+                return Ok(());
+            }
+            let len = self.buf.len();
+            self.consume(len);
+            if self.eof || self.fill_buf()? == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    fn next_value(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        loop {
+            // skip the whitespaces, so that the error position is relative to the record
+            let start = start_offset(&self.buf);
+            self.consume(start);
+            let result = match parse_first_value(&self.buf, &self.opts) {
+                // the value may continue in the next chunk, such as numbers
+                Ok(Some((_, len))) if len == self.buf.len() && !self.eof => None,
+                Ok(Some((value, len))) => Some(Ok(Some((value.to_vec(), len)))),
+                Ok(None) if self.eof => Some(Ok(None)),
+                Ok(None) => None,
+                Err(Error::Syntax(ParseErrorCode::InvalidEOF, _)) if !self.eof => None,
+                Err(err) => Some(Err(err)),
+            };
+            match result {
+                Some(Ok(Some((value, len)))) => {
+                    self.consume(len);
+                    self.index += 1;
+                    return Ok(Some(value));
+                }
+                Some(Ok(None)) => return Ok(None),
+                Some(Err(err)) => {
+                    let err = Error::Record(self.index, self.offset, Box::new(err));
+                    self.index += 1;
+                    self.skip_invalid_line()?;
+                    return Err(err);
+                }
+                None => {
+                    self.fill_buf()?;
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for ReaderValuesIter<R> {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.next_value() {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(err @ Error::Record(..)) => Some(Err(err)),
+            Err(err) => {
+                // failed to read from the reader
+                self.finished = true;
+                Some(Err(Error::Record(self.index, self.offset, Box::new(err))))
+            }
+        }
+    }
+}
+
+// The offset of the first non-whitespace character, where the record starts.
+fn start_offset(buf: &[u8]) -> usize {
+    buf.iter()
+        .position(|c| !c.is_ascii_whitespace())
+        .unwrap_or(buf.len())
+}
+
+// The length of the first line of the invalid record, including the newline.
+fn skip_line(buf: &[u8]) -> usize {
+    match buf.iter().position(|c| *c == b'\n') {
+        Some(pos) => pos + 1,
+        None => buf.len(),
+    }
+}
@@ -42,7 +42,18 @@ static HEX: [u8; 256] = {
     ]
 };
 
-pub fn parse_string(mut data: &[u8], len: usize, idx: &mut usize) -> Result<String, Error> {
+pub fn parse_string(data: &[u8], len: usize, idx: &mut usize) -> Result<String, Error> {
+    parse_quoted_string(data, len, idx, b'"')
+}
+
+// Parse the string enclosed by the `quote` character,
+// the `quote` character itself can also be escaped.
+pub fn parse_quoted_string(
+    mut data: &[u8],
+    len: usize,
+    idx: &mut usize,
+    quote: u8,
+) -> Result<String, Error> {
     let mut buf = Vec::with_capacity(len);
     let mut str_buf = String::with_capacity(4);
     while !data.is_empty() {
@@ -50,7 +61,7 @@ pub fn parse_string(mut data: &[u8], len: usize, idx: &mut usize) -> Result<Stri
         let byte = data[0];
         if byte == b'\\' {
             data = &data[1..];
-            data = parse_escaped_string(data, idx, &mut str_buf)?;
+            data = parse_escaped_string(data, idx, quote, &mut str_buf)?;
             buf.extend_from_slice(str_buf.as_bytes());
             str_buf.clear();
         } else {
@@ -64,6 +75,7 @@ pub fn parse_string(mut data: &[u8], len: usize, idx: &mut usize) -> Result<Stri
 fn parse_escaped_string<'a>(
     mut data: &'a [u8],
     idx: &mut usize,
+    quote: u8,
     str_buf: &mut String,
 ) -> Result<&'a [u8], Error> {
     let byte = data[0];
@@ -72,6 +84,7 @@ fn parse_escaped_string<'a>(
     match byte {
         b'\\' => str_buf.push(BS),
         b'"' => str_buf.push(QU),
+        b'\'' if quote == b'\'' => str_buf.push('\''),
         b'/' => str_buf.push(SD),
         b'b' => str_buf.push(BB),
         b'f' => str_buf.push(FF),
@@ -190,3 +203,98 @@ fn decode_hex_escape(numbers: Vec<u8>, idx: &usize) -> Result<u16, Error> {
     }
     Ok(n)
 }
+
+// Write the string as a quoted `JSON` string, escaping the quotation mark,
+// the backslash and the control characters.
+pub(crate) fn escape_string<W: std::fmt::Write>(value: &str, w: &mut W) -> std::fmt::Result {
+    escape_string_with(value, w, false, false)
+}
+
+// Write the string as a quoted `JSON` string, also escaping the non-ASCII characters
+// if `ascii_only` is true, and the `<`, `>`, `&` characters if `html_safe` is true.
+pub(crate) fn escape_string_with<W: std::fmt::Write>(
+    value: &str,
+    w: &mut W,
+    ascii_only: bool,
+    html_safe: bool,
+) -> std::fmt::Result {
+    w.write_char('"')?;
+    let mut last_start = 0;
+    for (i, b) in value.bytes().enumerate() {
+        // skip the remaining bytes of an escaped non-ASCII character
+        if i < last_start {
+            continue;
+        }
+        let escaped = match b {
+            b'"' | b'\\' | 0x00..=0x1F => true,
+            b'<' | b'>' | b'&' => html_safe,
+            0x80..=0xFF => ascii_only,
+            _ => false,
+        };
+        if !escaped {
+            continue;
+        }
+        if i > last_start {
+            w.write_str(&value[last_start..i])?;
+        }
+        let c = value[i..].chars().next().unwrap_or_default();
+        match c {
+            '\\' => w.write_str("\\\\")?,
+            '"' => w.write_str("\\\"")?,
+            '\x08' => w.write_str("\\b")?,
+            '\x0C' => w.write_str("\\f")?,
+            '\n' => w.write_str("\\n")?,
+            '\r' => w.write_str("\\r")?,
+            '\t' => w.write_str("\\t")?,
+            _ => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    write!(w, "\\u{:04x}", unit)?;
+                }
+            }
+        }
+        last_start = i + c.len_utf8();
+    }
+    if last_start < value.len() {
+        w.write_str(&value[last_start..])?;
+    }
+    w.write_char('"')
+}
+
+// Append the bytes to the comparable bytes with a terminator, so that they can be decoded
+// and a shorter value is less than the longer values starting with it.
+// The zero bytes are escaped as `0x00 0xFF` and the terminator is `0x00 0x01`.
+pub(crate) fn comparable_encode_bytes(value: &[u8], buf: &mut Vec<u8>) {
+    for b in value {
+        buf.push(*b);
+        if *b == 0x00 {
+            buf.push(0xFF);
+        }
+    }
+    buf.extend_from_slice(&[0x00, 0x01]);
+}
+
+// Decode the bytes written by `comparable_encode_bytes` and advance the input.
+pub(crate) fn comparable_decode_bytes(input: &mut &[u8]) -> Result<Vec<u8>, Error> {
+    let mut value = Vec::new();
+    loop {
+        match read_exact(input, 1)?[0] {
+            0x00 => match read_exact(input, 1)?[0] {
+                0xFF => value.push(0x00),
+                0x01 => return Ok(value),
+                _ => return Err(Error::InvalidJsonb),
+            },
+            b => value.push(b),
+        }
+    }
+}
+
+// Read the next `len` bytes and advance the input, running out of bytes is an error.
+pub(crate) fn read_exact<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if input.len() < len {
+        return Err(Error::InvalidJsonb);
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
@@ -0,0 +1,254 @@
+// Copyright 2024 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::cmp::Ordering;
+
+use super::constants::*;
+use super::error::ValidationError;
+use super::error::ValidationErrorCode;
+use super::extension::ExtensionValue;
+use super::format::detect_format;
+use super::format::Format;
+use super::format::JSONB_VERSION;
+use super::iterator::is_sorted_object;
+use super::jentry::JEntry;
+use super::number::Number;
+
+/// Check that the bytes are a well-formed `JSONB` value, so that they can be
+/// safely passed to the other functions. Every container header and `JEntry`
+/// is checked, as well as the bounds of the lengths and offsets, the UTF-8
+/// of strings and keys, and the encoding of numbers and extension values.
+///
+/// The keys of objects must be sorted and unique in `JSONB` with the version header
+/// and in objects storing offset JEntries, which are searched by binary search.
+/// The keys of `JSONB` without the header may be in any order, as written by the
+/// releases before the header was introduced.
+///
+/// Arrays and objects can be nested at most 128 levels deep,
+/// use [`validate_with_max_depth`] for a different limit.
+pub fn validate(value: &[u8]) -> Result<(), ValidationError> {
+    validate_with_max_depth(value, DEFAULT_MAX_DEPTH)
+}
+
+/// Check that the bytes are a well-formed `JSONB` value, with arrays and objects
+/// nested at most `max_depth` levels deep. See [`validate`] for the details.
+pub fn validate_with_max_depth(value: &[u8], max_depth: usize) -> Result<(), ValidationError> {
+    let start = match detect_format(value) {
+        Format::VersionedJsonb(version) if (1..=JSONB_VERSION).contains(&version) => {
+            JSONB_VERSION_HEADER_LEN
+        }
+        Format::VersionedJsonb(_) => {
+            return Err(ValidationError::new(ValidationErrorCode::InvalidVersion, 3));
+        }
+        _ => 0,
+    };
+    let validator = Validator {
+        buf: value,
+        max_depth,
+        sorted_keys: start > 0,
+    };
+    validator.validate_container(start, value.len(), 0)
+}
+
+// All positions are absolute offsets in `buf`,
+// so that errors can report where the problem is.
+struct Validator<'a> {
+    buf: &'a [u8],
+    max_depth: usize,
+    // whether the keys of all the objects must be sorted and unique
+    sorted_keys: bool,
+}
+
+impl Validator<'_> {
+    // Validate the container from `start`, which must end exactly at `end`.
+    fn validate_container(
+        &self,
+        start: usize,
+        end: usize,
+        depth: usize,
+    ) -> Result<(), ValidationError> {
+        let header = self.read_u32(start, end)?;
+        match header & CONTAINER_HEADER_TYPE_MASK {
+            // nested containers are always arrays or objects
+            SCALAR_CONTAINER_TAG if header == SCALAR_CONTAINER_TAG && depth == 0 => {
+                let jentry_offset = start + 4;
+                let encoded = self.read_u32(jentry_offset, end)?;
+                if encoded & JENTRY_IS_OFF_FLAG != 0 {
+                    return Err(error(ValidationErrorCode::InvalidJEntry, jentry_offset));
+                }
+                let jentry = JEntry::decode_jentry(encoded);
+                if jentry.type_code == CONTAINER_TAG {
+                    return Err(error(ValidationErrorCode::InvalidJEntry, jentry_offset));
+                }
+                let val_offset = jentry_offset + 4;
+                let val_end = self.value_end(&jentry, jentry_offset, val_offset, end)?;
+                self.validate_value(&jentry, jentry_offset, val_offset, val_end, depth)?;
+                self.check_end(val_end, end)
+            }
+            ARRAY_CONTAINER_TAG | OBJECT_CONTAINER_TAG if depth >= self.max_depth => {
+                Err(error(ValidationErrorCode::DepthLimitExceeded, start))
+            }
+            ARRAY_CONTAINER_TAG => {
+                let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
+                let mut val_offset = self.jentries_end(start, length, end)?;
+                for i in 0..length {
+                    let jentry_offset = start + 4 + 4 * i;
+                    let jentry = self.read_jentry(jentry_offset, val_offset, end)?;
+                    let val_end = self.value_end(&jentry, jentry_offset, val_offset, end)?;
+                    self.validate_value(&jentry, jentry_offset, val_offset, val_end, depth + 1)?;
+                    val_offset = val_end;
+                }
+                self.check_end(val_offset, end)
+            }
+            OBJECT_CONTAINER_TAG => {
+                let length = (header & CONTAINER_HEADER_LEN_MASK) as usize;
+                let mut val_offset = self.jentries_end(start, length * 2, end)?;
+                let sorted_keys = self.sorted_keys || is_sorted_object(self.buf, start, length);
+                // the keys are stored before the values
+                let mut prev_key: Option<&[u8]> = None;
+                for i in 0..length {
+                    let jentry_offset = start + 4 + 4 * i;
+                    let jentry = self.read_jentry(jentry_offset, val_offset, end)?;
+                    if jentry.type_code != STRING_TAG {
+                        return Err(error(ValidationErrorCode::KeyMustBeAString, jentry_offset));
+                    }
+                    let val_end = self.value_end(&jentry, jentry_offset, val_offset, end)?;
+                    self.validate_value(&jentry, jentry_offset, val_offset, val_end, depth + 1)?;
+                    let key = &self.buf[val_offset..val_end];
+                    if let Some(prev_key) = prev_key.filter(|_| sorted_keys) {
+                        match prev_key.cmp(key) {
+                            Ordering::Less => {}
+                            Ordering::Equal => {
+                                return Err(error(ValidationErrorCode::DuplicateKey, val_offset));
+                            }
+                            Ordering::Greater => {
+                                return Err(error(ValidationErrorCode::UnsortedKeys, val_offset));
+                            }
+                        }
+                    }
+                    prev_key = Some(key);
+                    val_offset = val_end;
+                }
+                for i in length..length * 2 {
+                    let jentry_offset = start + 4 + 4 * i;
+                    let jentry = self.read_jentry(jentry_offset, val_offset, end)?;
+                    let val_end = self.value_end(&jentry, jentry_offset, val_offset, end)?;
+                    self.validate_value(&jentry, jentry_offset, val_offset, val_end, depth + 1)?;
+                    val_offset = val_end;
+                }
+                self.check_end(val_offset, end)
+            }
+            _ => Err(error(ValidationErrorCode::InvalidHeader, start)),
+        }
+    }
+
+    // Validate the value of the `JEntry`, stored in `val_offset..val_end`.
+    fn validate_value(
+        &self,
+        jentry: &JEntry,
+        jentry_offset: usize,
+        val_offset: usize,
+        val_end: usize,
+        depth: usize,
+    ) -> Result<(), ValidationError> {
+        let data = &self.buf[val_offset..val_end];
+        match jentry.type_code {
+            NULL_TAG | TRUE_TAG | FALSE_TAG => {
+                if !data.is_empty() {
+                    return Err(error(ValidationErrorCode::InvalidLength, jentry_offset));
+                }
+            }
+            STRING_TAG => {
+                if let Err(e) = simdutf8::compat::from_utf8(data) {
+                    let offset = val_offset + e.valid_up_to();
+                    return Err(error(ValidationErrorCode::InvalidUtf8, offset));
+                }
+            }
+            NUMBER_TAG => {
+                if Number::decode(data).is_err() {
+                    return Err(error(ValidationErrorCode::InvalidNumber, val_offset));
+                }
+            }
+            EXTENSION_TAG => {
+                if ExtensionValue::decode(data).is_err() {
+                    return Err(error(ValidationErrorCode::InvalidExtension, val_offset));
+                }
+            }
+            CONTAINER_TAG => self.validate_container(val_offset, val_end, depth)?,
+            _ => return Err(error(ValidationErrorCode::InvalidJEntry, jentry_offset)),
+        }
+        Ok(())
+    }
+
+    // Check that `length` JEntries after the header fit in the container,
+    // returns the position of the first value.
+    fn jentries_end(
+        &self,
+        start: usize,
+        length: usize,
+        end: usize,
+    ) -> Result<usize, ValidationError> {
+        match length
+            .checked_mul(4)
+            .and_then(|len| len.checked_add(start + 4))
+        {
+            Some(jentries_end) if jentries_end <= end => Ok(jentries_end),
+            _ => Err(error(ValidationErrorCode::InvalidLength, start)),
+        }
+    }
+
+    // Read the `JEntry` and resolve the offset to the length of the value.
+    fn read_jentry(
+        &self,
+        jentry_offset: usize,
+        val_offset: usize,
+        end: usize,
+    ) -> Result<JEntry, ValidationError> {
+        let encoded = self.read_u32(jentry_offset, end)?;
+        JEntry::decode_jentry_at(encoded, jentry_offset, val_offset)
+            .map_err(|_| error(ValidationErrorCode::InvalidJEntry, jentry_offset))
+    }
+
+    // The end of the value, which must be inside the container.
+    fn value_end(
+        &self,
+        jentry: &JEntry,
+        jentry_offset: usize,
+        val_offset: usize,
+        end: usize,
+    ) -> Result<usize, ValidationError> {
+        let val_end = val_offset + jentry.length as usize;
+        if val_end > end {
+            return Err(error(ValidationErrorCode::InvalidLength, jentry_offset));
+        }
+        Ok(val_end)
+    }
+
+    fn check_end(&self, val_end: usize, end: usize) -> Result<(), ValidationError> {
+        if val_end != end {
+            return Err(error(ValidationErrorCode::TrailingBytes, val_end));
+        }
+        Ok(())
+    }
+
+    fn read_u32(&self, offset: usize, end: usize) -> Result<u32, ValidationError> {
+        match self.buf.get(offset..offset + 4) {
+            Some(bytes) if offset + 4 <= end => Ok(u32::from_be_bytes(bytes.try_into().unwrap())),
+            _ => Err(error(ValidationErrorCode::UnexpectedEOF, offset)),
+        }
+    }
+}
+
+fn error(code: ValidationErrorCode, offset: usize) -> ValidationError {
+    ValidationError::new(code, offset)
+}
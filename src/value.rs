@@ -13,14 +13,25 @@
 // limitations under the License.
 
 use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::hash::Hash;
+use std::hash::Hasher;
 use std::mem::discriminant;
 
+use super::constants::*;
+use super::error::Error;
+use super::extension::Date;
+use super::extension::ExtensionValue;
+use super::extension::TimestampTz;
+use super::extension::Uuid;
 use super::number::Number;
-use super::ser::Encoder;
+use super::ser::encode_to_vec;
+use super::ser::encode_to_vec_with_version;
+use super::util::escape_string;
 
 pub type Object<'a> = BTreeMap<String, Value<'a>>;
 
@@ -34,6 +45,10 @@ pub enum Value<'a> {
     Number(Number),
     Array(Vec<Value<'a>>),
     Object(Object<'a>),
+    Binary(Cow<'a, [u8]>),
+    Date(Date),
+    TimestampTz(TimestampTz),
+    Uuid(Uuid),
 }
 
 impl<'a> Debug for Value<'a> {
@@ -53,6 +68,10 @@ impl<'a> Debug for Value<'a> {
                 Debug::fmt(v, formatter)?;
                 formatter.write_str(")")
             }
+            Value::Binary(ref v) => formatter.debug_tuple("Binary").field(v).finish(),
+            Value::Date(ref v) => Debug::fmt(v, formatter),
+            Value::TimestampTz(ref v) => Debug::fmt(v, formatter),
+            Value::Uuid(ref v) => Debug::fmt(v, formatter),
         }
     }
 }
@@ -69,9 +88,7 @@ impl<'a> Display for Value<'a> {
                 }
             }
             Value::Number(ref v) => write!(f, "{}", v),
-            Value::String(ref v) => {
-                write!(f, "{:?}", v)
-            }
+            Value::String(ref v) => escape_string(v, f),
             Value::Array(ref vs) => {
                 let mut first = true;
                 write!(f, "[")?;
@@ -92,14 +109,82 @@ impl<'a> Display for Value<'a> {
                         write!(f, ",")?;
                     }
                     first = false;
-                    write!(f, "\"")?;
-                    write!(f, "{k}")?;
-                    write!(f, "\"")?;
+                    escape_string(k, f)?;
                     write!(f, ":")?;
                     write!(f, "{v}")?;
                 }
                 write!(f, "}}")
             }
+            Value::Binary(_) | Value::Date(_) | Value::TimestampTz(_) | Value::Uuid(_) => {
+                let v = ExtensionValue::from_value(self).unwrap();
+                write!(f, "\"{}\"", v)
+            }
+        }
+    }
+}
+
+impl<'a> PartialOrd for Value<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Values compare in the same order as `compare` of the encoded `JSONB` bytes:
+// Null > Array > Object > Extension > String > Number > Boolean(true > false),
+// arrays and objects compare the elements in turn, then the lengths.
+impl<'a> Ord for Value<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::String(l), Value::String(r)) => l.cmp(r),
+            (Value::Number(l), Value::Number(r)) => l.cmp(r),
+            (Value::Array(l), Value::Array(r)) => l.cmp(r),
+            (Value::Object(l), Value::Object(r)) => l.cmp(r),
+            (_, _) => match (
+                ExtensionValue::from_value(self),
+                ExtensionValue::from_value(other),
+            ) {
+                (Some(l), Some(r)) => l.cmp(&r),
+                (_, _) => self.compare_level().cmp(&other.compare_level()),
+            },
+        }
+    }
+}
+
+// The hash is the same as `hash_value` of the encoded `JSONB` bytes,
+// so equal values have the same hash, such as numbers with different representations.
+impl<'a> Hash for Value<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Value::Null => state.write_u8(NULL_LEVEL),
+            Value::Bool(true) => state.write_u8(TRUE_LEVEL),
+            Value::Bool(false) => state.write_u8(FALSE_LEVEL),
+            Value::String(v) => {
+                state.write_u8(STRING_LEVEL);
+                v.hash(state);
+            }
+            Value::Number(v) => {
+                state.write_u8(NUMBER_LEVEL);
+                v.hash(state);
+            }
+            Value::Array(vs) => {
+                state.write_u8(ARRAY_LEVEL);
+                vs.len().hash(state);
+                for v in vs {
+                    v.hash(state);
+                }
+            }
+            Value::Object(vs) => {
+                state.write_u8(OBJECT_LEVEL);
+                vs.len().hash(state);
+                for (k, v) in vs {
+                    k.hash(state);
+                    v.hash(state);
+                }
+            }
+            Value::Binary(_) | Value::Date(_) | Value::TimestampTz(_) | Value::Uuid(_) => {
+                state.write_u8(EXTENSION_LEVEL);
+                ExtensionValue::from_value(self).unwrap().hash(state);
+            }
         }
     }
 }
@@ -161,6 +246,14 @@ impl<'a> Value<'a> {
         self.as_u64().is_some()
     }
 
+    pub fn is_i128(&self) -> bool {
+        self.as_i128().is_some()
+    }
+
+    pub fn is_u128(&self) -> bool {
+        self.as_u128().is_some()
+    }
+
     pub fn is_f64(&self) -> bool {
         self.as_f64().is_some()
     }
@@ -179,6 +272,20 @@ impl<'a> Value<'a> {
         }
     }
 
+    pub fn as_i128(&self) -> Option<i128> {
+        match self {
+            Value::Number(n) => n.as_i128(),
+            _ => None,
+        }
+    }
+
+    pub fn as_u128(&self) -> Option<u128> {
+        match self {
+            Value::Number(n) => n.as_u128(),
+            _ => None,
+        }
+    }
+
     pub fn as_f64(&self) -> Option<f64> {
         match self {
             Value::Number(n) => n.as_f64(),
@@ -186,6 +293,34 @@ impl<'a> Value<'a> {
         }
     }
 
+    pub fn as_binary(&self) -> Option<&[u8]> {
+        match self {
+            Value::Binary(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_date(&self) -> Option<Date> {
+        match self {
+            Value::Date(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_timestamp_tz(&self) -> Option<TimestampTz> {
+        match self {
+            Value::TimestampTz(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_uuid(&self) -> Option<Uuid> {
+        match self {
+            Value::Uuid(v) => Some(*v),
+            _ => None,
+        }
+    }
+
     pub fn is_boolean(&self) -> bool {
         self.as_bool().is_some()
     }
@@ -209,18 +344,117 @@ impl<'a> Value<'a> {
     }
 
     /// Serialize the JSONB Value into a byte stream.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the encoded length of a string, number or container exceeds 256 MiB,
+    /// or a container has more elements than its header can hold, such values can't
+    /// be stored in `JSONB`. Use [`Value::try_write_to_vec`] to get an error instead.
     pub fn write_to_vec(&self, buf: &mut Vec<u8>) {
-        let mut encoder = Encoder::new(buf);
-        encoder.encode(self);
+        if let Err(err) = encode_to_vec(self, buf) {
+            panic!("failed to encode JSONB value: {}", err);
+        }
     }
 
     /// Serialize the JSONB Value into a byte stream.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is too large, see [`Value::write_to_vec`].
+    /// Use [`Value::try_to_vec`] to get an error instead.
     pub fn to_vec(&self) -> Vec<u8> {
         let mut buf = Vec::new();
         self.write_to_vec(&mut buf);
         buf
     }
 
+    /// Serialize the JSONB Value into a byte stream, returns `Error::ValueTooLarge`
+    /// if the encoded length of a string, number or container exceeds 256 MiB,
+    /// or a container has more elements than its header can hold.
+    /// Nothing is written into the buffer on error.
+    pub fn try_write_to_vec(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+        encode_to_vec(self, buf)
+    }
+
+    /// Serialize the JSONB Value into a byte stream, returns `Error::ValueTooLarge`
+    /// if the encoded length of a string, number or container exceeds 256 MiB,
+    /// or a container has more elements than its header can hold.
+    pub fn try_to_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        self.try_write_to_vec(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Serialize the JSONB Value into a byte stream, starting with the version header.
+    /// Large containers store offsets in their JEntries for random access,
+    /// which can't be read by the releases before the version header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is too large, see [`Value::write_to_vec`].
+    /// Use [`Encoder::encode_with_version`](crate::Encoder::encode_with_version)
+    /// to get an error instead.
+    pub fn write_to_vec_with_version(&self, buf: &mut Vec<u8>) {
+        if let Err(err) = encode_to_vec_with_version(self, buf) {
+            panic!("failed to encode JSONB value: {}", err);
+        }
+    }
+
+    /// Serialize the JSONB Value into a byte stream, starting with the version header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is too large, see [`Value::write_to_vec`].
+    pub fn to_vec_with_version(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.write_to_vec_with_version(&mut buf);
+        buf
+    }
+
+    /// Convert the Value to pretty `JSON` text,
+    /// indented the same as [`to_pretty_string`](crate::to_pretty_string) of the `JSONB` bytes.
+    pub fn to_pretty_string(&self) -> String {
+        let mut json = String::new();
+        self.write_pretty_string(0, &mut json);
+        json
+    }
+
+    fn write_pretty_string(&self, indent: usize, json: &mut String) {
+        let inner_indent = indent + 2;
+        match self {
+            Value::Array(vs) => {
+                json.push_str("[\n");
+                for (i, v) in vs.iter().enumerate() {
+                    if i > 0 {
+                        json.push_str(",\n");
+                    }
+                    json.push_str(&" ".repeat(inner_indent));
+                    v.write_pretty_string(inner_indent, json);
+                }
+                json.push('\n');
+                json.push_str(&" ".repeat(indent));
+                json.push(']');
+            }
+            Value::Object(vs) => {
+                json.push_str("{\n");
+                for (i, (k, v)) in vs.iter().enumerate() {
+                    if i > 0 {
+                        json.push_str(",\n");
+                    }
+                    json.push_str(&" ".repeat(inner_indent));
+                    // writing to a `String` never fails
+                    let _ = escape_string(k, json);
+                    json.push_str(": ");
+                    v.write_pretty_string(inner_indent, json);
+                }
+                json.push('\n');
+                json.push_str(&" ".repeat(indent));
+                json.push('}');
+            }
+            _ => json.push_str(&self.to_string()),
+        }
+    }
+
     pub fn get_by_name_ignore_case(&self, name: &str) -> Option<&Value<'a>> {
         match self {
             Value::Object(obj) => match obj.get(name) {
@@ -261,4 +495,20 @@ impl<'a> Value<'a> {
     pub fn eq_variant(&self, other: &Value) -> bool {
         discriminant(self) == discriminant(other)
     }
+
+    // Values of different levels are ordered by the level.
+    fn compare_level(&self) -> u8 {
+        match self {
+            Value::Null => NULL_LEVEL,
+            Value::Array(_) => ARRAY_LEVEL,
+            Value::Object(_) => OBJECT_LEVEL,
+            Value::String(_) => STRING_LEVEL,
+            Value::Number(_) => NUMBER_LEVEL,
+            Value::Bool(true) => TRUE_LEVEL,
+            Value::Bool(false) => FALSE_LEVEL,
+            Value::Binary(_) | Value::Date(_) | Value::TimestampTz(_) | Value::Uuid(_) => {
+                EXTENSION_LEVEL
+            }
+        }
+    }
 }
@@ -14,7 +14,10 @@
 
 use std::borrow::Cow;
 
-use jsonb::{from_slice, Object, Value};
+use jsonb::{
+    from_slice, from_slice_checked, from_slice_with_max_depth, parse_lazy_value, parse_value, Date,
+    Error, Object, TimestampTz, Uuid, Value,
+};
 
 #[test]
 fn test_decode_null() {
@@ -185,3 +188,129 @@ fn test_decode_corrupted() {
     let value = from_slice(corrupted.as_slice());
     assert!(value.is_err());
 }
+
+#[test]
+fn test_decode_decimal() {
+    let tests = vec![
+        (
+            b"\x20\0\0\0\x20\0\0\x0A\x70\x02\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFB".to_vec(),
+            "-0.05",
+        ),
+        (
+            b"\x20\0\0\0\x20\0\0\x12\x70\x03\0\0\0\0\0\0\0\0\x80\0\0\0\0\0\0\0".to_vec(),
+            "9223372036854775.808",
+        ),
+    ];
+    for (s, v) in tests {
+        let value = from_slice(s.as_slice()).unwrap();
+        assert!(value.is_number());
+        assert_eq!(value.to_string(), v);
+    }
+
+    // invalid scale
+    let s = b"\x20\0\0\0\x20\0\0\x0A\x70\x27\0\0\0\0\0\0\0\x01";
+    assert!(from_slice(s).is_err());
+}
+
+#[test]
+fn test_decode_int128() {
+    let s = b"\x20\0\0\0\x20\0\0\x11\x40\x80\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+    let value = from_slice(s).unwrap();
+    assert_eq!(value.as_i128(), Some(i128::MIN));
+    assert_eq!(value.as_i64(), None);
+
+    let s = b"\x20\0\0\0\x20\0\0\x11\x50\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF";
+    let value = from_slice(s).unwrap();
+    assert_eq!(value.as_u128(), Some(u128::MAX));
+    assert_eq!(value.as_i128(), None);
+}
+
+#[test]
+fn test_decode_extension() {
+    let s = b"\x20\0\0\0\x60\0\0\x03\x00\x01\xAB";
+    let value = from_slice(s).unwrap();
+    assert_eq!(value.as_binary(), Some(&b"\x01\xAB"[..]));
+    assert_eq!(value.to_string(), r#""01ab""#);
+
+    let s = b"\x20\0\0\0\x60\0\0\x05\x10\0\0\x4D\x29";
+    let value = from_slice(s).unwrap();
+    assert_eq!(value.as_date(), Date::from_ymd(2024, 1, 31));
+    assert_eq!(value.to_string(), r#""2024-01-31""#);
+
+    let s = b"\x20\0\0\0\x60\0\0\x0D\x20\0\x06\x10\x3D\x1B\x44\x86\x08\0\0\x70\x80";
+    let value = from_slice(s).unwrap();
+    assert_eq!(
+        value.as_timestamp_tz(),
+        Some(TimestampTz::new(1706704496789000, 28800))
+    );
+    assert_eq!(value.to_string(), r#""2024-01-31T20:34:56.789+08:00""#);
+
+    let s = b"\x20\0\0\0\x60\0\0\x11\x30\x55\x0E\x84\x00\xE2\x9B\x41\xD4\xA7\x16\x44\x66\x55\x44\x00\x00";
+    let value = from_slice(s).unwrap();
+    assert_eq!(
+        value.as_uuid(),
+        Some(Uuid {
+            value: [
+                0x55, 0x0E, 0x84, 0x00, 0xE2, 0x9B, 0x41, 0xD4, 0xA7, 0x16, 0x44, 0x66, 0x55, 0x44,
+                0x00, 0x00
+            ]
+        })
+    );
+    assert_eq!(
+        value.to_string(),
+        r#""550e8400-e29b-41d4-a716-446655440000""#
+    );
+
+    // invalid extension type and length
+    assert!(from_slice(b"\x20\0\0\0\x60\0\0\x01\x70").is_err());
+    assert!(from_slice(b"\x20\0\0\0\x60\0\0\x03\x10\0\0").is_err());
+    assert!(from_slice(b"\x20\0\0\0\x60\0\0\0").is_err());
+}
+
+#[test]
+fn test_decode_max_depth() {
+    let nested = |depth: usize| {
+        let mut value = Value::Null;
+        for _ in 0..depth {
+            value = Value::Array(vec![value]);
+        }
+        value.to_vec()
+    };
+    assert!(from_slice(&nested(128)).is_ok());
+    assert_eq!(from_slice(&nested(129)), Err(Error::RecursionLimitExceeded));
+    assert!(from_slice_with_max_depth(&nested(200), 200).is_ok());
+    assert_eq!(
+        from_slice_with_max_depth(&nested(3), 2),
+        Err(Error::RecursionLimitExceeded)
+    );
+    // `JSON` text is parsed with the same limit
+    assert!(from_slice_with_max_depth(b"[[1]]", 2).is_ok());
+    assert!(from_slice_with_max_depth(b"[[[1]]]", 2).is_err());
+}
+
+#[test]
+fn test_decode_checked() {
+    let value = parse_value(r#"{"测试":["a",1,{"b":"é"}]}"#.as_bytes()).unwrap();
+    let buf = value.to_vec();
+    assert_eq!(from_slice_checked(&buf), Ok(value.clone()));
+    assert_eq!(
+        from_slice_checked(br#"["a","b"]"#),
+        from_slice(br#"["a","b"]"#)
+    );
+
+    // invalid UTF-8 string
+    let s = b"\x20\0\0\0\x10\0\0\x03\x61\xFF\x64";
+    assert_eq!(from_slice_checked(s), Err(Error::InvalidUtf8));
+    // invalid UTF-8 key
+    let s = b"\x40\0\0\x01\x10\0\0\x02\x20\0\0\x01\xC3\x28\x00";
+    assert_eq!(from_slice_checked(s), Err(Error::InvalidUtf8));
+    // truncated multi-byte character
+    let s = b"\x80\0\0\x01\x10\0\0\x01\xE6";
+    assert_eq!(from_slice_checked(s), Err(Error::InvalidUtf8));
+
+    // lazy values may hold untrusted bytes and are always checked
+    let lazy_value = parse_lazy_value(s).unwrap();
+    assert_eq!(lazy_value.to_value(), Err(Error::InvalidUtf8));
+    let lazy_value = parse_lazy_value(&buf).unwrap();
+    assert_eq!(lazy_value.to_value().unwrap().as_ref(), &value);
+}
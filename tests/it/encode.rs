@@ -14,7 +14,10 @@
 
 use std::borrow::Cow;
 
-use jsonb::{parse_lazy_value, Number, Object, Value};
+use jsonb::{
+    parse_lazy_value, Date, Decimal, Encoder, Error, Number, Object, TimestampTz, Uuid, Value,
+    JSONB_VERSION,
+};
 
 #[test]
 fn test_encode_null() {
@@ -142,7 +145,7 @@ fn test_encode_array() {
     let lazy_value = parse_lazy_value(raw).unwrap();
     assert_eq!(lazy_value.array_length(), Some(2));
     assert_eq!(
-        lazy_value.to_value().as_ref(),
+        lazy_value.to_value().unwrap().as_ref(),
         &Value::Array(vec![Value::Bool(false), Value::Bool(true)])
     );
 }
@@ -156,3 +159,118 @@ fn test_encode_object() {
         b"\x40\0\0\x01\x10\0\0\x03\x10\0\0\x03\x61\x73\x64\x61\x64\x66"
     );
 }
+
+#[test]
+fn test_encode_decimal() {
+    assert_eq!(
+        &Value::Number(Number::Decimal(Decimal::new(-5, 2).unwrap())).to_vec(),
+        b"\x20\0\0\0\x20\0\0\x0A\x70\x02\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFB"
+    );
+    assert_eq!(
+        &Value::Number(Number::Decimal(
+            Decimal::new(i64::MAX as i128 + 1, 3).unwrap()
+        ))
+        .to_vec(),
+        b"\x20\0\0\0\x20\0\0\x12\x70\x03\0\0\0\0\0\0\0\0\x80\0\0\0\0\0\0\0"
+    );
+}
+
+#[test]
+fn test_encode_int128() {
+    assert_eq!(
+        &Value::Number(Number::Int128(-100)).to_vec(),
+        b"\x20\0\0\0\x20\0\0\x02\x40\x9C"
+    );
+    assert_eq!(
+        &Value::Number(Number::Int128(i128::MIN)).to_vec(),
+        b"\x20\0\0\0\x20\0\0\x11\x40\x80\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0"
+    );
+    assert_eq!(
+        &Value::Number(Number::UInt128(u128::MAX)).to_vec(),
+        b"\x20\0\0\0\x20\0\0\x11\x50\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF"
+    );
+}
+
+#[test]
+fn test_encode_extension() {
+    assert_eq!(
+        &Value::Binary(Cow::Borrowed(b"\x01\xAB")).to_vec(),
+        b"\x20\0\0\0\x60\0\0\x03\x00\x01\xAB"
+    );
+    assert_eq!(
+        &Value::Date(Date::from_ymd(2024, 1, 31).unwrap()).to_vec(),
+        b"\x20\0\0\0\x60\0\0\x05\x10\0\0\x4D\x29"
+    );
+    assert_eq!(
+        &Value::TimestampTz(TimestampTz::new(1706704496789000, 28800)).to_vec(),
+        b"\x20\0\0\0\x60\0\0\x0D\x20\0\x06\x10\x3D\x1B\x44\x86\x08\0\0\x70\x80"
+    );
+    assert_eq!(
+        &Value::Uuid(Uuid { value: [0xAB; 16] }).to_vec(),
+        b"\x20\0\0\0\x60\0\0\x11\x30\xAB\xAB\xAB\xAB\xAB\xAB\xAB\xAB\xAB\xAB\xAB\xAB\xAB\xAB\xAB\xAB"
+    );
+    assert_eq!(
+        &Value::Array(vec![Value::Date(Date { value: -1 }), Value::Null]).to_vec(),
+        b"\x80\0\0\x02\x60\0\0\x05\0\0\0\0\x10\xFF\xFF\xFF\xFF"
+    );
+}
+
+#[test]
+fn test_encode_too_large() {
+    // the length of a value is stored in the 28 bits of the JEntry
+    let max_len = 0x0FFFFFFF;
+    let s = "a".repeat(max_len + 1);
+    let value = Value::String(Cow::Borrowed(&s));
+    assert_eq!(value.try_to_vec(), Err(Error::ValueTooLarge));
+    let mut buf = b"prefix".to_vec();
+    assert_eq!(value.try_write_to_vec(&mut buf), Err(Error::ValueTooLarge));
+    assert_eq!(buf, b"prefix");
+    // the infallible encoding panics instead of truncating the length
+    assert!(std::panic::catch_unwind(|| value.to_vec()).is_err());
+    assert!(std::panic::catch_unwind(|| value.to_vec_with_version()).is_err());
+
+    // the values fit, but the nested array doesn't
+    let half = &s[..max_len / 2 + 1];
+    let value = Value::Array(vec![Value::Array(vec![
+        Value::String(Cow::Borrowed(half)),
+        Value::String(Cow::Borrowed(half)),
+    ])]);
+    assert_eq!(value.try_to_vec(), Err(Error::ValueTooLarge));
+
+    let value = Value::Array(vec![Value::String(Cow::Borrowed("a")); 40]);
+    assert_eq!(value.try_to_vec(), Ok(value.to_vec()));
+}
+
+#[test]
+fn test_encoder_writer() {
+    let values = vec![
+        Value::Null,
+        Value::String(Cow::from("asd")),
+        Value::Array(vec![Value::Bool(true), Value::Number(Number::Int64(-10))]),
+    ];
+    let mut encoder = Encoder::new(Vec::new());
+    let mut expected = Vec::new();
+    for value in &values {
+        let len = encoder.encode(value).unwrap();
+        let buf = value.to_vec();
+        assert_eq!(len, buf.len());
+        expected.extend_from_slice(&buf);
+    }
+    let len = encoder.encode_with_version(&values[1]).unwrap();
+    assert_eq!(len, 4 + values[1].to_vec().len());
+    expected.extend_from_slice(&values[1].to_vec_with_version());
+    assert_eq!(
+        &expected[expected.len() - len..][..4],
+        [0xFF, b'J', b'B', JSONB_VERSION]
+    );
+    assert_eq!(encoder.get_ref(), &expected);
+    assert_eq!(encoder.into_inner(), expected);
+
+    let mut buf = [0; 10];
+    let mut encoder = Encoder::new(&mut buf[..]);
+    assert_eq!(encoder.encode(&values[0]), Ok(8));
+    assert_eq!(
+        encoder.encode(&values[0]),
+        Err(Error::Io(std::io::ErrorKind::WriteZero))
+    );
+}
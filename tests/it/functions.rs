@@ -14,22 +14,34 @@
 
 use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::hash::Hash;
+use std::hash::Hasher;
 
 use jsonb::{
     array_distinct, array_except, array_insert, array_intersection, array_length, array_overlap,
-    array_values, as_bool, as_null, as_number, as_str, build_array, build_object, compare, concat,
-    contains, convert_to_comparable, delete_by_index, delete_by_keypath, delete_by_name,
-    exists_all_keys, exists_any_keys, from_slice, get_by_index, get_by_keypath, get_by_name,
-    get_by_path, get_by_path_array, is_array, is_object, keypath::parse_key_paths, object_delete,
-    object_each, object_insert, object_keys, object_pick, parse_value, path_exists, path_match,
-    strip_nulls, to_bool, to_f64, to_i64, to_pretty_string, to_serde_json, to_serde_json_object,
-    to_str, to_string, to_u64, traverse_check_string, type_of, Error, Number, Object, Value,
+    array_values, as_bool, as_i128, as_i64, as_null, as_number, as_str, as_u128, build_array,
+    build_object, canonicalize, compare, compare_with_options, concat, contains,
+    convert_from_comparable, convert_from_comparable_with_options, convert_to_comparable,
+    convert_to_comparable_with_options, delete_by_index, delete_by_keypath, delete_by_name,
+    detect_format, exists_all_keys, exists_any_keys, from_slice, get_by_index, get_by_keypath,
+    get_by_name, get_by_path, get_by_path_array, hash_value, is_array, is_i128, is_null, is_object,
+    is_u128, keypath::parse_key_paths, object_delete, object_each, object_insert, object_keys,
+    object_pick, parse_value, parse_value_with_options, path_exists, path_match, rand_value,
+    strip_nulls, to_bool, to_canonical_string, to_f64, to_i64, to_pretty_string, to_serde_json,
+    to_serde_json_object, to_str, to_string, to_string_with_options, to_u64, traverse_check_string,
+    type_of, Collation, CompareOptions, Date, Decimal, Error, FloatFormat, Format, Indent,
+    JsonFormatOptions, NonFinitePolicy, NullOrder, Number, Object, ParseOptions, RawNumber,
+    TimestampTz, TypeOrder, Uuid, Value, JSONB_VERSION,
 };
 
 use jsonb::jsonpath::parse_json_path;
 use nom::AsBytes;
+use rand::rngs::SmallRng;
+use rand::Rng;
+use rand::SeedableRng;
 
 #[test]
 fn test_build_array() {
@@ -529,6 +541,627 @@ fn test_array_values() {
     }
 }
 
+#[test]
+fn test_compare_decimal() {
+    let opts = ParseOptions::new().with_decimal(true);
+    let sources = vec![
+        ("0.1", "0.1", Ordering::Equal),
+        ("0.10", "0.1", Ordering::Equal),
+        ("1.5e1", "15", Ordering::Equal),
+        ("0.3", "0.30000000000000004", Ordering::Less),
+        ("-0.3", "-0.30000000000000004", Ordering::Greater),
+        (
+            "12345678901234567890.123",
+            "12345678901234567890.124",
+            Ordering::Less,
+        ),
+        (
+            "12345678901234567890.123",
+            "12345678901234567890",
+            Ordering::Greater,
+        ),
+        (
+            "-12345678901234567890.123",
+            "-12345678901234567890",
+            Ordering::Less,
+        ),
+        ("9007199254740993", "9007199254740992", Ordering::Greater),
+        ("1e-40", "0", Ordering::Greater),
+        ("-1e-40", "0", Ordering::Less),
+        ("1e100", "123456789012345678901234567890", Ordering::Greater),
+        ("[1.10, 2]", "[1.1, 2.5]", Ordering::Less),
+    ];
+    for (l, r, expect) in sources {
+        let lbuf = parse_value_with_options(l.as_bytes(), &opts)
+            .unwrap()
+            .to_vec();
+        let rbuf = parse_value_with_options(r.as_bytes(), &opts)
+            .unwrap()
+            .to_vec();
+        assert_eq!(compare(&lbuf, &rbuf).unwrap(), expect, "{l} {r}");
+
+        let mut lkey = Vec::new();
+        let mut rkey = Vec::new();
+        convert_to_comparable(&lbuf, &mut lkey);
+        convert_to_comparable(&rbuf, &mut rkey);
+        assert_comparable_order(&lkey, &rkey, expect);
+    }
+}
+
+// Values that are equal may have different types at the end of the comparable vectors,
+// which are not compared.
+#[track_caller]
+fn assert_comparable_order(lkey: &[u8], rkey: &[u8], expect: Ordering) {
+    fn strip_types(key: &[u8]) -> &[u8] {
+        let (key, len) = key.split_at(key.len() - 4);
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        &key[..key.len() - len]
+    }
+    assert_eq!(strip_types(lkey).cmp(strip_types(rkey)), expect);
+    if expect != Ordering::Equal {
+        assert_eq!(lkey.cmp(rkey), expect);
+    }
+}
+
+#[test]
+fn test_compare_value_order() {
+    // random values with a small domain, so that many of them are equal
+    // with different representations
+    fn rand_value(rng: &mut SmallRng, depth: usize) -> Value<'static> {
+        let max = if depth > 2 { 6 } else { 8 };
+        match rng.gen_range(0..=max) {
+            0 => Value::Null,
+            1 => Value::Bool(rng.gen_bool(0.5)),
+            2 => {
+                let s = ["", "a", "b", "ab", "\u{e9}"][rng.gen_range(0..5)];
+                Value::String(Cow::from(s))
+            }
+            3 | 4 => Value::Number(rand_number(rng)),
+            5 => Value::Date(Date {
+                value: rng.gen_range(-1..=1),
+            }),
+            6 => match rng.gen_range(0..3) {
+                0 => Value::TimestampTz(TimestampTz::new(
+                    rng.gen_range(-1..=1),
+                    rng.gen_range(-1..=1) * 3600,
+                )),
+                1 => Value::Binary(Cow::from(vec![0; rng.gen_range(0..=2)])),
+                _ => Value::Uuid(Uuid {
+                    value: [rng.gen_range(0..=1); 16],
+                }),
+            },
+            7 => {
+                let len = rng.gen_range(0..=3);
+                Value::Array((0..len).map(|_| rand_value(rng, depth + 1)).collect())
+            }
+            _ => {
+                let len = rng.gen_range(0..=3);
+                let mut obj = Object::new();
+                for _ in 0..len {
+                    let key = ["a", "b", "c"][rng.gen_range(0..3)].to_string();
+                    obj.insert(key, rand_value(rng, depth + 1));
+                }
+                Value::Object(obj)
+            }
+        }
+    }
+
+    fn rand_number(rng: &mut SmallRng) -> Number {
+        let v: i64 = rng.gen_range(-2..=2);
+        match rng.gen_range(0..10) {
+            0 => Number::Int64(v),
+            1 => Number::UInt64(v.unsigned_abs()),
+            2 => Number::Float64(v as f64),
+            3 => Number::Float64(v as f64 + 0.5),
+            4 => Number::Decimal(Decimal::new(v as i128 * 10 + 5, 1).unwrap()),
+            5 => Number::Decimal(Decimal::new(v as i128 * 100, 2).unwrap()),
+            6 => Number::Raw(RawNumber::new(format!("{}.0", v)).unwrap()),
+            7 => Number::Int128(v as i128),
+            8 => Number::UInt128(v.unsigned_abs() as u128),
+            _ => {
+                let v = [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 0.1][rng.gen_range(0..4)];
+                Number::Float64(v)
+            }
+        }
+    }
+
+    let mut rng = SmallRng::seed_from_u64(20241018);
+    let mut equals = 0;
+    for _ in 0..5000 {
+        let a = rand_value(&mut rng, 0);
+        let b = rand_value(&mut rng, 0);
+        let (abuf, bbuf) = (a.to_vec(), b.to_vec());
+        let order = compare(&abuf, &bbuf).unwrap();
+        assert_eq!(a.cmp(&b), order, "{:?} {:?}", a, b);
+        assert_eq!(b.cmp(&a), order.reverse(), "{:?} {:?}", a, b);
+        assert_eq!(a == b, order == Ordering::Equal);
+        if order == Ordering::Equal {
+            equals += 1;
+        }
+    }
+    assert!(equals > 0);
+
+    // sorting decoded values matches sorting encoded ones
+    let mut values: Vec<Value> = (0..200).map(|_| rand_value(&mut rng, 0)).collect();
+    let mut bufs: Vec<Vec<u8>> = values.iter().map(|v| v.to_vec()).collect();
+    values.sort();
+    bufs.sort_by(|l, r| compare(l, r).unwrap());
+    for (value, buf) in values.iter().zip(bufs.iter()) {
+        assert_eq!(compare(&value.to_vec(), buf).unwrap(), Ordering::Equal);
+    }
+}
+
+#[test]
+fn test_compare_with_options() {
+    fn sort_by_options(sources: &[&str], opts: &CompareOptions) -> Vec<String> {
+        let mut values: Vec<Vec<u8>> = sources
+            .iter()
+            .map(|s| parse_value(s.as_bytes()).unwrap().to_vec())
+            .collect();
+        values.sort_by(|l, r| compare_with_options(l, r, opts).unwrap());
+        values.iter().map(|v| to_string(v)).collect()
+    }
+
+    let sources = ["[]", "true", "null", r#""a""#, "{}", "1", "false"];
+    let cases = vec![
+        (
+            CompareOptions::default(),
+            ["false", "true", "1", r#""a""#, "{}", "[]", "null"],
+        ),
+        (
+            CompareOptions::new().with_nulls(NullOrder::First),
+            ["null", "false", "true", "1", r#""a""#, "{}", "[]"],
+        ),
+        (
+            CompareOptions::postgres(),
+            ["null", r#""a""#, "1", "false", "true", "[]", "{}"],
+        ),
+        (
+            CompareOptions::snowflake(),
+            ["1", "false", "true", r#""a""#, "{}", "[]", "null"],
+        ),
+    ];
+    for (opts, expected) in cases {
+        assert_eq!(sort_by_options(&sources, &opts), expected, "{:?}", opts);
+    }
+
+    let sources = [r#""b""#, r#""B""#, r#""a""#, r#""\u00c1""#, r#""A""#];
+    let cases = vec![
+        (
+            Collation::Binary,
+            ["\"A\"", "\"B\"", "\"a\"", "\"b\"", "\"\u{c1}\""],
+        ),
+        (
+            Collation::CaseInsensitive,
+            ["\"a\"", "\"A\"", "\"b\"", "\"B\"", "\"\u{c1}\""],
+        ),
+        (
+            Collation::Unicode,
+            ["\"A\"", "\"a\"", "\"\u{c1}\"", "\"B\"", "\"b\""],
+        ),
+    ];
+    for (collation, expected) in cases {
+        let opts = CompareOptions::new().with_collation(collation);
+        assert_eq!(sort_by_options(&sources, &opts), expected, "{:?}", opts);
+    }
+
+    let sources = vec![
+        (
+            "\"a\"",
+            "\"A\"",
+            [Ordering::Greater, Ordering::Equal, Ordering::Greater],
+        ),
+        (
+            r#"{"k":"X"}"#,
+            r#"{"K":"x"}"#,
+            [Ordering::Greater, Ordering::Equal, Ordering::Greater],
+        ),
+        (
+            r#""e""#,
+            r#""\u00e9""#,
+            [Ordering::Less, Ordering::Less, Ordering::Less],
+        ),
+        (
+            r#""\u00e9""#,
+            r#""f""#,
+            [Ordering::Greater, Ordering::Greater, Ordering::Less],
+        ),
+        (
+            r#""fi""#,
+            r#""\ufb01""#,
+            [Ordering::Less, Ordering::Less, Ordering::Less],
+        ),
+        (
+            r#""\ufb01""#,
+            r#""fj""#,
+            [Ordering::Greater, Ordering::Greater, Ordering::Less],
+        ),
+    ];
+    let collations = [
+        Collation::Binary,
+        Collation::CaseInsensitive,
+        Collation::Unicode,
+    ];
+    for (l, r, expected) in sources {
+        let lbuf = parse_value(l.as_bytes()).unwrap().to_vec();
+        let rbuf = parse_value(r.as_bytes()).unwrap().to_vec();
+        for (collation, expected) in collations.iter().zip(expected) {
+            let opts = CompareOptions::new().with_collation(*collation);
+            assert_eq!(
+                compare_with_options(&lbuf, &rbuf, &opts).unwrap(),
+                expected,
+                "{} {} {:?}",
+                l,
+                r,
+                collation
+            );
+        }
+    }
+
+    // the comparable bytes are in the same order for all the options
+    let sources = vec![
+        "null",
+        "true",
+        "false",
+        "0",
+        "-1.5",
+        "1.0",
+        "1",
+        r#""""#,
+        r#""a""#,
+        r#""A""#,
+        r#""ab""#,
+        r#""\u00e1""#,
+        r#""\u0000""#,
+        "[]",
+        "[null]",
+        "[1,2]",
+        r#"[1,"a"]"#,
+        r#"[1,"A"]"#,
+        "[[1],{}]",
+        "{}",
+        r#"{"a":1}"#,
+        r#"{"A":1.0}"#,
+        r#"{"a":null,"b":[]}"#,
+        r#"{"a":{"b":"c"}}"#,
+        r#"{"a":{"b":"C"}}"#,
+    ];
+    let values: Vec<Vec<u8>> = sources
+        .iter()
+        .map(|s| parse_value(s.as_bytes()).unwrap().to_vec())
+        .collect();
+    for nulls in [NullOrder::Last, NullOrder::First] {
+        for collation in collations {
+            for type_order in [TypeOrder::Jsonb, TypeOrder::Postgres, TypeOrder::Snowflake] {
+                let opts = CompareOptions::new()
+                    .with_nulls(nulls)
+                    .with_collation(collation)
+                    .with_type_order(type_order);
+                let keys: Vec<Vec<u8>> = values
+                    .iter()
+                    .map(|v| {
+                        let mut buf = Vec::new();
+                        convert_to_comparable_with_options(v, &mut buf, &opts);
+                        buf
+                    })
+                    .collect();
+                for (i, l) in values.iter().enumerate() {
+                    for (j, r) in values.iter().enumerate() {
+                        let order = compare_with_options(l, r, &opts).unwrap();
+                        let reverse = compare_with_options(r, l, &opts).unwrap();
+                        assert_eq!(order, reverse.reverse());
+                        assert_comparable_order(&keys[i], &keys[j], order);
+                    }
+                }
+            }
+        }
+    }
+
+    // the default options are the same as `compare` and `convert_to_comparable`
+    for l in values.iter() {
+        let mut buf = Vec::new();
+        let mut default_buf = Vec::new();
+        convert_to_comparable(l, &mut buf);
+        convert_to_comparable_with_options(l, &mut default_buf, &CompareOptions::default());
+        assert_eq!(buf, default_buf);
+        for r in values.iter() {
+            assert_eq!(
+                compare(l, r).unwrap(),
+                compare_with_options(l, r, &CompareOptions::default()).unwrap()
+            );
+        }
+    }
+
+    // strings that are not valid UTF-8 are corrupted for the collations using characters
+    let invalid = b"\x20\0\0\0\x10\0\0\x02\xC3\x28";
+    let valid = parse_value(br#""a""#).unwrap().to_vec();
+    for collation in collations {
+        let opts = CompareOptions::new().with_collation(collation);
+        let order = compare_with_options(invalid, &valid, &opts);
+        let mut key = Vec::new();
+        convert_to_comparable_with_options(invalid, &mut key, &opts);
+        let mut buf = Vec::new();
+        let restored = convert_from_comparable_with_options(&key, &mut buf, &opts);
+        if collation == Collation::Binary {
+            assert_eq!(order, Ok(Ordering::Greater));
+            assert_eq!(restored, Err(Error::InvalidUtf8));
+        } else {
+            assert_eq!(order, Err(Error::InvalidJsonb));
+            assert!(restored.is_err());
+        }
+    }
+}
+
+#[test]
+fn test_convert_from_comparable() {
+    let mut key = Vec::new();
+    let mut buf = Vec::new();
+    for _ in 0..1000 {
+        let value = rand_value().to_vec();
+        key.clear();
+        buf.clear();
+        convert_to_comparable(&value, &mut key);
+        convert_from_comparable(&key, &mut buf).unwrap();
+        assert_eq!(buf, value);
+    }
+
+    let decimal =
+        |value, scale| Value::Number(Number::Decimal(Decimal::new(value, scale).unwrap()));
+    let values = vec![
+        Value::Null,
+        Value::Bool(true),
+        Value::Bool(false),
+        Value::Number(Number::Int64(-5)),
+        Value::Number(Number::Int64(i64::MIN)),
+        Value::Number(Number::UInt64(u64::MAX)),
+        Value::Number(Number::Int128(i128::MIN)),
+        Value::Number(Number::UInt128(u128::MAX)),
+        Value::Number(Number::Float64(0.0)),
+        Value::Number(Number::Float64(-0.0)),
+        Value::Number(Number::Float64(3.0)),
+        Value::Number(Number::Float64(-123.456)),
+        Value::Number(Number::Float64(5e-324)),
+        Value::Number(Number::Float64(f64::MAX)),
+        Value::Number(Number::Float64(f64::NAN)),
+        Value::Number(Number::Float64(f64::INFINITY)),
+        Value::Number(Number::Float64(f64::NEG_INFINITY)),
+        decimal(-12345, 3),
+        decimal(500, 2),
+        decimal(0, 2),
+        decimal(i128::MAX, 10),
+        Value::Number(Number::Raw(RawNumber::new("-1.50e3").unwrap())),
+        Value::String(Cow::from("")),
+        Value::String(Cow::from("A\0b\u{c9}")),
+        Value::Binary(Cow::from(vec![0, 1, 0, 0xFF])),
+        Value::Date(Date { value: -1 }),
+        Value::TimestampTz(TimestampTz::new(-1_000_000, -3600)),
+        Value::Uuid(Uuid { value: [7; 16] }),
+        Value::Array(vec![]),
+        Value::Object(Object::new()),
+        parse_value(r#"[1,[2.0,[]],{"a":{},"A":[null,"X"]},-3]"#.as_bytes()).unwrap(),
+        parse_value_with_options(
+            r#"{"k":[1.10,{"\u00e9":-0.5e-3}],"K":"Ab"}"#.as_bytes(),
+            &ParseOptions::new().with_decimal(true),
+        )
+        .unwrap(),
+    ];
+    let collations = [
+        Collation::Binary,
+        Collation::CaseInsensitive,
+        Collation::Unicode,
+    ];
+    for nulls in [NullOrder::Last, NullOrder::First] {
+        for collation in collations {
+            for type_order in [TypeOrder::Jsonb, TypeOrder::Postgres, TypeOrder::Snowflake] {
+                let opts = CompareOptions::new()
+                    .with_nulls(nulls)
+                    .with_collation(collation)
+                    .with_type_order(type_order);
+                for value in values.iter() {
+                    let value = value.to_vec();
+                    key.clear();
+                    buf.clear();
+                    convert_to_comparable_with_options(&value, &mut key, &opts);
+                    convert_from_comparable_with_options(&key, &mut buf, &opts).unwrap();
+                    assert_eq!(buf, value, "{} {:?}", to_string(&value), opts);
+                }
+            }
+        }
+    }
+
+    // `JSON` text is restored as `JSONB`, invalid text is kept
+    let sources = vec![
+        (
+            r#"[1, 2.5, "a"]"#,
+            parse_value(br#"[1,2.5,"a"]"#).unwrap().to_vec(),
+        ),
+        ("[1,", b"[1,".to_vec()),
+    ];
+    for (source, expected) in sources {
+        key.clear();
+        buf.clear();
+        convert_to_comparable(source.as_bytes(), &mut key);
+        convert_from_comparable(&key, &mut buf).unwrap();
+        assert_eq!(buf, expected);
+    }
+
+    // corrupted vectors fail instead of panic
+    key.clear();
+    convert_to_comparable(&values[30].to_vec(), &mut key);
+    for len in 0..key.len() {
+        let _ = convert_from_comparable(&key[..len], &mut buf);
+        let mut corrupted = key.clone();
+        corrupted[len] ^= 0x5A;
+        let _ = convert_from_comparable(&corrupted, &mut buf);
+    }
+    assert!(convert_from_comparable(&[], &mut buf).is_err());
+    assert!(convert_from_comparable(&key[..key.len() - 1], &mut buf).is_err());
+
+    // the vectors start with the format version, the older ones start with a zero depth
+    assert_eq!(key[0], 1);
+    let mut old_key = Vec::new();
+    convert_to_comparable(b"[1,", &mut old_key);
+    assert_eq!(old_key[..3], [1, 0, 0]);
+    assert_eq!(
+        convert_from_comparable(&old_key[1..], &mut buf),
+        Err(Error::InvalidJsonbVersion)
+    );
+    assert_eq!(
+        convert_from_comparable(&[0, 7], &mut buf),
+        Err(Error::InvalidJsonbVersion)
+    );
+}
+
+#[test]
+fn test_hash_value() {
+    fn hash_of(value: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        hash_value(value, &mut hasher).unwrap();
+        hasher.finish()
+    }
+    fn value_hash_of(value: &Value) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let decimal_opts = ParseOptions::new().with_decimal(true);
+    let text_opts = ParseOptions::new().with_number_text(true);
+    let sources = vec![
+        ("1", "1.0"),
+        ("-1", "-1.00"),
+        ("0", "-0.0"),
+        ("0.1", "0.10"),
+        ("1.5e1", "15"),
+        ("1e2", "100"),
+        ("18446744073709551616", "18446744073709551616.0"),
+        (r#"[1, 2.50, "a"]"#, r#"[1.0, 2.5, "a"]"#),
+        (r#"{"a":[1,{"b":1e1}]}"#, r#"{"a":[1.0,{"b":10}]}"#),
+        ("null", "null"),
+        ("[]", "[]"),
+    ];
+    for (l, r) in sources {
+        let values = vec![
+            parse_value(l.as_bytes()).unwrap(),
+            parse_value(r.as_bytes()).unwrap(),
+            parse_value_with_options(l.as_bytes(), &decimal_opts).unwrap(),
+            parse_value_with_options(r.as_bytes(), &text_opts).unwrap(),
+        ];
+        let first = values[0].to_vec();
+        let expected = hash_of(&first);
+        for value in values {
+            let buf = value.to_vec();
+            assert_eq!(
+                compare(&first, &buf).unwrap(),
+                Ordering::Equal,
+                "{} {}",
+                l,
+                r
+            );
+            assert_eq!(hash_of(&buf), expected, "{} {}", l, r);
+            assert_eq!(hash_of(&value.to_vec_with_version()), expected);
+            assert_eq!(value_hash_of(&value), expected);
+        }
+        assert_eq!(hash_of(l.as_bytes()), expected);
+        assert_eq!(hash_of(r.as_bytes()), expected);
+    }
+
+    // equal integers with different widths
+    let numbers = vec![
+        Number::Int64(7),
+        Number::UInt64(7),
+        Number::Int128(7),
+        Number::UInt128(7),
+        Number::Float64(7.0),
+    ];
+    let expected = value_hash_of(&Value::Number(Number::UInt64(7)));
+    for num in numbers {
+        let value = Value::Number(num);
+        assert_eq!(hash_of(&value.to_vec()), expected);
+        assert_eq!(value_hash_of(&value), expected);
+    }
+
+    // values of different types have different hashes
+    let sources = vec![
+        "null",
+        "true",
+        "false",
+        "0",
+        r#""""#,
+        r#""0""#,
+        "[]",
+        "{}",
+        "[0]",
+        r#"{"0":0}"#,
+    ];
+    let hashes: BTreeSet<u64> = sources.iter().map(|s| hash_of(s.as_bytes())).collect();
+    assert_eq!(hashes.len(), sources.len());
+
+    let date = Value::Date(Date { value: 19000 });
+    assert_eq!(hash_of(&date.to_vec()), value_hash_of(&date));
+    let ts1 = Value::TimestampTz(TimestampTz::new(1_000_000, 0));
+    let ts2 = Value::TimestampTz(TimestampTz::new(1_000_000, 3600));
+    assert_eq!(
+        compare(&ts1.to_vec(), &ts2.to_vec()).unwrap(),
+        Ordering::Equal
+    );
+    assert_eq!(hash_of(&ts1.to_vec()), hash_of(&ts2.to_vec()));
+    assert_eq!(value_hash_of(&ts1), value_hash_of(&ts2));
+
+    // zeros and non-finite floats
+    let decimal = |value, scale| Number::Decimal(Decimal::new(value, scale).unwrap());
+    let zero = value_hash_of(&Value::Number(Number::UInt64(0)));
+    for num in [decimal(0, 5), Number::Float64(-0.0), Number::Int128(0)] {
+        assert_eq!(value_hash_of(&Value::Number(num)), zero);
+    }
+    let non_finite: BTreeSet<u64> = [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 0.0]
+        .into_iter()
+        .map(|v| value_hash_of(&Value::Number(Number::Float64(v))))
+        .collect();
+    assert_eq!(non_finite.len(), 4);
+
+    // invalid UTF-8 strings are corrupted
+    let mut hasher = DefaultHasher::new();
+    let value = b"\x80\0\0\x01\x10\0\0\x02\xC3\x28";
+    assert_eq!(hash_value(value, &mut hasher), Err(Error::InvalidJsonb));
+}
+
+#[test]
+fn test_compare_number_text() {
+    let opts = ParseOptions::new().with_number_text(true);
+    let sources = vec![
+        ("1.10", "1.1", Ordering::Equal),
+        ("1e2", "100", Ordering::Equal),
+        ("0.1000000000000000000001", "0.1", Ordering::Greater),
+        ("[1.50, -2e-3]", "[1.5, -0.002]", Ordering::Equal),
+        (
+            "123456789012345678901234567890.5",
+            "1.2345678901234568e29",
+            Ordering::Greater,
+        ),
+    ];
+    for (l, r, expect) in sources {
+        let lbuf = parse_value_with_options(l.as_bytes(), &opts)
+            .unwrap()
+            .to_vec();
+        let rbuf = parse_value(r.as_bytes()).unwrap().to_vec();
+        assert_eq!(compare(&lbuf, &rbuf).unwrap(), expect, "{l} {r}");
+
+        let mut lkey = Vec::new();
+        let mut rkey = Vec::new();
+        convert_to_comparable(&lbuf, &mut lkey);
+        convert_to_comparable(&rbuf, &mut rkey);
+        assert_comparable_order(&lkey, &rkey, expect);
+
+        assert_eq!(to_string(&lbuf), l.replace(' ', ""));
+        let json = to_serde_json(&lbuf).unwrap();
+        let expected = to_serde_json(&rbuf).unwrap();
+        if expect == Ordering::Equal {
+            assert_eq!(json, expected);
+        }
+    }
+}
+
 #[test]
 fn test_compare() {
     let sources = vec![
@@ -737,6 +1370,80 @@ fn test_as_type() {
     }
 }
 
+#[test]
+fn test_as_int128() {
+    let sources = vec![
+        ("-1234", Some(-1234), None),
+        ("1234", Some(1234), Some(1234)),
+        ("12.5", None, None),
+        (
+            "-170141183460469231731687303715884105728",
+            Some(i128::MIN),
+            None,
+        ),
+        (
+            "340282366920938463463374607431768211455",
+            None,
+            Some(u128::MAX),
+        ),
+        ("\"1234\"", None, None),
+    ];
+    for (s, expect_i128, expect_u128) in sources {
+        assert_eq!(as_i128(s.as_bytes()), expect_i128);
+        assert_eq!(as_u128(s.as_bytes()), expect_u128);
+        assert_eq!(is_i128(s.as_bytes()), expect_i128.is_some());
+        assert_eq!(is_u128(s.as_bytes()), expect_u128.is_some());
+
+        let buf = parse_value(s.as_bytes()).unwrap().to_vec();
+        assert_eq!(as_i128(&buf), expect_i128);
+        assert_eq!(as_u128(&buf), expect_u128);
+    }
+
+    let sources = vec![
+        (
+            "18446744073709551616",
+            "18446744073709551615",
+            Ordering::Greater,
+        ),
+        (
+            "-9223372036854775809",
+            "-9223372036854775808",
+            Ordering::Less,
+        ),
+        (
+            "340282366920938463463374607431768211455",
+            "-170141183460469231731687303715884105728",
+            Ordering::Greater,
+        ),
+        (
+            "340282366920938463463374607431768211455",
+            "3.402823669209385e38",
+            Ordering::Less,
+        ),
+        (
+            "170141183460469231731687303715884105727",
+            "1.7014118346046923e38",
+            Ordering::Less,
+        ),
+        (
+            "18446744073709551616",
+            "1.8446744073709552e19",
+            Ordering::Equal,
+        ),
+    ];
+    for (l, r, expect) in sources {
+        let lbuf = parse_value(l.as_bytes()).unwrap().to_vec();
+        let rbuf = parse_value(r.as_bytes()).unwrap().to_vec();
+        assert_eq!(compare(&lbuf, &rbuf).unwrap(), expect, "{l} {r}");
+
+        let mut lkey = Vec::new();
+        let mut rkey = Vec::new();
+        convert_to_comparable(&lbuf, &mut lkey);
+        convert_to_comparable(&rbuf, &mut rkey);
+        assert_comparable_order(&lkey, &rkey, expect);
+    }
+}
+
 #[test]
 fn test_to_type() {
     let sources = vec![
@@ -889,10 +1596,15 @@ fn test_to_string() {
             r#"{"k1":"v1","k2":[1,2,3],"k3":{"a":"b"}}"#,
             r#"{"k1":"v1","k2":[1,2,3],"k3":{"a":"b"}}"#,
         ),
+        (
+            r#"{"a\"b\\c\u0001":["\t\u001F\u007F","\/"]}"#,
+            "{\"a\\\"b\\\\c\\u0001\":[\"\\t\\u001f\u{7f}\",\"/\"]}",
+        ),
     ];
     let mut buf: Vec<u8> = Vec::new();
     for (s, expect) in sources {
         let value = parse_value(s.as_bytes()).unwrap();
+        assert_eq!(value.to_string(), expect);
         value.write_to_vec(&mut buf);
         let res = to_string(&buf);
         assert_eq!(res, expect);
@@ -959,6 +1671,7 @@ fn test_to_pretty_string() {
     let mut buf: Vec<u8> = Vec::new();
     for (s, expect) in sources {
         let value = parse_value(s.as_bytes()).unwrap();
+        assert_eq!(value.to_pretty_string(), expect);
         value.write_to_vec(&mut buf);
         let res = to_pretty_string(&buf);
         assert_eq!(res, expect);
@@ -1973,3 +2686,883 @@ fn init_object<'a>(entries: Vec<(&str, Value<'a>)>) -> Value<'a> {
     }
     Value::Object(map)
 }
+
+#[test]
+fn test_extension_functions() {
+    let values = [
+        Value::Binary(Cow::Borrowed(b"\x01\xAB")),
+        Value::Date(Date::from_ymd(2024, 1, 31).unwrap()),
+        Value::TimestampTz(TimestampTz::new(1706704496789000, -18000)),
+        Value::Uuid(Uuid { value: [0; 16] }),
+    ];
+    let expects = [
+        ("binary", "\"01ab\""),
+        ("date", "\"2024-01-31\""),
+        ("timestamp_tz", "\"2024-01-31T07:34:56.789-05:00\""),
+        ("uuid", "\"00000000-0000-0000-0000-000000000000\""),
+    ];
+    for (value, (ty, expect)) in values.iter().zip(expects) {
+        let buf = value.to_vec();
+        assert_eq!(type_of(&buf).unwrap(), ty);
+        assert_eq!(to_string(&buf), expect);
+        assert_eq!(value.to_string(), expect);
+        let json: serde_json::Value = serde_json::from_str(expect).unwrap();
+        assert_eq!(to_serde_json(&buf).unwrap(), json);
+
+        let arr = Value::Array(vec![value.clone(), Value::Null]).to_vec();
+        assert_eq!(to_string(&arr), format!("[{},null]", expect));
+    }
+
+    // Null > Array > Object > Extension > String > Number > Boolean,
+    // extensions order by type first and then by value.
+    let sorted = [
+        Value::Bool(true),
+        Value::Number(Number::Int64(1)),
+        Value::String(Cow::Borrowed("a")),
+        Value::Binary(Cow::Borrowed(b"")),
+        Value::Binary(Cow::Borrowed(b"\x01")),
+        Value::Date(Date::from_ymd(1969, 12, 31).unwrap()),
+        Value::Date(Date::from_ymd(1970, 1, 1).unwrap()),
+        Value::TimestampTz(TimestampTz::new(-1, 0)),
+        Value::TimestampTz(TimestampTz::new(0, 3600)),
+        Value::Uuid(Uuid { value: [1; 16] }),
+        Value::Object(Object::new()),
+        Value::Array(vec![]),
+        Value::Null,
+    ];
+    for i in 0..sorted.len() {
+        for j in 0..sorted.len() {
+            let left = sorted[i].to_vec();
+            let right = sorted[j].to_vec();
+            assert_eq!(compare(&left, &right).unwrap(), i.cmp(&j));
+
+            let mut left_buf = Vec::new();
+            convert_to_comparable(&left, &mut left_buf);
+            let mut right_buf = Vec::new();
+            convert_to_comparable(&right, &mut right_buf);
+            assert_eq!(left_buf.cmp(&right_buf), i.cmp(&j));
+        }
+    }
+
+    // binaries that are prefixes of others, nested in arrays and objects
+    let binary = |v: &'static [u8]| Value::Binary(Cow::Borrowed(v));
+    let nested = |v: &'static [u8], next: Value<'static>| {
+        let mut obj = Object::new();
+        obj.insert("k".to_string(), Value::Array(vec![binary(v), next]));
+        Value::Object(obj)
+    };
+    let sorted = [
+        nested(b"", Value::Null),
+        nested(b"\0", Value::Uuid(Uuid { value: [0xFF; 16] })),
+        nested(b"\0", Value::Null),
+        nested(b"\0\0", Value::Null),
+        nested(b"\0\x01", Value::Null),
+        nested(b"a", Value::Null),
+        nested(b"a\0", Value::Bool(false)),
+        nested(b"ab", Value::Bool(false)),
+        nested(b"b", Value::Bool(false)),
+    ];
+    for i in 0..sorted.len() {
+        for j in 0..sorted.len() {
+            let left = sorted[i].to_vec();
+            let right = sorted[j].to_vec();
+            assert_eq!(compare(&left, &right).unwrap(), i.cmp(&j), "{i} {j}");
+
+            let mut left_buf = Vec::new();
+            convert_to_comparable(&left, &mut left_buf);
+            let mut right_buf = Vec::new();
+            convert_to_comparable(&right, &mut right_buf);
+            assert_eq!(left_buf.cmp(&right_buf), i.cmp(&j), "{i} {j}");
+
+            let mut buf = Vec::new();
+            convert_from_comparable(&left_buf, &mut buf).unwrap();
+            assert_eq!(buf, left);
+        }
+    }
+
+    // timestamps with the same instant are equal regardless of the offset
+    let left = Value::TimestampTz(TimestampTz::new(0, 0)).to_vec();
+    let right = Value::TimestampTz(TimestampTz::new(0, 3600)).to_vec();
+    assert_eq!(compare(&left, &right).unwrap(), Ordering::Equal);
+
+    assert_eq!(Date::from_ymd(2024, 2, 30), None);
+    assert_eq!(
+        Date::from_ymd(2024, 2, 29).unwrap().to_string(),
+        "2024-02-29"
+    );
+    assert_eq!(Date::from_ymd(-1, 1, 1).unwrap().to_string(), "-0001-01-01");
+    assert_eq!(
+        TimestampTz::new(-1, 19800).to_string(),
+        "1970-01-01T05:29:59.999999+05:30"
+    );
+}
+
+#[test]
+fn test_detect_format() {
+    let value = parse_value(br#"{"a":[1,"x"]}"#).unwrap();
+    assert_eq!(detect_format(br#"{"a":[1,"x"]}"#), Format::Json);
+    assert_eq!(detect_format(b" 1"), Format::Json);
+    assert_eq!(detect_format(b""), Format::Json);
+    assert_eq!(detect_format(&value.to_vec()), Format::Jsonb);
+    assert_eq!(detect_format(&Value::Null.to_vec()), Format::Jsonb);
+    assert_eq!(detect_format(&Value::Array(vec![]).to_vec()), Format::Jsonb);
+    assert_eq!(
+        detect_format(&value.to_vec_with_version()),
+        Format::VersionedJsonb(JSONB_VERSION)
+    );
+    assert_eq!(
+        detect_format(b"\xFFJB\x09\x20\0\0\0\0\0\0\0"),
+        Format::VersionedJsonb(9)
+    );
+}
+
+#[test]
+fn test_versioned_jsonb() {
+    let value = parse_value(br#"{"a":[1,"x"],"b":null}"#).unwrap();
+    let buf = value.to_vec_with_version();
+    assert_eq!(&buf[..4], &[0xFF, b'J', b'B', JSONB_VERSION]);
+    assert_eq!(&buf[4..], &value.to_vec());
+
+    assert_eq!(from_slice(&buf).unwrap(), value);
+    assert_eq!(to_string(&buf), r#"{"a":[1,"x"],"b":null}"#);
+    assert_eq!(type_of(&buf).unwrap(), "object");
+    assert_eq!(compare(&buf, &value.to_vec()).unwrap(), Ordering::Equal);
+    assert_eq!(
+        get_by_name(&buf, "a", false),
+        Some(
+            Value::Array(vec![
+                Value::Number(Number::UInt64(1)),
+                Value::String(Cow::from("x"))
+            ])
+            .to_vec()
+        )
+    );
+    let mut key = Vec::new();
+    convert_to_comparable(&buf, &mut key);
+    let mut expected = Vec::new();
+    convert_to_comparable(&value.to_vec(), &mut expected);
+    assert_eq!(key, expected);
+
+    let json_path = parse_json_path(b"$.a[1]").unwrap();
+    let mut out = Vec::new();
+    let mut offsets = Vec::new();
+    get_by_path(&buf, json_path, &mut out, &mut offsets).unwrap();
+    assert_eq!(to_string(&out), r#""x""#);
+
+    let scalar = Value::Number(Number::Int64(-3)).to_vec_with_version();
+    assert_eq!(as_i64(&scalar), Some(-3));
+    let mut out = Vec::new();
+    build_array([scalar.as_slice(), buf.as_slice()], &mut out).unwrap();
+    assert_eq!(to_string(&out), r#"[-3,{"a":[1,"x"],"b":null}]"#);
+
+    // unsupported versions are rejected instead of being misread
+    let unsupported = b"\xFFJB\x09\x20\0\0\0\0\0\0\0";
+    assert_eq!(from_slice(unsupported), Err(Error::InvalidJsonbVersion));
+    assert!(!is_null(unsupported));
+}
+
+#[test]
+fn test_offset_jentries() {
+    let items: Vec<String> = (0..1000).map(|i| format!("v{}", i)).collect();
+    let values: Vec<Value> = items
+        .iter()
+        .map(|v| Value::String(Cow::Borrowed(v.as_str())))
+        .collect();
+    let array = Value::Array(values.clone());
+    let buf = array.to_vec_with_version();
+
+    // the 32nd JEntry stores the offset from it to the end of the value,
+    // values "v0" to "v31" have a total length of 86.
+    let encoded = u32::from_be_bytes(buf[132..136].try_into().unwrap());
+    assert_eq!(encoded, 0x80000000 | 0x10000000 | (4004 + 86 - 128));
+
+    // without the version header, every JEntry stores a length
+    let legacy = array.to_vec();
+    let mut expected = buf[4..].to_vec();
+    for i in (31..1000).step_by(32) {
+        let encoded = 0x10000000 | items[i].len() as u32;
+        expected[4 + i * 4..8 + i * 4].copy_from_slice(&encoded.to_be_bytes());
+    }
+    assert_eq!(expected, legacy);
+
+    for buf in [&buf, &legacy] {
+        for i in [0, 30, 31, 32, 63, 64, 500, 999] {
+            assert_eq!(get_by_index(buf, i), Some(values[i].to_vec()));
+        }
+        assert_eq!(get_by_index(buf, 1000), None);
+        assert_eq!(from_slice(buf).unwrap(), array);
+        assert_eq!(array_values(buf).unwrap()[700], values[700].to_vec());
+        assert_eq!(to_string(buf), array.to_string());
+
+        let json_path = parse_json_path(b"$[998 to last]").unwrap();
+        let mut out = Vec::new();
+        let mut offsets = Vec::new();
+        get_by_path(buf, json_path, &mut out, &mut offsets).unwrap();
+        assert_eq!(to_string(&out), r#"["v998","v999"]"#);
+    }
+    assert_eq!(compare(&buf, &legacy).unwrap(), Ordering::Equal);
+    let mut key = Vec::new();
+    convert_to_comparable(&buf, &mut key);
+    let mut legacy_key = Vec::new();
+    convert_to_comparable(&legacy, &mut legacy_key);
+    assert_eq!(key, legacy_key);
+
+    // an offset pointing before the start of the value is corrupted
+    let mut corrupted = buf.clone();
+    corrupted[132..136].copy_from_slice(&(0x80000000u32 | 0x10000000 | 4).to_be_bytes());
+    assert_eq!(compare(&corrupted, &buf), Err(Error::InvalidJsonb));
+    assert_eq!(array_values(&corrupted), None);
+
+    // builders write the same bytes as the encoder without the version header
+    let scalars: Vec<Vec<u8>> = values.iter().map(|v| v.to_vec()).collect();
+    let mut built = Vec::new();
+    build_array(scalars.iter().map(|v| v.as_slice()), &mut built).unwrap();
+    assert_eq!(built, legacy);
+
+    let mut obj = Object::new();
+    for i in 0..40 {
+        obj.insert(
+            format!("k{:02}", i),
+            Value::Array(vec![Value::Number(Number::Int64(-i))]),
+        );
+    }
+    let object = Value::Object(obj);
+    let buf = object.to_vec_with_version();
+    let mut built = Vec::new();
+    let items: Vec<(String, Vec<u8>)> = object
+        .as_object()
+        .unwrap()
+        .iter()
+        .map(|(k, v)| (k.clone(), v.to_vec()))
+        .collect();
+    build_object(items.iter().map(|(k, v)| (k, v.as_slice())), &mut built).unwrap();
+    assert_eq!(built, object.to_vec());
+
+    assert_eq!(from_slice(&buf).unwrap(), object);
+    assert_eq!(to_string(&buf), object.to_string());
+    let expect = Value::Array(vec![Value::Number(Number::Int64(-35))]).to_vec();
+    assert_eq!(get_by_name(&buf, "k35", false), Some(expect.clone()));
+    let each = object_each(&buf).unwrap();
+    assert_eq!(each[35], (b"k35".to_vec(), expect));
+
+    let keys = object_keys(&buf).unwrap();
+    assert_eq!(
+        from_slice(&keys).unwrap(),
+        Value::Array(
+            (0..40)
+                .map(|i| Value::String(Cow::Owned(format!("k{:02}", i))))
+                .collect()
+        )
+    );
+    assert_eq!(
+        get_by_index(&keys, 33),
+        Some(Value::String(Cow::from("k33")).to_vec())
+    );
+
+    let json_path = parse_json_path(b"$.k39[0]").unwrap();
+    let mut out = Vec::new();
+    let mut offsets = Vec::new();
+    get_by_path(&buf, json_path, &mut out, &mut offsets).unwrap();
+    assert_eq!(to_string(&out), "-39");
+}
+
+#[test]
+fn test_offset_jentries_copied() {
+    // containers copied out of versioned `JSONB` use the length-only layout
+    let items: Vec<Value> = (0..100)
+        .map(|i| Value::String(Cow::from(format!("v{}", i))))
+        .collect();
+    let inner = Value::Array(items);
+    let mut object = Object::new();
+    object.insert("k".to_string(), Value::Array(vec![inner.clone(); 40]));
+    object.insert("s".to_string(), Value::Number(Number::UInt64(1)));
+    let value = Value::Object(object);
+    let buf = value.to_vec_with_version();
+    let nested = Value::Array(vec![inner.clone(); 40]);
+
+    let res = get_by_name(&buf, "k", false).unwrap();
+    assert_eq!(detect_format(&res), Format::Jsonb);
+    assert_eq!(res, nested.to_vec());
+    assert_eq!(get_by_index(&res, 33), Some(inner.to_vec()));
+
+    let keypaths = parse_key_paths(b"{k,35}").unwrap();
+    assert_eq!(
+        get_by_keypath(&buf, keypaths.paths.iter()),
+        Some(inner.to_vec())
+    );
+    let keypaths = parse_key_paths(b"{}").unwrap();
+    assert_eq!(
+        get_by_keypath(&buf, keypaths.paths.iter()),
+        Some(value.to_vec())
+    );
+
+    let versioned_inner = inner.to_vec_with_version();
+    let mut out = Vec::new();
+    build_array([versioned_inner.as_slice(), buf.as_slice()], &mut out).unwrap();
+    assert_eq!(
+        out,
+        Value::Array(vec![inner.clone(), value.clone()]).to_vec()
+    );
+
+    let mut out = Vec::new();
+    build_object([("a", versioned_inner.as_slice())], &mut out).unwrap();
+    let mut expected = Object::new();
+    expected.insert("a".to_string(), inner.clone());
+    assert_eq!(out, Value::Object(expected).to_vec());
+
+    let mut out = Vec::new();
+    delete_by_name(&buf, "s", &mut out).unwrap();
+    let mut expected = Object::new();
+    expected.insert("k".to_string(), nested.clone());
+    assert_eq!(out, Value::Object(expected).to_vec());
+
+    let mut out = Vec::new();
+    delete_by_index(&versioned_inner, 100, &mut out).unwrap();
+    assert_eq!(out, inner.to_vec());
+
+    let json_path = parse_json_path(b"$.k[34]").unwrap();
+    let mut out = Vec::new();
+    let mut offsets = Vec::new();
+    get_by_path(&buf, json_path, &mut out, &mut offsets).unwrap();
+    assert_eq!(out, inner.to_vec());
+}
+
+#[test]
+fn test_get_by_name_wide_object() {
+    let mut obj = Object::new();
+    for i in 0..1000 {
+        obj.insert(format!("key{}", i), Value::Number(Number::Int64(i)));
+    }
+    obj.insert("".to_string(), Value::Null);
+    obj.insert("Upper".to_string(), Value::Bool(true));
+    let value = Value::Object(obj);
+
+    // keys are searched by binary search only with the version header
+    for buf in [value.to_vec_with_version(), value.to_vec()] {
+        for i in 0..1000 {
+            let name = format!("key{}", i);
+            let expect = Value::Number(Number::Int64(i)).to_vec();
+            assert_eq!(get_by_name(&buf, &name, false), Some(expect.clone()));
+            assert_eq!(get_by_name(&buf, &name, true), Some(expect));
+        }
+        assert_eq!(get_by_name(&buf, "", false), Some(Value::Null.to_vec()));
+        assert_eq!(get_by_name(&buf, "key1000", false), None);
+        assert_eq!(get_by_name(&buf, "upper", false), None);
+        assert_eq!(
+            get_by_name(&buf, "upper", true),
+            Some(Value::Bool(true).to_vec())
+        );
+        assert_eq!(
+            get_by_name(&buf, "KEY7", true),
+            Some(Value::Number(Number::Int64(7)).to_vec())
+        );
+
+        assert!(exists_all_keys(
+            &buf,
+            [b"key0".as_slice(), b"key999", b"Upper"].into_iter()
+        ));
+        assert!(!exists_all_keys(
+            &buf,
+            [b"key0".as_slice(), b"key"].into_iter()
+        ));
+        assert!(exists_any_keys(
+            &buf,
+            [b"zzz".as_slice(), b"key500"].into_iter()
+        ));
+
+        let key_paths = parse_key_paths(b"{key123}").unwrap();
+        assert_eq!(
+            get_by_keypath(&buf, key_paths.paths.iter()),
+            Some(Value::Number(Number::Int64(123)).to_vec())
+        );
+        let json_path = parse_json_path(b"$.key456").unwrap();
+        let mut out = Vec::new();
+        let mut offsets = Vec::new();
+        get_by_path(&buf, json_path, &mut out, &mut offsets).unwrap();
+        assert_eq!(to_string(&out), "456");
+
+        let right = |keys: &[i64]| {
+            let mut obj = Object::new();
+            for i in keys {
+                obj.insert(format!("key{}", i), Value::Number(Number::Int64(*i)));
+            }
+            Value::Object(obj).to_vec()
+        };
+        assert!(contains(&buf, &right(&[10, 999])));
+        assert!(!contains(&buf, &right(&[10, 1000])));
+    }
+
+    // build object keeps the keys in the order of the items,
+    // the same as the releases before, the first duplicate key wins
+    let mut values: Vec<(String, Vec<u8>)> = (0..40)
+        .rev()
+        .map(|i| {
+            (
+                format!("k{:02}", i),
+                Value::Number(Number::Int64(i)).to_vec(),
+            )
+        })
+        .collect();
+    values.push(("k05".to_string(), Value::Null.to_vec()));
+    let mut built = Vec::new();
+    build_object(values.iter().map(|(k, v)| (k, v.as_slice())), &mut built).unwrap();
+    assert_eq!(
+        object_keys(&built).and_then(|keys| get_by_index(&keys, 0)),
+        Some(Value::String(Cow::from("k39")).to_vec())
+    );
+    for (k, v) in values.iter().take(40) {
+        assert_eq!(get_by_name(&built, k, false).as_ref(), Some(v));
+    }
+    assert!(exists_all_keys(&built, [b"k05".as_slice()].into_iter()));
+    let key_paths = parse_key_paths(b"{k05}").unwrap();
+    assert_eq!(
+        get_by_keypath(&built, key_paths.paths.iter()),
+        Some(Value::Number(Number::Int64(5)).to_vec())
+    );
+    let json_path = parse_json_path(b"$.k05").unwrap();
+    let mut out = Vec::new();
+    let mut offsets = Vec::new();
+    get_by_path(&built, json_path, &mut out, &mut offsets).unwrap();
+    assert_eq!(to_string(&out), "5");
+}
+
+#[test]
+fn test_corrupted_jsonb() {
+    let mut sources = vec![
+        r#"null"#.to_string(),
+        r#"-1.5e10"#.to_string(),
+        r#""abc""#.to_string(),
+        r#"[1,"a",[true,null],{"k":false}]"#.to_string(),
+        r#"{"a":1,"b":[2,{"c":"d"}],"e":{"f":[]}}"#.to_string(),
+    ];
+    let keys: Vec<String> = (0..40).map(|i| format!(r#""k{}":{}"#, i, i)).collect();
+    sources.push(format!("{{{}}}", keys.join(",")));
+
+    let key_paths = parse_key_paths(b"{a,0,b}").unwrap();
+    let keys: BTreeSet<&str> = ["a", "b"].into_iter().collect();
+    let json_path = parse_json_path(b"$.a[*] ? (@ > 1)").unwrap();
+
+    let check = |value: &[u8]| {
+        let mut buf = Vec::new();
+        let mut offsets = Vec::new();
+        let _ = from_slice(value);
+        let _ = to_string(value);
+        let _ = to_pretty_string(value);
+        let _ = to_serde_json(value);
+        let _ = as_str(value);
+        let _ = as_number(value);
+        let _ = type_of(value);
+        let _ = array_length(value);
+        let _ = array_values(value);
+        let _ = object_keys(value);
+        let _ = object_each(value);
+        let _ = get_by_index(value, 1);
+        let _ = get_by_name(value, "k3", false);
+        let _ = get_by_keypath(value, key_paths.paths.iter());
+        let _ = get_by_path(value, json_path.clone(), &mut buf, &mut offsets);
+        let _ = path_exists(value, json_path.clone());
+        let _ = compare(value, value);
+        let _ = contains(value, value);
+        let _ = traverse_check_string(value, |_| false);
+        let _ = exists_all_keys(value, [b"a".as_slice()].into_iter());
+        convert_to_comparable(value, &mut buf);
+        buf.clear();
+        let _ = concat(value, value, &mut buf);
+        buf.clear();
+        let _ = delete_by_name(value, "a", &mut buf);
+        buf.clear();
+        let _ = delete_by_index(value, -1, &mut buf);
+        buf.clear();
+        let _ = delete_by_keypath(value, key_paths.paths.iter(), &mut buf);
+        buf.clear();
+        let _ = object_delete(value, &keys, &mut buf);
+        buf.clear();
+        let _ = object_pick(value, &keys, &mut buf);
+        buf.clear();
+        let _ = array_insert(value, 0, value, &mut buf);
+        buf.clear();
+        let _ = object_insert(value, "a", value, true, &mut buf);
+        buf.clear();
+        let _ = strip_nulls(value, &mut buf);
+        buf.clear();
+        let _ = build_array([value], &mut buf);
+        buf.clear();
+        let _ = build_object([("a", value)], &mut buf);
+    };
+
+    // none of the functions may panic, whatever the corrupted bytes are
+    for source in sources {
+        let value = parse_value(source.as_bytes()).unwrap().to_vec();
+        for len in 0..value.len() {
+            check(&value[..len]);
+        }
+        for i in 0..value.len() {
+            for b in [0x00, 0x10, 0x50, 0x7F, 0xFF] {
+                let mut corrupted = value.clone();
+                corrupted[i] = b;
+                check(&corrupted);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_functions_max_depth() {
+    let nested = |depth: usize| {
+        let mut value = Value::Null;
+        for _ in 0..depth {
+            let mut obj = Object::new();
+            obj.insert("a".to_string(), Value::Array(vec![value]));
+            value = Value::Object(obj);
+        }
+        value.to_vec()
+    };
+    let value = nested(64);
+    assert_eq!(compare(&value, &value), Ok(Ordering::Equal));
+    assert!(contains(&value, &value));
+    let mut buf = Vec::new();
+    assert!(strip_nulls(&value, &mut buf).is_ok());
+
+    let value = nested(65);
+    assert_eq!(compare(&value, &value), Err(Error::RecursionLimitExceeded));
+    assert!(!contains(&value, &value));
+    let mut buf = Vec::new();
+    assert_eq!(
+        strip_nulls(&value, &mut buf),
+        Err(Error::RecursionLimitExceeded)
+    );
+
+    // the functions fail without overflowing the stack, the arrays are encoded directly
+    // as the values would overflow the stack when they are dropped
+    let deep_array = |depth: usize| {
+        let mut buf = b"\x80\0\0\0".to_vec();
+        for _ in 1..depth {
+            let jentry = 0x50000000 | buf.len() as u32;
+            let mut outer = b"\x80\0\0\x01".to_vec();
+            outer.extend_from_slice(&jentry.to_be_bytes());
+            outer.extend_from_slice(&buf);
+            buf = outer;
+        }
+        buf
+    };
+    let value = deep_array(20000);
+    let opts = JsonFormatOptions::new();
+    assert_eq!(
+        to_string_with_options(&value, &opts),
+        Err(Error::RecursionLimitExceeded)
+    );
+    assert_eq!(
+        to_canonical_string(&value),
+        Err(Error::RecursionLimitExceeded)
+    );
+    assert_eq!(to_serde_json(&value), Err(Error::RecursionLimitExceeded));
+    assert_eq!(compare(&value, &value), Err(Error::RecursionLimitExceeded));
+    let mut key = Vec::new();
+    convert_to_comparable(&value, &mut key);
+    assert_eq!(key.len(), 1 + 2 * 129 + 5);
+
+    // the limit is configurable
+    let value = deep_array(200);
+    let text = format!("{}{}", "[".repeat(200), "]".repeat(200));
+    assert_eq!(
+        to_string_with_options(&value, &JsonFormatOptions::new()),
+        Err(Error::RecursionLimitExceeded)
+    );
+    let opts = JsonFormatOptions::new().with_max_depth(200);
+    assert_eq!(to_string_with_options(&value, &opts).unwrap(), text);
+    // the infallible functions are not limited
+    assert_eq!(to_string(&value), text);
+    assert!(to_pretty_string(&value).starts_with("[\n  [\n    ["));
+    let opts = JsonFormatOptions::new().with_max_depth(199);
+    assert_eq!(
+        to_string_with_options(&value, &opts),
+        Err(Error::RecursionLimitExceeded)
+    );
+    let opts = CompareOptions::new().with_max_depth(200);
+    assert_eq!(
+        compare_with_options(&value, &deep_array(201), &opts),
+        Ok(Ordering::Less)
+    );
+    let mut key = Vec::new();
+    convert_to_comparable_with_options(&value, &mut key, &opts);
+    let mut buf = Vec::new();
+    convert_from_comparable_with_options(&key, &mut buf, &opts).unwrap();
+    assert_eq!(buf, value);
+    assert_eq!(
+        convert_from_comparable(&key, &mut buf),
+        Err(Error::RecursionLimitExceeded)
+    );
+
+    // the depth of the comparable bytes is at most 255
+    let opts = CompareOptions::new().with_max_depth(1000);
+    let mut key = Vec::new();
+    convert_to_comparable_with_options(&deep_array(300), &mut key, &opts);
+    assert_eq!(key.len(), 1 + 2 * 256 + 5);
+    let mut shallow = Vec::new();
+    convert_to_comparable_with_options(&deep_array(256), &mut shallow, &opts);
+    assert_eq!(key, shallow);
+}
+
+#[test]
+fn test_to_string_with_options() {
+    let value = parse_value(r#"{"b":[1,{}],"a":"<é😀&>"}"#.as_bytes())
+        .unwrap()
+        .to_vec();
+    let opts = JsonFormatOptions::new();
+    assert_eq!(
+        to_string_with_options(&value, &opts).unwrap(),
+        to_string(&value)
+    );
+    let opts = JsonFormatOptions::pretty();
+    assert_eq!(
+        to_string_with_options(&value, &opts).unwrap(),
+        to_pretty_string(&value)
+    );
+
+    let opts = JsonFormatOptions::new().with_indent(Indent::Tab);
+    assert_eq!(
+        to_string_with_options(&value, &opts).unwrap(),
+        "{\n\t\"a\": \"<é😀&>\",\n\t\"b\": [\n\t\t1,\n\t\t{\n\n\t\t}\n\t]\n}"
+    );
+    let opts = JsonFormatOptions::new().with_indent(Indent::Spaces(4));
+    assert_eq!(
+        to_string_with_options(br#"[1,[2]]"#, &opts).unwrap(),
+        "[\n    1,\n    [\n        2\n    ]\n]"
+    );
+    let opts = JsonFormatOptions::new().with_ascii_only(true);
+    assert_eq!(
+        to_string_with_options(&value, &opts).unwrap(),
+        r#"{"a":"<\u00e9\ud83d\ude00&>","b":[1,{}]}"#
+    );
+    let opts = JsonFormatOptions::new().with_html_safe(true);
+    assert_eq!(
+        to_string_with_options(&value, &opts).unwrap(),
+        r#"{"a":"\u003cé😀\u0026\u003e","b":[1,{}]}"#
+    );
+
+    // keys of objects written by other encoders may not be sorted
+    let mut unsorted = Vec::new();
+    unsorted.extend_from_slice(b"\x40\0\0\x02\x10\0\0\x01\x10\0\0\x01");
+    unsorted.extend_from_slice(b"\x20\0\0\x02\x20\0\0\x02ba\x50\x01\x50\x02");
+    assert_eq!(to_string(&unsorted), r#"{"b":1,"a":2}"#);
+    let opts = JsonFormatOptions::new().with_sort_keys(true);
+    assert_eq!(
+        to_string_with_options(&unsorted, &opts).unwrap(),
+        r#"{"a":2,"b":1}"#
+    );
+
+    let non_finite = Value::Array(vec![
+        Value::Number(Number::Float64(f64::NAN)),
+        Value::Number(Number::Float64(f64::INFINITY)),
+        Value::Number(Number::Float64(f64::NEG_INFINITY)),
+    ])
+    .to_vec();
+    let tests = vec![
+        (NonFinitePolicy::Display, Ok(r#"[NaN,inf,-inf]"#)),
+        (NonFinitePolicy::Null, Ok(r#"[null,null,null]"#)),
+        (
+            NonFinitePolicy::String,
+            Ok(r#"["NaN","Infinity","-Infinity"]"#),
+        ),
+        (NonFinitePolicy::Error, Err(Error::NonFiniteNumber)),
+    ];
+    for (policy, expected) in tests {
+        let opts = JsonFormatOptions::new().with_non_finite(policy);
+        let res = to_string_with_options(&non_finite, &opts);
+        assert_eq!(res.as_deref(), expected.as_deref());
+    }
+
+    let floats = Value::Array(vec![
+        Value::Number(Number::Float64(1.0)),
+        Value::Number(Number::Float64(0.25)),
+        Value::Number(Number::Float64(1e20)),
+        Value::Number(Number::Float64(-1.5e-7)),
+        Value::Number(Number::Int64(7)),
+    ])
+    .to_vec();
+    let tests = vec![
+        (FloatFormat::Shortest, "[1.0,0.25,1e20,-1.5e-7,7]"),
+        (
+            FloatFormat::Plain,
+            "[1,0.25,100000000000000000000,-0.00000015,7]",
+        ),
+        (FloatFormat::Scientific, "[1e0,2.5e-1,1e20,-1.5e-7,7]"),
+    ];
+    for (format, expected) in tests {
+        let opts = JsonFormatOptions::new().with_float_format(format);
+        assert_eq!(to_string_with_options(&floats, &opts).unwrap(), expected);
+    }
+
+    assert_eq!(
+        to_string_with_options(b"", &JsonFormatOptions::new()).unwrap(),
+        "null"
+    );
+    assert!(to_string_with_options(b"[1,", &JsonFormatOptions::new()).is_err());
+}
+
+#[test]
+fn test_to_canonical_string() {
+    // the number examples of RFC 8785
+    let numbers = vec![
+        (0.0, "0"),
+        (-0.0, "0"),
+        (1.0, "1"),
+        (-1.5, "-1.5"),
+        (5e-324, "5e-324"),
+        (-5e-324, "-5e-324"),
+        (1.7976931348623157e308, "1.7976931348623157e+308"),
+        (-1.7976931348623157e308, "-1.7976931348623157e+308"),
+        (9007199254740992.0, "9007199254740992"),
+        (-9007199254740992.0, "-9007199254740992"),
+        (295147905179352830000.0, "295147905179352830000"),
+        (9.999999999999997e22, "9.999999999999997e+22"),
+        (1e23, "1e+23"),
+        (1e21, "1e+21"),
+        (999999999999999700000.0, "999999999999999700000"),
+        (0.000001, "0.000001"),
+        (0.0000001, "1e-7"),
+        (333333333.3333333, "333333333.3333333"),
+        (1.5e-10, "1.5e-10"),
+    ];
+    for (n, expected) in numbers {
+        let value = Value::Number(Number::Float64(n)).to_vec();
+        assert_eq!(to_canonical_string(&value).unwrap(), expected);
+    }
+    let value = Value::Number(Number::Float64(f64::NAN)).to_vec();
+    assert_eq!(to_canonical_string(&value), Err(Error::NonFiniteNumber));
+
+    // the sorting example of RFC 8785
+    let source = r#"{
+        "\u20ac": "Euro Sign",
+        "\r": "Carriage Return",
+        "\ufb33": "Hebrew Letter Dalet With Dagesh",
+        "1": "One",
+        "\ud83d\ude00": "Emoji: Grinning Face",
+        "\u0080": "Control",
+        "\u00f6": "Latin Small Letter O With Diaeresis"
+    }"#;
+    let expected = "{\"\\r\":\"Carriage Return\",\"1\":\"One\",\"\u{80}\":\"Control\",\
+        \"\u{f6}\":\"Latin Small Letter O With Diaeresis\",\"\u{20ac}\":\"Euro Sign\",\
+        \"\u{1f600}\":\"Emoji: Grinning Face\",\"\u{fb33}\":\"Hebrew Letter Dalet With Dagesh\"}";
+    let value = parse_value(source.as_bytes()).unwrap().to_vec();
+    assert_eq!(to_canonical_string(&value).unwrap(), expected);
+    assert_eq!(to_canonical_string(source.as_bytes()).unwrap(), expected);
+
+    // semantically equal values have the same canonical text
+    let opts = ParseOptions::new().with_decimal(true);
+    let sources = vec![
+        r#"{"a":[1,2.50,"x\/y"],"b":{"c":null,"d":1e2}}"#,
+        r#" { "b" : { "d" : 100.0, "c" : null }, "a" : [ 1.0, 25E-1, "x/y" ] } "#,
+        r#"{"b":{"c":null,"d":10e1},"a":[0.1e1,2.5,"\u0078/y"]}"#,
+    ];
+    for source in sources {
+        let expected = r#"{"a":[1,2.5,"x/y"],"b":{"c":null,"d":100}}"#;
+        assert_eq!(to_canonical_string(source.as_bytes()).unwrap(), expected);
+        let value = parse_value_with_options(source.as_bytes(), &opts).unwrap();
+        assert_eq!(to_canonical_string(&value.to_vec()).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_canonicalize() {
+    let decimal = |value, scale| Number::Decimal(Decimal::new(value, scale).unwrap());
+    // each group contains equal numbers with different representations
+    let groups = vec![
+        vec![
+            Number::Int64(5),
+            Number::UInt64(5),
+            Number::Int128(5),
+            Number::UInt128(5),
+            Number::Float64(5.0),
+            decimal(500, 2),
+            Number::Raw(RawNumber::new("5.0").unwrap()),
+        ],
+        vec![
+            Number::UInt64(0),
+            Number::Int64(0),
+            Number::Float64(-0.0),
+            decimal(0, 3),
+            Number::Raw(RawNumber::new("-0").unwrap()),
+        ],
+        vec![
+            Number::Int64(-300),
+            Number::Int128(-300),
+            Number::Float64(-300.0),
+            decimal(-3000, 1),
+            Number::Raw(RawNumber::new("-3e2").unwrap()),
+        ],
+        vec![
+            Number::Float64(2.5),
+            decimal(250, 2),
+            Number::Raw(RawNumber::new("2.50").unwrap()),
+        ],
+        vec![Number::Float64(0.1), decimal(1000, 4)],
+        vec![
+            Number::UInt128(1 << 70),
+            Number::Float64(2f64.powi(70)),
+            Number::Raw(RawNumber::new("1180591620717411303424").unwrap()),
+        ],
+        vec![
+            Number::Int128(i128::MIN),
+            Number::Float64(-(2f64.powi(127))),
+        ],
+    ];
+    for group in groups {
+        let mut expected = None;
+        for num in group {
+            let value = Value::Array(vec![Value::Number(num.clone())]).to_vec();
+            let mut buf = Vec::new();
+            canonicalize(&value, &mut buf).unwrap();
+            assert_eq!(compare(&value, &buf).unwrap(), Ordering::Equal);
+            let mut again = Vec::new();
+            canonicalize(&buf, &mut again).unwrap();
+            assert_eq!(buf, again);
+            match &expected {
+                None => expected = Some(buf),
+                Some(expected) => assert_eq!(&buf, expected, "{:?}", num),
+            }
+        }
+    }
+
+    // decimals that are not equal to a float keep the exact value
+    let mut buf = Vec::new();
+    let value = Value::Number(decimal(1_000_000_000_000_000_010, 19)).to_vec();
+    canonicalize(&value, &mut buf).unwrap();
+    assert_eq!(
+        from_slice(&buf).unwrap(),
+        Value::Number(decimal(100_000_000_000_000_001, 18))
+    );
+
+    // the keys are sorted and unique, the last value of duplicate keys is kept
+    let mut unsorted = Vec::new();
+    unsorted.extend_from_slice(b"\x40\0\0\x03\x10\0\0\x01\x10\0\0\x01\x10\0\0\x01");
+    unsorted.extend_from_slice(b"\x20\0\0\x02\x20\0\0\x02\x20\0\0\x02bab\x50\x01\x50\x02\x50\x03");
+    let expected = parse_value(br#"{"a":2,"b":3}"#).unwrap().to_vec();
+    let mut buf = Vec::new();
+    canonicalize(&unsorted, &mut buf).unwrap();
+    assert_eq!(buf, expected);
+
+    let sources = vec![
+        r#"{"b":3,"a":2.0}"#,
+        r#"{"b":1,"a":2,"b":3.00}"#,
+        r#" { "a" : 20e-1 , "b" : 3 } "#,
+    ];
+    for source in sources {
+        let mut buf = Vec::new();
+        canonicalize(source.as_bytes(), &mut buf).unwrap();
+        assert_eq!(buf, expected);
+    }
+
+    // timestamps of the same instant are converted to UTC
+    let expected = Value::Array(vec![Value::TimestampTz(TimestampTz::new(-1, 0))]).to_vec();
+    for offset in [0, 3600, -19800] {
+        let value = Value::Array(vec![Value::TimestampTz(TimestampTz::new(-1, offset))]).to_vec();
+        let mut buf = Vec::new();
+        canonicalize(&value, &mut buf).unwrap();
+        assert_eq!(buf, expected);
+        assert_eq!(
+            to_canonical_string(&value).unwrap(),
+            r#"["1969-12-31T23:59:59.999999+00:00"]"#
+        );
+    }
+}
@@ -18,3 +18,5 @@ mod functions;
 mod jsonpath_parser;
 mod keypath_parser;
 mod parser;
+mod stream;
+mod validate;
@@ -14,7 +14,10 @@
 
 use std::borrow::Cow;
 
-use jsonb::{parse_value, Number, Object, Value};
+use jsonb::{
+    parse_value, parse_value_with_options, Decimal, DuplicateKeyPolicy, Error, Number, Object,
+    ParseErrorCode, ParseOptions, RawNumber, Value,
+};
 
 fn test_parse_err(errors: &[(&str, &'static str)]) {
     for &(s, err) in errors {
@@ -109,6 +112,44 @@ fn test_parse_u64() {
     ]);
 }
 
+#[test]
+fn test_parse_i128() {
+    test_parse_ok(vec![
+        (
+            "-9223372036854775809",
+            Value::Number(Number::Int128(i64::MIN as i128 - 1)),
+        ),
+        (
+            &i128::MIN.to_string(),
+            Value::Number(Number::Int128(i128::MIN)),
+        ),
+    ]);
+}
+
+#[test]
+fn test_parse_u128() {
+    test_parse_ok(vec![
+        (
+            "18446744073709551616",
+            Value::Number(Number::UInt128(u64::MAX as u128 + 1)),
+        ),
+        (
+            &u128::MAX.to_string(),
+            Value::Number(Number::UInt128(u128::MAX)),
+        ),
+    ]);
+    let value = parse_value(b"18446744073709551616").unwrap();
+    assert!(matches!(value, Value::Number(Number::UInt128(_))));
+    assert_eq!(value.as_u128(), Some(18446744073709551616));
+    assert_eq!(value.as_i128(), Some(18446744073709551616));
+    assert_eq!(value.as_u64(), None);
+    assert_eq!(value.to_string(), "18446744073709551616");
+
+    // out of the 128-bit range
+    let value = parse_value(b"340282366920938463463374607431768211456").unwrap();
+    assert!(matches!(value, Value::Number(Number::Float64(_))));
+}
+
 #[test]
 fn test_parse_f64() {
     test_parse_ok(vec![
@@ -175,11 +216,11 @@ fn test_parse_f64() {
             Value::Number(Number::Float64(0.01)),
         ),
         (
-            &format!("{}", (i64::MIN as f64) - 1.0),
+            &format!("{:e}", (i64::MIN as f64) - 1.0),
             Value::Number(Number::Float64((i64::MIN as f64) - 1.0)),
         ),
         (
-            &format!("{}", (u64::MAX as f64) + 1.0),
+            &format!("{:e}", (u64::MAX as f64) + 1.0),
             Value::Number(Number::Float64((u64::MAX as f64) + 1.0)),
         ),
         (
@@ -415,3 +456,303 @@ fn test_parse_object() {
         (r#"{ \x0C "d":  5}"#, Value::Object(obj5)),
     ]);
 }
+
+#[test]
+fn test_parse_duplicate_key() {
+    let json = r#"{"a":1,"b":2,"a":3}"#;
+
+    let mut obj = Object::new();
+    obj.insert("a".to_string(), Value::Number(Number::UInt64(3)));
+    obj.insert("b".to_string(), Value::Number(Number::UInt64(2)));
+    assert_eq!(
+        parse_value(json.as_bytes()).unwrap(),
+        Value::Object(obj.clone())
+    );
+
+    let opts = ParseOptions::new().with_duplicate_key(DuplicateKeyPolicy::LastWins);
+    let value = parse_value_with_options(json.as_bytes(), &opts).unwrap();
+    assert_eq!(value, Value::Object(obj));
+
+    let mut obj = Object::new();
+    obj.insert("a".to_string(), Value::Number(Number::UInt64(1)));
+    obj.insert("b".to_string(), Value::Number(Number::UInt64(2)));
+    let opts = ParseOptions::new().with_duplicate_key(DuplicateKeyPolicy::FirstWins);
+    let value = parse_value_with_options(json.as_bytes(), &opts).unwrap();
+    assert_eq!(value, Value::Object(obj));
+
+    let opts = ParseOptions::new().with_duplicate_key(DuplicateKeyPolicy::Error);
+    let res = parse_value_with_options(json.as_bytes(), &opts);
+    assert_eq!(
+        res,
+        Err(Error::Syntax(
+            ParseErrorCode::ObjectDuplicateKey("a".to_string()),
+            16
+        ))
+    );
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "duplicate object key `a`, pos 16"
+    );
+
+    let json = r#"{"a":{"b":1,"b":2}}"#;
+    let res = parse_value_with_options(json.as_bytes(), &opts);
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "duplicate object key `b`, pos 15"
+    );
+    let json = r#"{"a":{"b":1},"b":{"a":2}}"#;
+    assert!(parse_value_with_options(json.as_bytes(), &opts).is_ok());
+}
+
+#[test]
+fn test_parse_lenient() {
+    // all extensions are rejected by default
+    test_parse_err(&[
+        ("[1, // one\n 2]", "expected value, pos 5"),
+        ("[1,]", "expected value, pos 4"),
+        (r#"{"a":1,}"#, "expected value, pos 8"),
+        ("'abc'", "expected value, pos 1"),
+        ("{a:1}", "expected value, pos 2"),
+        ("NaN", "expected value, pos 1"),
+        ("-Infinity", "invalid number, pos 2"),
+    ]);
+
+    let mut obj = Object::new();
+    obj.insert("a".to_string(), Value::Number(Number::UInt64(1)));
+    obj.insert("b_$1".to_string(), Value::String(Cow::from("it's")));
+
+    let tests = vec![
+        (
+            ParseOptions::new().with_comments(true),
+            "/* head */ [1, // one\n 2 /* two */] // tail",
+            Value::Array(vec![
+                Value::Number(Number::UInt64(1)),
+                Value::Number(Number::UInt64(2)),
+            ]),
+        ),
+        (
+            ParseOptions::new().with_trailing_commas(true),
+            "[1, 2 , ]",
+            Value::Array(vec![
+                Value::Number(Number::UInt64(1)),
+                Value::Number(Number::UInt64(2)),
+            ]),
+        ),
+        (
+            ParseOptions::new().with_trailing_commas(true),
+            r#"{"a": 1, }"#,
+            Value::Object(Object::from_iter([(
+                "a".to_string(),
+                Value::Number(Number::UInt64(1)),
+            )])),
+        ),
+        (
+            ParseOptions::new().with_single_quotes(true),
+            r#"['a\'b', "c'd", 'e"f']"#,
+            Value::Array(vec![
+                Value::String(Cow::from("a'b")),
+                Value::String(Cow::from("c'd")),
+                Value::String(Cow::from("e\"f")),
+            ]),
+        ),
+        (
+            ParseOptions::new().with_unquoted_keys(true),
+            r#"{a: 1, b_$1: "it's"}"#,
+            Value::Object(obj.clone()),
+        ),
+        (
+            ParseOptions::new().with_nan_inf(true),
+            "[Infinity, -Infinity]",
+            Value::Array(vec![
+                Value::Number(Number::Float64(f64::INFINITY)),
+                Value::Number(Number::Float64(f64::NEG_INFINITY)),
+            ]),
+        ),
+        (
+            ParseOptions::lenient(),
+            "{\n  // comment\n  a: 1,\n  'b_$1': 'it\\'s',\n}",
+            Value::Object(obj),
+        ),
+    ];
+    for (opts, s, expected) in tests {
+        let value = parse_value_with_options(s.as_bytes(), &opts).unwrap();
+        assert_eq!(value, expected);
+    }
+
+    let opts = ParseOptions::new().with_nan_inf(true);
+    let value = parse_value_with_options(b"NaN", &opts).unwrap();
+    assert!(value.as_f64().unwrap().is_nan());
+
+    // escaped single quote is not allowed in double-quoted strings
+    let opts = ParseOptions::lenient();
+    assert!(parse_value_with_options(br#""a\'b""#, &opts).is_err());
+    assert!(parse_value_with_options(b"[1,,]", &opts).is_err());
+    assert!(parse_value_with_options(b"[1 /* unterminated", &opts).is_err());
+    for (s, pos) in [
+        ("1 /* abc", 8),
+        ("[1] /*", 6),
+        ("/*", 2),
+        ("[1, /", 5),
+        ("1 /", 3),
+    ] {
+        assert_eq!(
+            parse_value_with_options(s.as_bytes(), &opts),
+            Err(Error::Syntax(ParseErrorCode::InvalidEOF, pos))
+        );
+    }
+    // a line comment may end at the end of the input
+    assert!(parse_value_with_options(b"1 // abc", &opts).is_ok());
+}
+
+#[test]
+fn test_parse_decimal() {
+    let opts = ParseOptions::new().with_decimal(true);
+    let tests = vec![
+        ("0.1", Some((1, 1)), "0.1"),
+        ("-0.05", Some((-5, 2)), "-0.05"),
+        ("1.50", Some((150, 2)), "1.50"),
+        ("1.5e3", Some((1500, 0)), "1500"),
+        ("125E-4", Some((125, 4)), "0.0125"),
+        (
+            "12345678901234567890.123",
+            Some((12345678901234567890123, 3)),
+            "12345678901234567890.123",
+        ),
+        (
+            "-1.5e30",
+            Some((-1500000000000000000000000000000, 0)),
+            "-1500000000000000000000000000000",
+        ),
+        (
+            "-123456789012345678901234567890",
+            None,
+            "-123456789012345678901234567890",
+        ),
+        ("123", None, "123"),
+        ("-123", None, "-123"),
+        ("1e100", None, "1e100"),
+        ("1.5e-50", None, "1.5e-50"),
+    ];
+    for (s, expected, display) in tests {
+        let value = parse_value_with_options(s.as_bytes(), &opts).unwrap();
+        let Value::Number(num) = &value else {
+            panic!("expected number: {s}");
+        };
+        match expected {
+            Some((v, scale)) => {
+                let Number::Decimal(d) = num else {
+                    panic!("expected decimal: {s}");
+                };
+                assert_eq!((d.value, d.scale), (v, scale));
+            }
+            None => assert!(!matches!(num, Number::Decimal(_)), "{s}"),
+        }
+        assert_eq!(value.to_string(), display);
+
+        // encode and decode keep the exact value
+        let buf = value.to_vec();
+        assert_eq!(jsonb::to_string(&buf), display);
+    }
+
+    // decimals are compared with the exact values
+    let d = |v, scale| Number::Decimal(Decimal::new(v, scale).unwrap());
+    assert_eq!(d(150, 2), d(15, 1));
+    assert_eq!(d(3000, 3), Number::Int64(3));
+    assert_eq!(d(1, 1), Number::Float64(0.1));
+    assert!(d(12345678901234567890124, 3) > d(12345678901234567890123, 3));
+    assert!(d(i128::MAX, 0) > d(i128::MAX, 38));
+    assert!(d(-1, 38) < Number::UInt64(0));
+    assert!(d(-1, 38) > Number::Int64(-1));
+    assert!(d(1, 0) < Number::Float64(f64::INFINITY));
+    assert!(d(1, 0) < Number::Float64(f64::NAN));
+    // the floats can't distinguish the values
+    assert!(d(10000000000000000001, 0) > Number::Float64(1e19));
+    assert_eq!(
+        Number::UInt64(u64::MAX).as_f64(),
+        Number::Float64(1.8446744073709552e19).as_f64()
+    );
+    assert!(Number::UInt64(u64::MAX) < Number::Float64(1.8446744073709552e19));
+}
+
+#[test]
+fn test_parse_number_text() {
+    let opts = ParseOptions::new().with_number_text(true);
+    let tests = vec![
+        ("1.10", Number::Float64(1.1)),
+        ("1e2", Number::UInt64(100)),
+        ("-0.0", Number::Int64(0)),
+        ("0.30000000000000004", Number::Float64(0.30000000000000004)),
+        (
+            "18446744073709551616",
+            Number::UInt128(18446744073709551616),
+        ),
+        ("1E+400", Number::Float64(f64::INFINITY)),
+    ];
+    for (s, expected) in tests {
+        let value = parse_value_with_options(s.as_bytes(), &opts).unwrap();
+        assert_eq!(
+            value,
+            Value::Number(Number::Raw(RawNumber::new(s).unwrap()))
+        );
+        assert_eq!(value, Value::Number(expected));
+        assert_eq!(value.to_string(), s);
+
+        let buf = value.to_vec();
+        assert_eq!(jsonb::to_string(&buf), s);
+        assert_eq!(jsonb::from_slice(&buf).unwrap(), value);
+    }
+
+    let value = parse_value_with_options(br#"{"a":[1.50, 2E3]}"#, &opts).unwrap();
+    assert_eq!(value.to_string(), r#"{"a":[1.50,2E3]}"#);
+    let array = value
+        .as_object()
+        .unwrap()
+        .get("a")
+        .unwrap()
+        .as_array()
+        .unwrap();
+    assert_eq!(array[0].as_f64(), Some(1.5));
+    assert_eq!(array[1].as_u64(), Some(2000));
+    assert_eq!(array[1].as_i128(), Some(2000));
+
+    // invalid number text
+    assert!(jsonb::from_slice(b"\x20\0\0\0\x20\0\0\x04\x80abc").is_err());
+    assert!(jsonb::from_slice(b"\x20\0\0\0\x20\0\0\x04\x80 1 ").is_err());
+    for s in [
+        "", " 1", "1 ", " 1 ", "abc", "01", "1.", "-", "+1", "1e", "NaN", "[1]",
+    ] {
+        assert!(RawNumber::new(s).is_none(), "{:?}", s);
+    }
+    let raw = RawNumber::new("-1.50e1").unwrap();
+    assert_eq!(raw.as_str(), "-1.50e1");
+    assert_eq!(raw.value().as_f64(), Some(-15.0));
+}
+
+#[test]
+fn test_parse_max_depth() {
+    let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    assert!(parse_value(nested(128).as_bytes()).is_ok());
+    assert_eq!(
+        parse_value(nested(129).as_bytes()),
+        Err(Error::Syntax(ParseErrorCode::RecursionLimitExceeded, 129))
+    );
+    let objects = format!("{}1{}", r#"{"a":"#.repeat(129), "}".repeat(129));
+    assert_eq!(
+        parse_value(objects.as_bytes()),
+        Err(Error::Syntax(ParseErrorCode::RecursionLimitExceeded, 641))
+    );
+    // deep text fails instead of overflowing the stack
+    assert!(parse_value(nested(1_000_000).as_bytes()).is_err());
+
+    let opts = ParseOptions::new().with_max_depth(1000);
+    assert!(parse_value_with_options(nested(1000).as_bytes(), &opts).is_ok());
+    let opts = ParseOptions::new().with_max_depth(2);
+    assert!(parse_value_with_options(br#"[{"a":1}]"#, &opts).is_ok());
+    assert!(parse_value_with_options(br#"[{"a":[]}]"#, &opts).is_err());
+
+    let mut parser = jsonb::PushParser::with_options(&opts);
+    assert_eq!(
+        parser.feed(b"[[[]]]"),
+        Err(Error::Syntax(ParseErrorCode::RecursionLimitExceeded, 3))
+    );
+}
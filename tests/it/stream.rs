@@ -0,0 +1,338 @@
+// Copyright 2024 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::ErrorKind;
+use std::io::Read;
+
+use jsonb::{
+    parse_value, parse_value_from_reader, parse_value_with_options, parse_values_iter,
+    parse_values_iter_with_options, parse_values_reader, parse_values_reader_with_options,
+    to_string, DuplicateKeyPolicy, Error, ParseErrorCode, ParseOptions, ParseStatus, PushParser,
+};
+
+// A reader returns one byte at a time to test the buffer boundaries.
+struct ByteReader<'a> {
+    data: &'a [u8],
+}
+
+impl Read for ByteReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.data.is_empty() || buf.is_empty() {
+            return Ok(0);
+        }
+        buf[0] = self.data[0];
+        self.data = &self.data[1..];
+        Ok(1)
+    }
+}
+
+#[test]
+fn test_parse_values_iter() {
+    let sources = vec![
+        ("", vec![]),
+        (" \n ", vec![]),
+        ("1", vec!["1"]),
+        (
+            "{\"a\":1}\n[1,2]\n\"abc\"\n",
+            vec![r#"{"a":1}"#, "[1,2]", r#""abc""#],
+        ),
+        (
+            "\r\n123 true\tnull\n\n-1.5e3",
+            vec!["123", "true", "null", "-1500.0"],
+        ),
+        (
+            r#"{"a":1}{"b":2}[]"x""#,
+            vec![r#"{"a":1}"#, r#"{"b":2}"#, "[]", r#""x""#],
+        ),
+    ];
+    for (s, expected) in sources {
+        let values: Vec<_> = parse_values_iter(s.as_bytes())
+            .map(|v| v.unwrap().to_string())
+            .collect();
+        assert_eq!(values, expected);
+
+        let values: Vec<_> = parse_values_reader(s.as_bytes())
+            .map(|v| to_string(&v.unwrap()))
+            .collect();
+        assert_eq!(values, expected);
+
+        let reader = ByteReader { data: s.as_bytes() };
+        let values: Vec<_> = parse_values_reader(reader)
+            .map(|v| to_string(&v.unwrap()))
+            .collect();
+        assert_eq!(values, expected);
+    }
+}
+
+#[test]
+fn test_parse_values_iter_errors() {
+    let s = "{\"a\":1}\n  [1,,2] [3]\n\"abc\"\n{\"b\":";
+    let expected = vec![
+        Ok(r#"{"a":1}"#.to_string()),
+        Err(Error::Record(
+            1,
+            10,
+            Box::new(Error::Syntax(ParseErrorCode::ExpectedSomeValue, 4)),
+        )),
+        Ok(r#""abc""#.to_string()),
+        Err(Error::Record(
+            3,
+            27,
+            Box::new(Error::Syntax(ParseErrorCode::InvalidEOF, 5)),
+        )),
+    ];
+
+    let values: Vec<_> = parse_values_iter(s.as_bytes())
+        .map(|v| v.map(|v| v.to_string()))
+        .collect();
+    assert_eq!(values, expected);
+
+    let reader = ByteReader { data: s.as_bytes() };
+    let values: Vec<_> = parse_values_reader(reader)
+        .map(|v| v.map(|v| to_string(&v)))
+        .collect();
+    assert_eq!(values, expected);
+
+    let err = values[1].clone().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "expected value, pos 4, record 1 at offset 10"
+    );
+}
+
+// A reader that fails after returning the data.
+struct FailingReader<'a> {
+    data: &'a [u8],
+}
+
+impl Read for FailingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.data.is_empty() {
+            return Err(std::io::Error::from(ErrorKind::ConnectionReset));
+        }
+        let len = self.data.len().min(buf.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        Ok(len)
+    }
+}
+
+#[test]
+fn test_parse_values_reader_io_error() {
+    let reader = FailingReader { data: b"1\n[2" };
+    let values: Vec<_> = parse_values_reader(reader)
+        .take(5)
+        .map(|v| v.map(|v| to_string(&v)))
+        .collect();
+    assert_eq!(
+        values,
+        vec![
+            Ok("1".to_string()),
+            Err(Error::Record(
+                1,
+                2,
+                Box::new(Error::Io(ErrorKind::ConnectionReset))
+            )),
+        ]
+    );
+
+    let reader = FailingReader { data: b"[1, 2" };
+    assert_eq!(
+        parse_value_from_reader(reader),
+        Err(Error::Io(ErrorKind::ConnectionReset))
+    );
+}
+
+#[test]
+fn test_parse_values_iter_with_options() {
+    let s = "// header\n{a: 1,}\n/* next */ [NaN, 'x']";
+    let opts = ParseOptions::lenient();
+    let values: Vec<_> = parse_values_iter_with_options(s.as_bytes(), &opts)
+        .map(|v| v.unwrap())
+        .collect();
+    assert_eq!(values.len(), 2);
+    assert_eq!(values[0], parse_value(br#"{"a":1}"#).unwrap());
+    assert!(values[1].as_array().unwrap()[0].as_f64().unwrap().is_nan());
+
+    // the comments may be split at any byte between the reads
+    let reader = ByteReader { data: s.as_bytes() };
+    let values: Vec<_> = parse_values_reader_with_options(reader, &opts)
+        .map(|v| to_string(&v.unwrap()))
+        .collect();
+    assert_eq!(values, vec![r#"{"a":1}"#, "[NaN,\"x\"]"]);
+
+    // the reader is read in chunks of 8 KiB, split the comment openers at the end of the chunk
+    for source in ["[1, /* c */ 2]", "1 /* c */ 2", "1 // c\n2"] {
+        let expected: Vec<_> = parse_values_iter_with_options(source.as_bytes(), &opts)
+            .map(|v| v.unwrap().to_string())
+            .collect();
+        let split = source.find('/').unwrap();
+        for pad in [8191 - split, 8192 - split] {
+            let padded = format!("{}{}", " ".repeat(pad), source);
+            let values: Vec<_> = parse_values_reader_with_options(padded.as_bytes(), &opts)
+                .map(|v| to_string(&v.unwrap()))
+                .collect();
+            assert_eq!(values, expected, "source: {source}, pad: {pad}");
+        }
+    }
+}
+
+#[test]
+fn test_push_parser() {
+    let sources = vec![
+        r#"null"#,
+        r#"  -12.5e3  "#,
+        r#"123456789012345678901234567890"#,
+        r#""hello \"world\" \\""#,
+        r#"[]"#,
+        r#"{}"#,
+        r#"[1, "a", [true, false], {"k": null}]"#,
+        r#"{"b": [1, {"c": "\u4e2d\u6587"}], "a": {}, "d": 1.5}"#,
+        r#"{"a": 1, "a": 2}"#,
+    ];
+    for source in sources {
+        let expected = parse_value(source.as_bytes()).unwrap().to_vec();
+
+        let mut parser = PushParser::new();
+        parser.feed(source.as_bytes()).unwrap();
+        assert_eq!(parser.finish().unwrap(), expected);
+
+        // feed one byte at a time
+        let mut parser = PushParser::new();
+        for b in source.as_bytes() {
+            parser.feed(&[*b]).unwrap();
+        }
+        assert_eq!(parser.finish().unwrap(), expected);
+
+        let reader = ByteReader {
+            data: source.as_bytes(),
+        };
+        assert_eq!(parse_value_from_reader(reader).unwrap(), expected);
+    }
+
+    // numbers may continue in the next chunk
+    let mut parser = PushParser::new();
+    assert_eq!(parser.feed(b"12").unwrap(), ParseStatus::Incomplete);
+    assert_eq!(parser.feed(b"3").unwrap(), ParseStatus::Incomplete);
+    assert_eq!(to_string(&parser.finish().unwrap()), "123");
+
+    let mut parser = PushParser::new();
+    assert_eq!(parser.feed(b"[1, {\"a\"").unwrap(), ParseStatus::Incomplete);
+    assert_eq!(parser.feed(b": \"x\"}]").unwrap(), ParseStatus::Complete);
+    assert_eq!(parser.feed(b"  \n").unwrap(), ParseStatus::Complete);
+    assert_eq!(to_string(&parser.finish().unwrap()), r#"[1,{"a":"x"}]"#);
+}
+
+#[test]
+fn test_push_parser_errors() {
+    let sources = vec![
+        ("", Error::Syntax(ParseErrorCode::InvalidEOF, 0)),
+        ("[1, 2", Error::Syntax(ParseErrorCode::InvalidEOF, 5)),
+        ("\"abc", Error::Syntax(ParseErrorCode::InvalidEOF, 4)),
+        (
+            "[1 2]",
+            Error::Syntax(ParseErrorCode::ExpectedArrayCommaOrEnd, 3),
+        ),
+        ("{\"a\" 1}", Error::Syntax(ParseErrorCode::ExpectedColon, 5)),
+        (
+            "{\"a\": 1 2}",
+            Error::Syntax(ParseErrorCode::ExpectedObjectCommaOrEnd, 8),
+        ),
+        ("{1: 2}", Error::Syntax(ParseErrorCode::KeyMustBeAString, 2)),
+        ("[1,]", Error::Syntax(ParseErrorCode::ExpectedSomeValue, 4)),
+        (
+            "[1] 2",
+            Error::Syntax(ParseErrorCode::UnexpectedTrailingCharacters, 5),
+        ),
+    ];
+    for (source, expected) in sources {
+        let mut parser = PushParser::new();
+        let result = source
+            .as_bytes()
+            .iter()
+            .try_for_each(|b| parser.feed(&[*b]).map(|_| ()));
+        let err = match result {
+            Ok(()) => parser.finish().unwrap_err(),
+            Err(err) => err,
+        };
+        assert_eq!(err.to_string(), expected.to_string(), "source: {source}");
+        let err = parse_value(source.as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), expected.to_string(), "source: {source}");
+    }
+}
+
+#[test]
+fn test_push_parser_with_options() {
+    let opts = ParseOptions::lenient();
+    let source = r#"// comment
+    {unquoted: 'single', /* block */ "nan": NaN, arr: [1, -Infinity,],}"#;
+    let expected = parse_value_with_options(source.as_bytes(), &opts)
+        .unwrap()
+        .to_vec();
+    let mut parser = PushParser::with_options(&opts);
+    for b in source.as_bytes() {
+        parser.feed(&[*b]).unwrap();
+    }
+    assert_eq!(parser.finish().unwrap(), expected);
+
+    for (source, pos) in [("1 /* abc", 8), ("[1] /*", 6)] {
+        let mut parser = PushParser::with_options(&opts);
+        parser.feed(source.as_bytes()).unwrap();
+        assert_eq!(
+            parser.finish(),
+            Err(Error::Syntax(ParseErrorCode::InvalidEOF, pos))
+        );
+    }
+    // a delimiter in the key position is not a key
+    let sources = [
+        ("{:1}", opts),
+        ("{,}", opts),
+        ("{a:1,:2}", opts),
+        (r#"{"a":1,}"#, opts.with_trailing_commas(false)),
+    ];
+    for (source, opts) in sources {
+        let expected = parse_value_with_options(source.as_bytes(), &opts).unwrap_err();
+        assert!(matches!(
+            expected,
+            Error::Syntax(ParseErrorCode::ExpectedSomeValue, _)
+        ));
+        let mut parser = PushParser::with_options(&opts);
+        let result = parser.feed(source.as_bytes()).and_then(|_| parser.finish());
+        assert_eq!(result, Err(expected), "source: {source}");
+    }
+    let mut parser = PushParser::with_options(&opts);
+    assert_eq!(
+        parser.feed(b"{["),
+        Err(Error::Syntax(ParseErrorCode::ExpectedSomeValue, 2))
+    );
+
+    let mut parser = PushParser::with_options(&opts);
+    parser.feed(b"1 // abc").unwrap();
+    assert_eq!(to_string(&parser.finish().unwrap()), "1");
+
+    let source = br#"{"a": 1, "a": 2}"#;
+    let opts = ParseOptions::new().with_duplicate_key(DuplicateKeyPolicy::FirstWins);
+    let mut parser = PushParser::with_options(&opts);
+    parser.feed(source).unwrap();
+    assert_eq!(to_string(&parser.finish().unwrap()), r#"{"a":1}"#);
+
+    let opts = ParseOptions::new().with_duplicate_key(DuplicateKeyPolicy::Error);
+    let mut parser = PushParser::with_options(&opts);
+    let err = parser.feed(source).unwrap_err();
+    assert_eq!(
+        err,
+        Error::Syntax(ParseErrorCode::ObjectDuplicateKey("a".to_string()), 12)
+    );
+    assert_eq!(err.to_string(), "duplicate object key `a`, pos 12");
+}
@@ -0,0 +1,209 @@
+// Copyright 2024 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jsonb::{
+    build_object, from_slice, parse_value, parse_value_with_options, validate,
+    validate_with_max_depth, Date, ParseOptions, ValidationError, ValidationErrorCode, Value,
+};
+
+#[test]
+fn test_validate_valid() {
+    let sources = vec![
+        r#"null"#,
+        r#"true"#,
+        r#"-1.5"#,
+        r#""测试""#,
+        r#"[]"#,
+        r#"{}"#,
+        r#"[1,"a",null,false,[2,{"k":[]}]]"#,
+        r#"{"a":1,"b":{"c":[true,"d"],"":0},"e":-100000000000000000000}"#,
+    ];
+    for s in sources {
+        let value = parse_value(s.as_bytes()).unwrap();
+        assert_eq!(validate(&value.to_vec()), Ok(()), "{}", s);
+        assert_eq!(validate(&value.to_vec_with_version()), Ok(()), "{}", s);
+    }
+
+    // containers with offset JEntries
+    let array = Value::Array((0..100).map(|i| Value::from(i as u64)).collect());
+    let object = Value::Object(
+        (0..100)
+            .map(|i| (format!("k{:03}", i), Value::from(format!("v{}", i))))
+            .collect(),
+    );
+    assert_eq!(validate(&array.to_vec_with_version()), Ok(()));
+    assert_eq!(validate(&object.to_vec_with_version()), Ok(()));
+
+    // keys without the version header may be unsorted or duplicated,
+    // as written by `build_object` and the releases before the header
+    let items: Vec<(String, Vec<u8>)> = (0..40)
+        .rev()
+        .chain([5])
+        .map(|i| (format!("k{:02}", i), Value::from(i as u64).to_vec()))
+        .collect();
+    let mut buf = Vec::new();
+    build_object(items.iter().map(|(k, v)| (k, v.as_slice())), &mut buf).unwrap();
+    assert_eq!(validate(&buf), Ok(()));
+    assert_eq!(
+        validate(b"\x40\0\0\x02\x10\0\0\x01\x10\0\0\x01\0\0\0\0\0\0\0\0ba"),
+        Ok(())
+    );
+
+    let ext = Value::Array(vec![Value::Date(Date { value: 10 }), Value::Null]);
+    assert_eq!(validate(&ext.to_vec()), Ok(()));
+}
+
+#[test]
+fn test_validate_invalid() {
+    let tests: Vec<(&[u8], ValidationErrorCode, usize)> = vec![
+        (b"", ValidationErrorCode::UnexpectedEOF, 0),
+        (b"\x20\0\0\0", ValidationErrorCode::UnexpectedEOF, 4),
+        (
+            b"\x20\0\0\x01\0\0\0\0",
+            ValidationErrorCode::InvalidHeader,
+            0,
+        ),
+        (
+            b"\xFFJB\x09\x20\0\0\0\0\0\0\0",
+            ValidationErrorCode::InvalidVersion,
+            3,
+        ),
+        (b"null", ValidationErrorCode::InvalidHeader, 0),
+        // scalar
+        (
+            b"\x20\0\0\0\x70\0\0\0",
+            ValidationErrorCode::InvalidJEntry,
+            4,
+        ),
+        (
+            b"\x20\0\0\0\x00\0\0\x01\0",
+            ValidationErrorCode::InvalidLength,
+            4,
+        ),
+        (
+            b"\x20\0\0\0\x10\0\0\x03ab",
+            ValidationErrorCode::InvalidLength,
+            4,
+        ),
+        (
+            b"\x20\0\0\0\x10\0\0\x02a\xFF",
+            ValidationErrorCode::InvalidUtf8,
+            9,
+        ),
+        (
+            b"\x20\0\0\0\x20\0\0\0",
+            ValidationErrorCode::InvalidNumber,
+            8,
+        ),
+        (
+            b"\x20\0\0\0\x20\0\0\x02\x60\x01",
+            ValidationErrorCode::InvalidNumber,
+            8,
+        ),
+        (
+            b"\x20\0\0\0\x60\0\0\x02\x10\x01",
+            ValidationErrorCode::InvalidExtension,
+            8,
+        ),
+        (
+            b"\x20\0\0\0\x00\0\0\0\0",
+            ValidationErrorCode::TrailingBytes,
+            8,
+        ),
+        (
+            b"\x20\0\0\0\x50\0\0\0",
+            ValidationErrorCode::InvalidJEntry,
+            4,
+        ),
+        // array
+        (
+            b"\x80\0\0\x02\0\0\0\0",
+            ValidationErrorCode::InvalidLength,
+            0,
+        ),
+        (
+            b"\x80\0\0\x01\x50\0\0\x08\x20\0\0\0\0\0\0\0",
+            ValidationErrorCode::InvalidHeader,
+            8,
+        ),
+        // object
+        (
+            b"\x40\0\0\x01\x20\0\0\x01\0\0\0\0\0\0",
+            ValidationErrorCode::KeyMustBeAString,
+            4,
+        ),
+        // keys must be sorted and unique with the version header
+        (
+            b"\xFFJB\x02\x40\0\0\x02\x10\0\0\x01\x10\0\0\x01\0\0\0\0\0\0\0\0ba",
+            ValidationErrorCode::UnsortedKeys,
+            25,
+        ),
+        (
+            b"\xFFJB\x02\x40\0\0\x02\x10\0\0\x01\x10\0\0\x01\0\0\0\0\0\0\0\0aa",
+            ValidationErrorCode::DuplicateKey,
+            25,
+        ),
+    ];
+    for (buf, code, offset) in tests {
+        assert_eq!(
+            validate(buf),
+            Err(ValidationError { code, offset }),
+            "{:?}",
+            buf
+        );
+    }
+}
+
+#[test]
+fn test_validate_depth() {
+    let nested = |depth: usize| {
+        let s = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        let opts = ParseOptions::new().with_max_depth(depth);
+        parse_value_with_options(s.as_bytes(), &opts)
+            .unwrap()
+            .to_vec()
+    };
+    assert_eq!(validate(&nested(128)), Ok(()));
+    let err = validate(&nested(129)).unwrap_err();
+    assert_eq!(err.code, ValidationErrorCode::DepthLimitExceeded);
+    // the innermost array
+    assert_eq!(err.offset, 128 * 8);
+
+    assert_eq!(validate_with_max_depth(&nested(3), 3), Ok(()));
+    assert_eq!(
+        validate_with_max_depth(&nested(3), 2),
+        Err(ValidationError {
+            code: ValidationErrorCode::DepthLimitExceeded,
+            offset: 16,
+        })
+    );
+}
+
+#[test]
+fn test_validate_corrupted() {
+    let value =
+        parse_value(r#"{"a":[1,2.5,"x"],"b":{"c":null,"d":true},"e":"测试"}"#.as_bytes()).unwrap();
+    let buf = value.to_vec();
+    // every corrupted value is either rejected, or can be decoded
+    for i in 0..buf.len() {
+        for b in [0x00, 0x01, 0x7F, 0x80, 0xFF] {
+            let mut corrupted = buf.clone();
+            corrupted[i] = b;
+            if validate(&corrupted).is_ok() {
+                assert!(from_slice(&corrupted).is_ok(), "{:?}", corrupted);
+            }
+        }
+        assert!(validate(&buf[..i]).is_err());
+    }
+}